@@ -1,7 +1,14 @@
 use cope_config::config::TmpConfig;
 use cope_config::file_generator::generate;
+use cope_config::types::coding_scheduler_type::CodingSchedulerType;
+use cope_config::types::coding_scheme::CodingScheme;
+use cope_config::types::discovery_type::DiscoveryType;
+use cope_config::types::election_type::ElectionType;
+use cope_config::types::fairness_type::FairnessType;
+use cope_config::types::link_loss_type::LinkLossType;
 use cope_config::types::mac_address::MacAddress;
 use cope_config::types::node_id::NodeID;
+use cope_config::types::routing_type::RoutingType;
 use cope_config::types::traffic_generator_type::TrafficGeneratorType;
 use parse_duration;
 use serde::Deserialize;
@@ -12,19 +19,34 @@ use std::str::FromStr;
 #[derive(Deserialize)]
 struct TOMLConfig {
     nodes: Vec<(String, String)>,
-    relay: String,
+    relays: Vec<String>,
     rx_whitelist: Vec<(String, Vec<String>)>,
     tx_whitelist: Vec<(String, Vec<String>)>,
+    routes: Vec<(String, Vec<(String, String)>)>,
+    routing: String,
+    discovery: String,
+    neighbor_denylist: Vec<(String, Vec<String>)>,
+    election: String,
     traffic_generators: Vec<(String, String)>,
     simulator_packet_loss: f64,
+    simulator_link_loss: Vec<(String, String, String)>,
+    simulator_node_failures: Vec<(String, String)>,
     round_trip_time: String,
     packet_pool_size: usize,
+    max_packet_size: usize,
     control_packet_duration: String,
     max_retrans_amount: u8,
     use_coding: bool,
+    use_nacks: bool,
+    coding_scheduler: String,
+    hybrid_fairness: String,
+    guess_threshold: f64,
+    coding_scheme: String,
+    generation_size: usize,
     stats_log_duration: String,
     log_node_stats: bool,
     log_espnow_stats: bool,
+    network_key: String,
 }
 
 fn main() {
@@ -56,10 +78,19 @@ fn main() {
         })
         .collect();
 
-    let relay = NodeID::from_str(&toml_config.relay)
-        .unwrap_or_else(|e| panic!("Node ID {} is invalid: {}.", toml_config.relay, e));
+    let relays: Vec<NodeID> = toml_config
+        .relays
+        .iter()
+        .map(|relay| {
+            NodeID::from_str(relay)
+                .unwrap_or_else(|e| panic!("Node ID {} is invalid: {}.", relay, e))
+        })
+        .collect();
+    if relays.is_empty() {
+        panic!("Relays are invalid: there has to be at least one relay.");
+    }
 
-    let rx_whitelist = toml_config
+    let rx_whitelist: Vec<(NodeID, Vec<NodeID>)> = toml_config
         .rx_whitelist
         .iter()
         .map(|(node, list)| {
@@ -93,7 +124,60 @@ fn main() {
         })
         .collect();
 
-    let traffic_generators = toml_config
+    let parse_id = |id: &String| {
+        NodeID::from_str(id).unwrap_or_else(|e| panic!("Node ID {} is invalid: {}.", id, e))
+    };
+    let routes: Vec<(NodeID, Vec<(NodeID, NodeID)>)> = toml_config
+        .routes
+        .iter()
+        .map(|(node, list)| {
+            (
+                parse_id(node),
+                list.iter()
+                    .map(|(target, nexthop)| (parse_id(target), parse_id(nexthop)))
+                    .collect(),
+            )
+        })
+        .collect();
+    // NOTE: The nexthop has to be able to hear us, otherwise the route is a dead end
+    for (node, list) in routes.iter() {
+        for (target, nexthop) in list {
+            let can_receive = rx_whitelist
+                .iter()
+                .find(|(n, _)| n == nexthop)
+                .is_some_and(|(_, rx)| rx.contains(node));
+            if !can_receive {
+                panic!(
+                    "Route from {} to {} is invalid: {} can not receive from {}.",
+                    node, target, nexthop, node
+                );
+            }
+        }
+    }
+
+    let routing = RoutingType::from_str(&toml_config.routing)
+        .unwrap_or_else(|e| panic!("Routing {} is invalid: {}.", toml_config.routing, e));
+    let discovery = DiscoveryType::from_str(&toml_config.discovery)
+        .unwrap_or_else(|e| panic!("Discovery {} is invalid: {}.", toml_config.discovery, e));
+    let neighbor_denylist = toml_config
+        .neighbor_denylist
+        .iter()
+        .map(|(node, list)| (parse_id(node), list.iter().map(parse_id).collect()))
+        .collect();
+    let election = ElectionType::from_str(&toml_config.election)
+        .unwrap_or_else(|e| panic!("Election {} is invalid: {}.", toml_config.election, e));
+    // NOTE: Candidates are only reachable, if they are discovered, and routes
+    // learned by routing::Router assume that the relays don't change
+    let is_supported =
+        matches!(discovery, DiscoveryType::Beacon(..)) && routing == RoutingType::Static;
+    if election != ElectionType::Static && !is_supported {
+        panic!(
+            "Election {} is invalid: requires Beacon discovery and Static routing.",
+            election
+        );
+    }
+
+    let traffic_generators: Vec<(NodeID, TrafficGeneratorType)> = toml_config
         .traffic_generators
         .iter()
         .map(|(node, tgt)| {
@@ -107,32 +191,129 @@ fn main() {
         })
         .collect();
     let simulator_packet_loss = toml_config.simulator_packet_loss;
+    let simulator_link_loss = toml_config
+        .simulator_link_loss
+        .iter()
+        .map(|(a, b, loss)| {
+            (
+                parse_id(a),
+                parse_id(b),
+                LinkLossType::from_str(loss)
+                    .unwrap_or_else(|e| panic!("Link loss {} is invalid: {}.", loss, e)),
+            )
+        })
+        .collect();
+    let simulator_node_failures = toml_config
+        .simulator_node_failures
+        .iter()
+        .map(|(node, time)| {
+            let time = parse_duration::parse(time)
+                .unwrap_or_else(|e| panic!("Failure time {} is invalid: {}.", time, e));
+            (parse_id(node), time)
+        })
+        .collect();
     let round_trip_time = parse_duration::parse(&toml_config.round_trip_time).unwrap();
     let packet_pool_size = toml_config.packet_pool_size;
+    let max_packet_size = toml_config.max_packet_size;
     let control_packet_duration =
         parse_duration::parse(&toml_config.control_packet_duration).unwrap();
     let max_retrans_amount = toml_config.max_retrans_amount;
     let use_coding = toml_config.use_coding;
+    let use_nacks = toml_config.use_nacks;
+    let coding_scheduler = CodingSchedulerType::from_str(&toml_config.coding_scheduler)
+        .unwrap_or_else(|e| {
+            panic!("Coding scheduler {} is invalid: {}.", toml_config.coding_scheduler, e)
+        });
+    let hybrid_fairness = FairnessType::from_str(&toml_config.hybrid_fairness)
+        .unwrap_or_else(|e| {
+            panic!("Fairness {} is invalid: {}.", toml_config.hybrid_fairness, e)
+        });
+    let guess_threshold = toml_config.guess_threshold;
+    if !(0.0..=1.0).contains(&guess_threshold) {
+        panic!("Guess threshold {} is invalid: must be between 0 and 1.", guess_threshold);
+    }
+    let coding_scheme = CodingScheme::from_str(&toml_config.coding_scheme).unwrap_or_else(|e| {
+        panic!("Coding scheme {} is invalid: {}.", toml_config.coding_scheme, e)
+    });
+    // NOTE: RLNC relays recode generations without decoding them, so they can
+    // not forward them to another relay, see coding::rlnc_leaf_coding. Elected
+    // relays are fine, there is only one at a time.
+    let is_single_relay = relays.len() == 1 || election != ElectionType::Static;
+    if coding_scheme == CodingScheme::Rlnc && !is_single_relay {
+        panic!(
+            "Coding scheme {} is invalid: RLNC only supports a single relay.",
+            coding_scheme
+        );
+    }
+    // NOTE: Relays with a traffic generator use hybrid_fairness, RLNC relays
+    // only recode and can't generate traffic
+    for (node, tgt) in traffic_generators.iter() {
+        let is_source = !matches!(tgt, TrafficGeneratorType::None);
+        if coding_scheme == CodingScheme::Rlnc && is_source && relays.contains(node) {
+            panic!(
+                "Relay {} is invalid: only COPE relays can generate traffic.",
+                node
+            );
+        }
+    }
+    // NOTE: Coefficient vectors are limited to 255 entries on the wire
+    let generation_size = toml_config.generation_size;
+    if generation_size == 0 || generation_size > 255 {
+        panic!("Generation size {} is invalid: must be between 1 and 255.", generation_size);
+    }
     let stats_log_duration = parse_duration::parse(&toml_config.stats_log_duration).unwrap();
     let log_node_stats = toml_config.log_node_stats;
     let log_espnow_stats = toml_config.log_espnow_stats;
+    // NOTE: An empty key disables packet protection
+    let network_key = match toml_config.network_key.as_str() {
+        "" => None,
+        key => Some(parse_key(key).unwrap_or_else(|e| panic!("Network key is invalid: {}.", e))),
+    };
 
-    let config = TmpConfig::new(
+    let config = TmpConfig {
         nodes,
-        relay,
+        relays,
         rx_whitelist,
         tx_whitelist,
+        routes,
         traffic_generators,
+        simulator_link_loss,
+        routing,
+        neighbor_denylist,
+        discovery,
+        election,
+        simulator_node_failures,
         simulator_packet_loss,
         round_trip_time,
         packet_pool_size,
+        max_packet_size,
         control_packet_duration,
         max_retrans_amount,
         use_coding,
+        use_nacks,
+        coding_scheduler,
+        hybrid_fairness,
+        guess_threshold,
+        coding_scheme,
+        generation_size,
         stats_log_duration,
         log_node_stats,
         log_espnow_stats,
-    );
+        network_key,
+    };
     let dest_path = "src/config.rs";
     generate(&config, &dest_path.to_string());
 }
+
+fn parse_key(key: &str) -> Result<[u8; 32], String> {
+    if key.len() != 64 || !key.is_ascii() {
+        return Err(format!("expected 64 hex digits, got {:?}", key));
+    }
+
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&key[2 * i..2 * i + 2], 16)
+            .map_err(|_| format!("{:?} is not a hex byte", &key[2 * i..2 * i + 2]))?;
+    }
+    Ok(bytes)
+}
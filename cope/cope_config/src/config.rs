@@ -1,76 +1,64 @@
 use std::time::Duration;
 
+use crate::types::coding_scheduler_type::CodingSchedulerType;
+use crate::types::coding_scheme::CodingScheme;
+use crate::types::discovery_type::DiscoveryType;
+use crate::types::election_type::ElectionType;
+use crate::types::fairness_type::FairnessType;
+use crate::types::link_loss_type::LinkLossType;
 use crate::types::mac_address::MacAddress;
 use crate::types::node_id::NodeID;
+use crate::types::routing_type::RoutingType;
 use crate::types::traffic_generator_type::TrafficGeneratorType;
 
 trait CopeConfig {}
 
+// NOTE: Settings parsed by build.rs, that file_generator::generate writes to
+// src/config.rs as a Config
 #[derive(Debug)]
 pub struct TmpConfig {
-    node_count: usize,
-    nodes: Vec<(NodeID, MacAddress)>,
-    relay: NodeID,
-    rx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
-    tx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
-    traffic_generators: Vec<(NodeID, TrafficGeneratorType)>,
+    pub nodes: Vec<(NodeID, MacAddress)>,
+    pub relays: Vec<NodeID>,
+    pub rx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
+    pub tx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
+    pub routes: Vec<(NodeID, Vec<(NodeID, NodeID)>)>,
+    pub traffic_generators: Vec<(NodeID, TrafficGeneratorType)>,
+    pub simulator_link_loss: Vec<(NodeID, NodeID, LinkLossType)>,
+    pub routing: RoutingType,
+    pub neighbor_denylist: Vec<(NodeID, Vec<NodeID>)>,
+    pub discovery: DiscoveryType,
+    pub election: ElectionType,
+    pub simulator_node_failures: Vec<(NodeID, Duration)>,
     pub simulator_packet_loss: f64,
     pub round_trip_time: Duration,
     pub packet_pool_size: usize,
+    pub max_packet_size: usize,
     pub control_packet_duration: Duration,
     pub max_retrans_amount: u8,
     pub use_coding: bool,
+    pub use_nacks: bool,
+    pub coding_scheduler: CodingSchedulerType,
+    pub hybrid_fairness: FairnessType,
+    pub guess_threshold: f64,
+    pub coding_scheme: CodingScheme,
+    pub generation_size: usize,
     pub stats_log_duration: Duration,
     pub log_node_stats: bool,
     pub log_espnow_stats: bool,
+    pub network_key: Option<[u8; 32]>,
 }
 
 impl TmpConfig {
-    pub fn new(
-        nodes: Vec<(NodeID, MacAddress)>,
-        relay: NodeID,
-        rx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
-        tx_whitelist: Vec<(NodeID, Vec<NodeID>)>,
-        traffic_generators: Vec<(NodeID, TrafficGeneratorType)>,
-        simulator_packet_loss: f64,
-        round_trip_time: Duration,
-        packet_pool_size: usize,
-        control_packet_duration: Duration,
-        max_retrans_amount: u8,
-        use_coding: bool,
-        stats_log_duration: Duration,
-        log_node_stats: bool,
-        log_espnow_stats: bool,
-    ) -> Self {
-        Self {
-            node_count: nodes.len(),
-            nodes,
-            relay,
-            rx_whitelist,
-            tx_whitelist,
-            traffic_generators,
-            simulator_packet_loss,
-            round_trip_time,
-            packet_pool_size,
-            control_packet_duration,
-            max_retrans_amount,
-            use_coding,
-            stats_log_duration,
-            log_node_stats,
-            log_espnow_stats,
-        }
-    }
-
     pub fn node_count(&self) -> usize {
-        self.node_count
+        self.nodes.len()
     }
 
     pub fn nodes(&self) -> &Vec<(NodeID, MacAddress)> {
         &self.nodes
     }
 
-    pub fn relay(&self) -> NodeID {
-        self.relay
+    pub fn relays(&self) -> &Vec<NodeID> {
+        &self.relays
     }
 
     pub fn rx_whitelist(&self) -> &Vec<(NodeID, Vec<NodeID>)> {
@@ -81,29 +69,84 @@ impl TmpConfig {
         &self.tx_whitelist
     }
 
+    pub fn routes(&self) -> &Vec<(NodeID, Vec<(NodeID, NodeID)>)> {
+        &self.routes
+    }
+
     pub fn traffic_generators(&self) -> &Vec<(NodeID, TrafficGeneratorType)> {
         &self.traffic_generators
     }
+
+    pub fn simulator_link_loss(&self) -> &Vec<(NodeID, NodeID, LinkLossType)> {
+        &self.simulator_link_loss
+    }
+
+    pub fn neighbor_denylist(&self) -> &Vec<(NodeID, Vec<NodeID>)> {
+        &self.neighbor_denylist
+    }
+
+    pub fn simulator_node_failures(&self) -> &Vec<(NodeID, Duration)> {
+        &self.simulator_node_failures
+    }
 }
 
 impl CopeConfig for TmpConfig {}
 
+// NOTE: Routes of a single node, see Config::routes
+pub type RouteList<const N: usize> = [Option<(NodeID, NodeID)>; N];
+
 pub struct Config<const N: usize> {
     pub nodes: [(NodeID, MacAddress); N],
-    pub relay: NodeID,
+    pub relays: [Option<NodeID>; N],
     // we technically only need N-1 nodes here but yeah
+    // NOTE: With beacon discovery, the whitelists only filter the discovered
+    // neighbors and destinations, and empty lists don't filter at all
     pub rx_whitelist: [(NodeID, [Option<NodeID>; N]); N],
     pub tx_whitelist: [(NodeID, [Option<NodeID>; N]); N],
+    // NOTE: Pairs of destination and nexthop, destinations without a route
+    // are reached like in the star topology, see topology::Topology
+    pub routes: [(NodeID, RouteList<N>); N],
     pub traffic_generators: [(NodeID, TrafficGeneratorType); N],
+    // NOTE: Routes are either only the static ones above, or learned from
+    // link state adverts, see RoutingType
+    pub routing: RoutingType,
+    // NOTE: Neighbors are either only the ones in the whitelists, or learned
+    // from beacons, see DiscoveryType
+    pub discovery: DiscoveryType,
+    // NOTE: The relay is either the first of the relays above, or elected
+    // among them at runtime, see ElectionType
+    pub election: ElectionType,
+    // NOTE: Nodes that are never accepted as neighbors, with any discovery
+    pub neighbor_denylist: &'static [(NodeID, &'static [NodeID])],
     pub simulator_packet_loss: f64,
+    // NOTE: Loss of single links, it applies to both directions and replaces
+    // simulator_packet_loss for them
+    pub simulator_link_loss: &'static [(NodeID, NodeID, LinkLossType)],
+    // NOTE: Nodes that the simulator stops after the given time
+    pub simulator_node_failures: &'static [(NodeID, Duration)],
     pub round_trip_time: Duration,
     pub control_packet_duration: Duration,
     pub packet_pool_size: usize,
+    pub max_packet_size: usize,
     pub max_retrans_amount: u8,
     pub use_coding: bool,
+    // NOTE: Leaves ask the relay to resend packets they could not decode,
+    // instead of waiting for the retransmission after round_trip_time.
+    pub use_nacks: bool,
+    pub coding_scheduler: CodingSchedulerType,
+    // NOTE: Only used by relays with a traffic generator, see FairnessType
+    pub hybrid_fairness: FairnessType,
+    // NOTE: The relay guesses that a neighbor has overheard a packet, if the
+    // estimated probability of decoding is at least this. 1.0 disables guessing.
+    pub guess_threshold: f64,
+    pub coding_scheme: CodingScheme,
+    // NOTE: Number of natives that are coded together by RLNC
+    pub generation_size: usize,
     pub stats_log_duration: Duration,
     pub log_node_stats: bool,
     pub log_espnow_stats: bool,
+    // NOTE: Pre-shared key of the network, packets are only protected if it is set
+    pub network_key: Option<[u8; 32]>,
 }
 
 impl<const N: usize> CopeConfig for Config<N> {}
@@ -129,6 +172,10 @@ impl<const N: usize> Config<N> {
         None
     }
 
+    pub fn get_relays(&self) -> Vec<NodeID> {
+        self.relays.iter().filter_map(|opt| *opt).collect()
+    }
+
     pub fn get_rx_whitelist_for(&self, id: NodeID) -> Option<Vec<NodeID>> {
         self.rx_whitelist
             .iter()
@@ -143,6 +190,36 @@ impl<const N: usize> Config<N> {
             .map(|(_, list)| list.iter().filter_map(|opt| *opt).collect::<Vec<NodeID>>())
     }
 
+    pub fn get_routes_for(&self, id: NodeID) -> Vec<(NodeID, NodeID)> {
+        self.routes
+            .iter()
+            .find(|&&(node, _)| id == node)
+            .map(|(_, list)| list.iter().filter_map(|opt| *opt).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_denylist_for(&self, id: NodeID) -> Vec<NodeID> {
+        self.neighbor_denylist
+            .iter()
+            .find(|&&(node, _)| id == node)
+            .map(|(_, list)| list.to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn get_link_loss_for(&self, a: NodeID, b: NodeID) -> Option<LinkLossType> {
+        self.simulator_link_loss
+            .iter()
+            .find(|&&(x, y, _)| (x, y) == (a, b) || (x, y) == (b, a))
+            .map(|&(_, _, loss)| loss)
+    }
+
+    pub fn get_failure_time_for(&self, id: NodeID) -> Option<Duration> {
+        self.simulator_node_failures
+            .iter()
+            .find(|&&(node, _)| id == node)
+            .map(|&(_, time)| time)
+    }
+
     pub fn get_generator_type_for(&self, id: NodeID) -> Option<TrafficGeneratorType> {
         for i in 0..N {
             if self.traffic_generators[i].0 == id {
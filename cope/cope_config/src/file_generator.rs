@@ -3,8 +3,12 @@
 // User can specify the location of the output file
 
 use crate::config::TmpConfig;
+use crate::types::coding_scheduler_type::CodingSchedulerType;
+use crate::types::discovery_type::DiscoveryType;
+use crate::types::link_loss_type::LinkLossType;
 use crate::types::mac_address::MacAddress;
 use crate::types::node_id::NodeID;
+use crate::types::routing_type::RoutingType;
 use crate::types::traffic_generator_type::TrafficGeneratorType;
 use std::fs;
 use std::io::Write;
@@ -20,8 +24,30 @@ pub fn generate(config: &TmpConfig, path: &String) {
     )
     .unwrap();
     writeln!(file, "use cope_config::config::*;").unwrap();
+    writeln!(
+        file,
+        "use cope_config::types::coding_scheduler_type::CodingSchedulerType;"
+    )
+    .unwrap();
+    writeln!(file, "use cope_config::types::coding_scheme::CodingScheme;").unwrap();
+    writeln!(
+        file,
+        "use cope_config::types::discovery_type::DiscoveryType;"
+    )
+    .unwrap();
+    writeln!(file, "use cope_config::types::election_type::ElectionType;").unwrap();
+    writeln!(file, "use cope_config::types::fairness_type::FairnessType;").unwrap();
+    // NOTE: Only needed if there are lossy links, see write_link_loss
+    if !config.simulator_link_loss().is_empty() {
+        writeln!(
+            file,
+            "use cope_config::types::link_loss_type::LinkLossType;"
+        )
+        .unwrap();
+    }
     writeln!(file, "use cope_config::types::node_id::NodeID;").unwrap();
     writeln!(file, "use cope_config::types::mac_address::MacAddress;").unwrap();
+    writeln!(file, "use cope_config::types::routing_type::RoutingType;").unwrap();
     writeln!(
         file,
         "use cope_config::types::traffic_generator_type::TrafficGeneratorType;"
@@ -35,6 +61,14 @@ pub fn generate(config: &TmpConfig, path: &String) {
         config.simulator_packet_loss
     )
     .unwrap();
+    writeln!(file, "    routing: {},", routing_to_string(&config.routing)).unwrap();
+    writeln!(
+        file,
+        "    discovery: {},",
+        discovery_to_string(&config.discovery)
+    )
+    .unwrap();
+    writeln!(file, "    election: ElectionType::{:?},", config.election).unwrap();
     writeln!(
         file,
         "    round_trip_time: Duration::new({}, {}),",
@@ -58,7 +92,34 @@ pub fn generate(config: &TmpConfig, path: &String) {
     .unwrap();
 
     writeln!(file, "    use_coding: {},", config.use_coding).unwrap();
+    writeln!(file, "    use_nacks: {},", config.use_nacks).unwrap();
+    writeln!(
+        file,
+        "    coding_scheduler: {},",
+        scheduler_to_string(&config.coding_scheduler)
+    )
+    .unwrap();
+    writeln!(
+        file,
+        "    hybrid_fairness: FairnessType::{:?},",
+        config.hybrid_fairness
+    )
+    .unwrap();
+    writeln!(
+        file,
+        "    guess_threshold: {:.3},",
+        config.guess_threshold
+    )
+    .unwrap();
+    writeln!(
+        file,
+        "    coding_scheme: CodingScheme::{:?},",
+        config.coding_scheme
+    )
+    .unwrap();
+    writeln!(file, "    generation_size: {},", config.generation_size).unwrap();
     writeln!(file, "    packet_pool_size: {},", config.packet_pool_size).unwrap();
+    writeln!(file, "    max_packet_size: {},", config.max_packet_size).unwrap();
     writeln!(
         file,
         "    stats_log_duration: Duration::new({}, {}),",
@@ -68,12 +129,17 @@ pub fn generate(config: &TmpConfig, path: &String) {
     .unwrap();
     writeln!(file, "    log_node_stats: {},", config.log_node_stats).unwrap();
     writeln!(file, "    log_espnow_stats: {},", config.log_espnow_stats).unwrap();
+    writeln!(file, "    network_key: {:?},", config.network_key).unwrap();
 
     write_nodes(&mut file, config);
-    write_relay(&mut file, config);
+    write_relays(&mut file, config, node_count);
     write_whitelist(&mut file, config, node_count, "rx_whitelist");
     write_whitelist(&mut file, config, node_count, "tx_whitelist");
+    write_routes(&mut file, config, node_count);
     write_traffic_generators(&mut file, config);
+    write_link_loss(&mut file, config);
+    write_denylist(&mut file, config);
+    write_node_failures(&mut file, config);
     writeln!(file, "}};").unwrap();
 }
 
@@ -89,9 +155,9 @@ fn write_nodes(file: &mut fs::File, config: &TmpConfig) {
     writeln!(file, "    nodes: {},", nodes).unwrap();
 }
 
-fn write_relay(file: &mut fs::File, config: &TmpConfig) {
-    let relay = node_id_to_string(&config.relay());
-    writeln!(file, "    relay: {},", relay).unwrap();
+fn write_relays(file: &mut fs::File, config: &TmpConfig, node_count: usize) {
+    let relays = node_list_to_string(config.relays(), node_count);
+    writeln!(file, "    relays: {},", relays).unwrap();
 }
 
 fn write_whitelist(file: &mut fs::File, config: &TmpConfig, node_count: usize, key: &str) {
@@ -101,12 +167,15 @@ fn write_whitelist(file: &mut fs::File, config: &TmpConfig, node_count: usize, k
         _ => panic!("Invalid key {}", key),
     };
 
+    // NOTE: Discovered neighbors don't have to be listed, see Config::rx_whitelist
+    let empty = vec![];
     let mut s = String::new();
     s.push_str("[\n");
     for (node, _) in config.nodes() {
         let node_id = node_id_to_string(node);
         let list = match source.iter().find(|(n, _)| *n == *node).map(|(_, l)| l) {
             Some(res) => res,
+            None if config.discovery != DiscoveryType::Static => &empty,
             None => panic!("Did not find Node {} in {}", node_id, key),
         };
         let bl_atr = node_list_to_string(list, node_count);
@@ -116,6 +185,36 @@ fn write_whitelist(file: &mut fs::File, config: &TmpConfig, node_count: usize, k
     writeln!(file, "    {}: {},", key, s).unwrap();
 }
 
+// NOTE: Unlike the whitelists, nodes don't need to have routes
+fn write_routes(file: &mut fs::File, config: &TmpConfig, node_count: usize) {
+    let mut s = String::new();
+    s.push_str("[\n");
+    for (node, _) in config.nodes() {
+        let routes = config
+            .routes()
+            .iter()
+            .find(|(n, _)| *n == *node)
+            .map(|(_, l)| l.as_slice())
+            .unwrap_or_default();
+        assert!(node_count >= routes.len());
+
+        let mut list = String::new();
+        list.push_str("[\n            ");
+        for (target, nexthop) in routes {
+            let target = node_id_to_string(target);
+            let nexthop = node_id_to_string(nexthop);
+            list.push_str(&format!("Some(({}, {})),\n            ", target, nexthop));
+        }
+        for _ in 0..(node_count - routes.len()) {
+            list.push_str("None, ");
+        }
+        list.push_str("\n        ]");
+        s.push_str(&format!("        ({}, {}),\n", node_id_to_string(node), list));
+    }
+    s.push_str("    ]");
+    writeln!(file, "    routes: {},", s).unwrap();
+}
+
 fn write_traffic_generators(file: &mut fs::File, config: &TmpConfig) {
     let mut tgs = String::new();
     tgs.push_str("[\n");
@@ -129,6 +228,49 @@ fn write_traffic_generators(file: &mut fs::File, config: &TmpConfig) {
     writeln!(file, "    traffic_generators: {},", tgs).unwrap();
 }
 
+// NOTE: The amount of lossy links is not bounded by the node count, so they are
+// written as a slice instead of an array
+fn write_link_loss(file: &mut fs::File, config: &TmpConfig) {
+    let mut s = String::new();
+    s.push_str("&[\n");
+    for (a, b, loss) in config.simulator_link_loss() {
+        let a = node_id_to_string(a);
+        let b = node_id_to_string(b);
+        let loss = link_loss_to_string(loss);
+        s.push_str(&format!("        ({}, {}, {}),\n", a, b, loss));
+    }
+    s.push_str("    ]");
+    writeln!(file, "    simulator_link_loss: {},", s).unwrap();
+}
+
+fn write_denylist(file: &mut fs::File, config: &TmpConfig) {
+    let mut s = String::new();
+    s.push_str("&[\n");
+    for (node, list) in config.neighbor_denylist() {
+        let list = list
+            .iter()
+            .map(node_id_to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let node = node_id_to_string(node);
+        s.push_str(&format!("        ({}, &[{}]),\n", node, list));
+    }
+    s.push_str("    ]");
+    writeln!(file, "    neighbor_denylist: {},", s).unwrap();
+}
+
+fn write_node_failures(file: &mut fs::File, config: &TmpConfig) {
+    let mut s = String::new();
+    s.push_str("&[\n");
+    for (node, time) in config.simulator_node_failures() {
+        let node = node_id_to_string(node);
+        let time = format!("Duration::new({}, {})", time.as_secs(), time.subsec_nanos());
+        s.push_str(&format!("        ({}, {}),\n", node, time));
+    }
+    s.push_str("    ]");
+    writeln!(file, "    simulator_node_failures: {},", s).unwrap();
+}
+
 fn node_id_to_string(node_id: &NodeID) -> String {
     return format!("NodeID::new('{}')", node_id.to_string());
 }
@@ -158,6 +300,58 @@ fn tgt_to_string(tgt: &TrafficGeneratorType) -> String {
     return format!("TrafficGeneratorType::{}", serialized);
 }
 
+fn scheduler_to_string(scheduler: &CodingSchedulerType) -> String {
+    let duration = |d: &std::time::Duration| {
+        format!("Duration::new({}, {})", d.as_secs(), d.subsec_nanos())
+    };
+    let serialized = match scheduler {
+        CodingSchedulerType::Immediate => "Immediate".into(),
+        CodingSchedulerType::WaitForPartner(d) => format!("WaitForPartner({})", duration(d)),
+        CodingSchedulerType::QueueLength(l) => format!("QueueLength({})", l),
+        CodingSchedulerType::MaxGain(d) => format!("MaxGain({})", duration(d)),
+    };
+
+    format!("CodingSchedulerType::{}", serialized)
+}
+
+fn routing_to_string(routing: &RoutingType) -> String {
+    let serialized = match routing {
+        RoutingType::Static => "Static".into(),
+        RoutingType::Etx(d) => format!("Etx(Duration::new({}, {}))", d.as_secs(), d.subsec_nanos()),
+    };
+
+    format!("RoutingType::{}", serialized)
+}
+
+fn discovery_to_string(discovery: &DiscoveryType) -> String {
+    let duration = |d: &std::time::Duration| {
+        format!("Duration::new({}, {})", d.as_secs(), d.subsec_nanos())
+    };
+    let serialized = match discovery {
+        DiscoveryType::Static => "Static".into(),
+        DiscoveryType::Beacon(interval, timeout) => {
+            format!("Beacon({}, {})", duration(interval), duration(timeout))
+        }
+    };
+
+    format!("DiscoveryType::{}", serialized)
+}
+
+fn link_loss_to_string(loss: &LinkLossType) -> String {
+    let serialized = match loss {
+        LinkLossType::Constant(p) => format!("Constant({:.3})", p),
+        LinkLossType::Step(before, after, at) => format!(
+            "Step({:.3}, {:.3}, Duration::new({}, {}))",
+            before,
+            after,
+            at.as_secs(),
+            at.subsec_nanos()
+        ),
+    };
+
+    format!("LinkLossType::{}", serialized)
+}
+
 fn node_list_to_string(list: &Vec<NodeID>, node_count: usize) -> String {
     let mut str = String::new();
     str.push_str("[\n            ");
@@ -0,0 +1,102 @@
+use parse_duration;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub enum CodingSchedulerTypeError {
+    MissingArgument,
+    InvalidArgument,
+    InvalidFormat,
+    UnknownScheduler,
+}
+
+impl std::fmt::Display for CodingSchedulerTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        type Error = CodingSchedulerTypeError;
+
+        match self {
+            Error::MissingArgument => write!(f, "No argument supplied"),
+            Error::InvalidArgument => write!(f, "Argument supplied for scheduler without one"),
+            Error::InvalidFormat => write!(f, "Invalid format"),
+            Error::UnknownScheduler => write!(
+                f,
+                "Unknown scheduler, expected Immediate, WaitForPartner, QueueLength or MaxGain"
+            ),
+        }
+    }
+}
+
+// NOTE: Decides when the relay sends the next coded packet, see cope::scheduler.
+// Immediate sends as soon as a packet is there, WaitForPartner waits up to the
+// given delay for packets of a second nexthop, QueueLength waits until the pool
+// holds the given amount of packets and MaxGain waits up to the given delay
+// for packets of every nexthop.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CodingSchedulerType {
+    Immediate,
+    WaitForPartner(Duration),
+    QueueLength(usize),
+    MaxGain(Duration),
+}
+
+impl CodingSchedulerType {
+    fn parse_duration_argument(s: &str) -> Result<Duration, CodingSchedulerTypeError> {
+        parse_duration::parse(s).map_err(|_| CodingSchedulerTypeError::InvalidFormat)
+    }
+
+    fn parse_length_argument(s: &str) -> Result<usize, CodingSchedulerTypeError> {
+        match s.parse() {
+            Ok(0) | Err(_) => Err(CodingSchedulerTypeError::InvalidFormat),
+            Ok(length) => Ok(length),
+        }
+    }
+}
+
+impl FromStr for CodingSchedulerType {
+    type Err = CodingSchedulerTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: Same format as TrafficGeneratorType, e.g. WaitForPartner(200ms)
+        let parts: Vec<&str> = s.split("(").collect();
+        if parts.is_empty() || parts.len() > 2 {
+            return Err(CodingSchedulerTypeError::InvalidFormat);
+        }
+
+        let name = parts[0];
+        let arg = match parts.get(1) {
+            Some(arg) => match arg.strip_suffix(")") {
+                Some(arg) => Some(arg),
+                None => return Err(CodingSchedulerTypeError::InvalidFormat),
+            },
+            None => None,
+        };
+
+        let scheduler = match (name, arg) {
+            ("Immediate", None) => CodingSchedulerType::Immediate,
+            ("WaitForPartner", Some(d)) => {
+                CodingSchedulerType::WaitForPartner(Self::parse_duration_argument(d)?)
+            }
+            ("QueueLength", Some(l)) => {
+                CodingSchedulerType::QueueLength(Self::parse_length_argument(l)?)
+            }
+            ("MaxGain", Some(d)) => CodingSchedulerType::MaxGain(Self::parse_duration_argument(d)?),
+            ("Immediate", Some(_)) => return Err(CodingSchedulerTypeError::InvalidArgument),
+            ("WaitForPartner" | "QueueLength" | "MaxGain", None) => {
+                return Err(CodingSchedulerTypeError::MissingArgument)
+            }
+            (_, _) => return Err(CodingSchedulerTypeError::UnknownScheduler),
+        };
+
+        Ok(scheduler)
+    }
+}
+
+impl std::fmt::Display for CodingSchedulerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodingSchedulerType::Immediate => write!(f, "Immediate"),
+            CodingSchedulerType::WaitForPartner(d) => write!(f, "WaitForPartner({:?})", d),
+            CodingSchedulerType::QueueLength(l) => write!(f, "QueueLength({})", l),
+            CodingSchedulerType::MaxGain(d) => write!(f, "MaxGain({:?})", d),
+        }
+    }
+}
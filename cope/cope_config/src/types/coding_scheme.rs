@@ -0,0 +1,44 @@
+use std::str::FromStr;
+
+pub enum CodingSchemeError {
+    UnknownScheme,
+}
+
+impl std::fmt::Display for CodingSchemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CodingSchemeError::UnknownScheme => {
+                write!(f, "Unknown coding scheme, expected COPE or RLNC")
+            }
+        }
+    }
+}
+
+// NOTE: COPE codes natives pairwise using XOR, RLNC uses random linear
+// network coding over GF(2^8) on generations of natives.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CodingScheme {
+    Cope,
+    Rlnc,
+}
+
+impl FromStr for CodingScheme {
+    type Err = CodingSchemeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "COPE" => Ok(CodingScheme::Cope),
+            "RLNC" => Ok(CodingScheme::Rlnc),
+            _ => Err(CodingSchemeError::UnknownScheme),
+        }
+    }
+}
+
+impl std::fmt::Display for CodingScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodingScheme::Cope => write!(f, "COPE"),
+            CodingScheme::Rlnc => write!(f, "RLNC"),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use parse_duration;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub enum DiscoveryTypeError {
+    MissingArgument,
+    InvalidArgument,
+    InvalidTimeout,
+    InvalidFormat,
+    UnknownDiscovery,
+}
+
+impl std::fmt::Display for DiscoveryTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        type Error = DiscoveryTypeError;
+
+        match self {
+            Error::MissingArgument => write!(f, "Expected an interval and a timeout"),
+            Error::InvalidArgument => write!(f, "Argument supplied for discovery without one"),
+            Error::InvalidTimeout => write!(f, "Timeout has to be longer than the interval"),
+            Error::InvalidFormat => write!(f, "Invalid format"),
+            Error::UnknownDiscovery => write!(f, "Unknown discovery, expected Static or Beacon"),
+        }
+    }
+}
+
+// NOTE: Static only uses the whitelists from the config. Beacon sends a beacon
+// with the given interval and forgets neighbors that were not heard for the
+// given timeout, see cope::discovery.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DiscoveryType {
+    Static,
+    Beacon(Duration, Duration),
+}
+
+impl DiscoveryType {
+    fn parse_duration_argument(s: &str) -> Result<Duration, DiscoveryTypeError> {
+        match parse_duration::parse(s.trim()) {
+            Ok(d) if !d.is_zero() => Ok(d),
+            _ => Err(DiscoveryTypeError::InvalidFormat),
+        }
+    }
+}
+
+impl FromStr for DiscoveryType {
+    type Err = DiscoveryTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: Same format as LinkLossType, e.g. Beacon(200ms, 1s)
+        let (name, args) = match s.split_once("(") {
+            Some((name, args)) => match args.strip_suffix(")") {
+                Some(args) => (name, Some(args)),
+                None => return Err(DiscoveryTypeError::InvalidFormat),
+            },
+            None => (s, None),
+        };
+        let args: Option<Vec<&str>> = args.map(|args| args.split(",").collect());
+
+        let discovery = match (name, args.as_deref()) {
+            ("Static", None) => DiscoveryType::Static,
+            ("Beacon", Some([interval, timeout])) => {
+                let interval = Self::parse_duration_argument(interval)?;
+                let timeout = Self::parse_duration_argument(timeout)?;
+                if timeout <= interval {
+                    return Err(DiscoveryTypeError::InvalidTimeout);
+                }
+                DiscoveryType::Beacon(interval, timeout)
+            }
+            ("Static", Some(_)) => return Err(DiscoveryTypeError::InvalidArgument),
+            ("Beacon", _) => return Err(DiscoveryTypeError::MissingArgument),
+            (_, _) => return Err(DiscoveryTypeError::UnknownDiscovery),
+        };
+
+        Ok(discovery)
+    }
+}
+
+impl std::fmt::Display for DiscoveryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryType::Static => write!(f, "Static"),
+            DiscoveryType::Beacon(interval, timeout) => {
+                write!(f, "Beacon({:?}, {:?})", interval, timeout)
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+pub enum ElectionTypeError {
+    UnknownElection,
+}
+
+impl std::fmt::Display for ElectionTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ElectionTypeError::UnknownElection => {
+                write!(f, "Unknown election, expected Static or LowestId")
+            }
+        }
+    }
+}
+
+// NOTE: Static always uses the first of the relays from the config. LowestId
+// treats them as candidates, and elects the one with the lowest node id among
+// the reachable ones, see cope::election.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ElectionType {
+    Static,
+    LowestId,
+}
+
+impl FromStr for ElectionType {
+    type Err = ElectionTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Static" => Ok(ElectionType::Static),
+            "LowestId" => Ok(ElectionType::LowestId),
+            _ => Err(ElectionTypeError::UnknownElection),
+        }
+    }
+}
+
+impl std::fmt::Display for ElectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElectionType::Static => write!(f, "Static"),
+            ElectionType::LowestId => write!(f, "LowestId"),
+        }
+    }
+}
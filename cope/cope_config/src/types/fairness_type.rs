@@ -0,0 +1,80 @@
+use std::str::FromStr;
+
+pub enum FairnessTypeError {
+    MissingArgument,
+    InvalidArgument,
+    InvalidFormat,
+    UnknownFairness,
+}
+
+impl std::fmt::Display for FairnessTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        type Error = FairnessTypeError;
+
+        match self {
+            Error::MissingArgument => write!(f, "Expected a local and a forwarded weight"),
+            Error::InvalidArgument => write!(f, "Argument supplied for fairness without one"),
+            Error::InvalidFormat => write!(f, "Invalid format"),
+            Error::UnknownFairness => write!(f, "Unknown fairness, expected Fifo or Weighted"),
+        }
+    }
+}
+
+// NOTE: Decides how relays that generate traffic as well share the packet pool
+// between their own and forwarded packets, see cope::coding::relay_node_coding.
+// Fifo adds own packets as soon as there is room. Weighted adds them in the
+// given ratio of local to forwarded packets, while forwarded ones are waiting.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FairnessType {
+    Fifo,
+    Weighted(u32, u32),
+}
+
+impl FairnessType {
+    fn parse_weight_argument(s: &str) -> Result<u32, FairnessTypeError> {
+        match s.trim().parse() {
+            Ok(0) | Err(_) => Err(FairnessTypeError::InvalidFormat),
+            Ok(weight) => Ok(weight),
+        }
+    }
+}
+
+impl FromStr for FairnessType {
+    type Err = FairnessTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: Same format as DiscoveryType, e.g. Weighted(1, 3)
+        let (name, args) = match s.split_once("(") {
+            Some((name, args)) => match args.strip_suffix(")") {
+                Some(args) => (name, Some(args)),
+                None => return Err(FairnessTypeError::InvalidFormat),
+            },
+            None => (s, None),
+        };
+        let args: Option<Vec<&str>> = args.map(|args| args.split(",").collect());
+
+        let fairness = match (name, args.as_deref()) {
+            ("Fifo", None) => FairnessType::Fifo,
+            ("Weighted", Some([local, forwarded])) => FairnessType::Weighted(
+                Self::parse_weight_argument(local)?,
+                Self::parse_weight_argument(forwarded)?,
+            ),
+            ("Fifo", Some(_)) => return Err(FairnessTypeError::InvalidArgument),
+            ("Weighted", _) => return Err(FairnessTypeError::MissingArgument),
+            (_, _) => return Err(FairnessTypeError::UnknownFairness),
+        };
+
+        Ok(fairness)
+    }
+}
+
+impl std::fmt::Display for FairnessType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FairnessType::Fifo => write!(f, "Fifo"),
+            FairnessType::Weighted(local, forwarded) => {
+                write!(f, "Weighted({}, {})", local, forwarded)
+            }
+        }
+    }
+}
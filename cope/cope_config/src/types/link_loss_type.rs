@@ -0,0 +1,90 @@
+use parse_duration;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub enum LinkLossTypeError {
+    MissingArgument,
+    InvalidProbability,
+    InvalidFormat,
+    UnknownLoss,
+}
+
+impl std::fmt::Display for LinkLossTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        type Error = LinkLossTypeError;
+
+        match self {
+            Error::MissingArgument => write!(f, "Wrong number of arguments supplied"),
+            Error::InvalidProbability => write!(f, "Loss must be between 0 and 1"),
+            Error::InvalidFormat => write!(f, "Invalid format"),
+            Error::UnknownLoss => write!(f, "Unknown link loss, expected Constant or Step"),
+        }
+    }
+}
+
+// NOTE: Packet loss of a single link in the simulator, which replaces
+// simulator_packet_loss for it. Constant always drops packets with the given
+// probability, Step(before, after, at) changes the probability once the
+// simulation has run for the given time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LinkLossType {
+    Constant(f64),
+    Step(f64, f64, Duration),
+}
+
+impl LinkLossType {
+    pub fn loss_at(&self, elapsed: Duration) -> f64 {
+        match *self {
+            LinkLossType::Constant(loss) => loss,
+            LinkLossType::Step(before, _, at) if elapsed < at => before,
+            LinkLossType::Step(_, after, _) => after,
+        }
+    }
+
+    fn parse_probability_argument(s: &str) -> Result<f64, LinkLossTypeError> {
+        match s.trim().parse::<f64>() {
+            Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+            Ok(_) => Err(LinkLossTypeError::InvalidProbability),
+            Err(_) => Err(LinkLossTypeError::InvalidFormat),
+        }
+    }
+}
+
+impl FromStr for LinkLossType {
+    type Err = LinkLossTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: Same format as TrafficGeneratorType, e.g. Step(0.0, 0.8, 4s)
+        let Some((name, args)) = s.split_once("(") else {
+            return Err(LinkLossTypeError::InvalidFormat);
+        };
+        let Some(args) = args.strip_suffix(")") else {
+            return Err(LinkLossTypeError::InvalidFormat);
+        };
+        let args: Vec<&str> = args.split(",").collect();
+
+        let loss = match (name, args.as_slice()) {
+            ("Constant", [p]) => LinkLossType::Constant(Self::parse_probability_argument(p)?),
+            ("Step", [before, after, at]) => LinkLossType::Step(
+                Self::parse_probability_argument(before)?,
+                Self::parse_probability_argument(after)?,
+                parse_duration::parse(at.trim()).map_err(|_| LinkLossTypeError::InvalidFormat)?,
+            ),
+            ("Constant" | "Step", _) => return Err(LinkLossTypeError::MissingArgument),
+            (_, _) => return Err(LinkLossTypeError::UnknownLoss),
+        };
+
+        Ok(loss)
+    }
+}
+
+impl std::fmt::Display for LinkLossType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkLossType::Constant(p) => write!(f, "Constant({})", p),
+            LinkLossType::Step(before, after, at) => {
+                write!(f, "Step({}, {}, {:?})", before, after, at)
+            }
+        }
+    }
+}
@@ -1,3 +1,10 @@
+pub mod coding_scheduler_type;
+pub mod coding_scheme;
+pub mod discovery_type;
+pub mod election_type;
+pub mod fairness_type;
+pub mod link_loss_type;
 pub mod mac_address;
 pub mod node_id;
+pub mod routing_type;
 pub mod traffic_generator_type;
@@ -0,0 +1,75 @@
+use parse_duration;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub enum RoutingTypeError {
+    MissingArgument,
+    InvalidArgument,
+    InvalidFormat,
+    UnknownRouting,
+}
+
+impl std::fmt::Display for RoutingTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        type Error = RoutingTypeError;
+
+        match self {
+            Error::MissingArgument => write!(f, "No argument supplied"),
+            Error::InvalidArgument => write!(f, "Argument supplied for routing without one"),
+            Error::InvalidFormat => write!(f, "Invalid format"),
+            Error::UnknownRouting => write!(f, "Unknown routing, expected Static or Etx"),
+        }
+    }
+}
+
+// NOTE: Static only uses the routes from the config. Etx sends link state
+// adverts with the given interval and routes along the paths with the lowest
+// expected transmission count, see cope::routing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RoutingType {
+    Static,
+    Etx(Duration),
+}
+
+impl FromStr for RoutingType {
+    type Err = RoutingTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // NOTE: Same format as TrafficGeneratorType, e.g. Etx(1s)
+        let parts: Vec<&str> = s.split("(").collect();
+        if parts.is_empty() || parts.len() > 2 {
+            return Err(RoutingTypeError::InvalidFormat);
+        }
+
+        let name = parts[0];
+        let arg = match parts.get(1) {
+            Some(arg) => match arg.strip_suffix(")") {
+                Some(arg) => Some(arg),
+                None => return Err(RoutingTypeError::InvalidFormat),
+            },
+            None => None,
+        };
+
+        let routing = match (name, arg) {
+            ("Static", None) => RoutingType::Static,
+            ("Etx", Some(d)) => match parse_duration::parse(d) {
+                Ok(d) if !d.is_zero() => RoutingType::Etx(d),
+                _ => return Err(RoutingTypeError::InvalidFormat),
+            },
+            ("Static", Some(_)) => return Err(RoutingTypeError::InvalidArgument),
+            ("Etx", None) => return Err(RoutingTypeError::MissingArgument),
+            (_, _) => return Err(RoutingTypeError::UnknownRouting),
+        };
+
+        Ok(routing)
+    }
+}
+
+impl std::fmt::Display for RoutingType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoutingType::Static => write!(f, "Static"),
+            RoutingType::Etx(d) => write!(f, "Etx({:?})", d),
+        }
+    }
+}
@@ -1,26 +1,36 @@
 use cope_config::types::mac_address::MacAddress;
+use serde::{Serialize, Serializer};
 
-#[derive(Default, Debug)]
-struct EspNowMacHeader {
-    frame_control: u16,
-    duration_or_id: u16,
-    destination: MacAddress,
-    source: MacAddress,
-    broadcast: MacAddress,
-    sequence_control: u16,
+fn serialize_mac<S: Serializer>(mac: &MacAddress, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(mac)
 }
 
-#[derive(Default, Debug)]
-struct EspNowVendorContent {
-    element_id: u8,
-    length: u8,
-    organization_identifier: [u8; 3],
-    vc_type: u8,
-    version: u8,
-    body: Vec<u8>,
+#[derive(Default, Debug, Serialize)]
+pub struct EspNowMacHeader {
+    pub frame_control: u16,
+    pub duration_or_id: u16,
+    #[serde(serialize_with = "serialize_mac")]
+    pub destination: MacAddress,
+    #[serde(serialize_with = "serialize_mac")]
+    pub source: MacAddress,
+    #[serde(serialize_with = "serialize_mac")]
+    pub broadcast: MacAddress,
+    pub sequence_control: u16,
+}
+
+#[derive(Default, Debug, Serialize)]
+pub struct EspNowVendorContent {
+    pub element_id: u8,
+    pub length: u8,
+    pub organization_identifier: [u8; 3],
+    pub vc_type: u8,
+    pub version: u8,
+    // NOTE: The body is decoded separately as a Frame
+    #[serde(skip)]
+    pub body: Vec<u8>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct EspNowFrame {
     mac_header: EspNowMacHeader,
     category_code: u8,
@@ -41,6 +51,24 @@ pub enum EspNowDecodingError {
     InvalidVendorContentType,
 }
 
+impl std::fmt::Display for EspNowDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength => write!(f, "invalid length"),
+            Self::InvalidFrameControl => write!(f, "frame is not an action frame"),
+            Self::InvalidBroadcastMAC => write!(f, "BSSID is not the broadcast address"),
+            Self::InvalidCategoryCode => write!(f, "category code is not vendor specific"),
+            Self::InvalidOrganizationIdentifier => {
+                write!(f, "organization identifier is not Espressif")
+            }
+            Self::InvalidElementId => write!(f, "element id is not vendor specific"),
+            Self::InvalidVendorContentType => write!(f, "vendor content type is not EspNow"),
+        }
+    }
+}
+
+impl std::error::Error for EspNowDecodingError {}
+
 pub const ESPNOW_HEADER_SIZE: usize = 39;
 
 impl TryFrom<&[u8]> for EspNowFrame {
@@ -103,7 +131,11 @@ impl TryFrom<&[u8]> for EspNowFrame {
 
         decoded.vendor_content.length = bytes[33];
 
-        if decoded.vendor_content.length as usize != bytes.len() - 38 {
+        // NOTE: The length includes the organization identifier, type and version,
+        // which take up 5 bytes.
+        if decoded.vendor_content.length < 5
+            || decoded.vendor_content.length as usize != bytes.len() - 38
+        {
             return Err(Self::Error::InvalidLength);
         }
 
@@ -136,6 +168,14 @@ impl TryFrom<&[u8]> for EspNowFrame {
 }
 
 impl EspNowFrame {
+    pub fn mac_header(&self) -> &EspNowMacHeader {
+        &self.mac_header
+    }
+
+    pub fn vendor_content(&self) -> &EspNowVendorContent {
+        &self.vendor_content
+    }
+
     pub fn get_body(&self) -> &[u8] {
         self.vendor_content.body.as_slice()
     }
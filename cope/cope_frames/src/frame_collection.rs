@@ -2,6 +2,8 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::{cmp::min, ops::Deref};
 
+use serde::Serialize;
+
 const FIRST_FRAME_HEADER_SIZE: u8 = 7;
 const FOLLOWING_FRAME_HEADER_SIZE: u8 = 6;
 
@@ -15,9 +17,26 @@ pub enum FrameCollectionError {
     MismatchedMagic(u32),
 }
 
+impl std::fmt::Display for FrameCollectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FrameMissing => write!(f, "not all frames were received"),
+            Self::FrameAlreadyAdded => write!(f, "frame was already added"),
+            Self::InvalidFrameSize => write!(f, "invalid frame size"),
+            Self::InvalidFrameCount(count) => write!(f, "invalid frame count {}", count),
+            Self::UnsetMagic => write!(f, "magic is not set"),
+            Self::MismatchedMagic(magic) => {
+                write!(f, "frame magic {:#010x} does not match collection", magic)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameCollectionError {}
+
 // NOTE: Maybe it would be better if each frame contained the index and length,
 // not just the first frame.
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize)]
 pub enum FrameType {
     First((u8, u8)), // frame count and max size of each frame
     Following,
@@ -28,11 +47,23 @@ pub enum FrameError {
     InvalidHeader,
 }
 
-#[derive(PartialEq, Clone, Debug)]
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "invalid frame header"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+#[derive(PartialEq, Clone, Debug, Serialize)]
 pub struct Frame {
     ftype: FrameType,
     index: u8,
     magic: u32,
+    // NOTE: Only meaningful once all frames are collected
+    #[serde(skip)]
     data: Vec<u8>,
 }
 
@@ -49,6 +80,18 @@ impl Frame {
     pub fn get_magic(&self) -> u32 {
         self.magic
     }
+
+    pub fn get_type(&self) -> &FrameType {
+        &self.ftype
+    }
+
+    pub fn get_index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
 }
 
 impl TryFrom<&[u8]> for Frame {
@@ -150,10 +193,7 @@ impl FrameCollection {
         Ok(self)
     }
 
-    // NOTE: We only use this method for testing. Somehow rust-analyzer thinks this
-    // method is not used, but it is. Probably we use a custom test runner on the
-    // esp.
-    #[allow(dead_code)]
+    // NOTE: We only use this method for testing.
     pub fn with_magic(mut self, magic: u32) -> FrameCollection {
         self.magic = Some(magic);
         self
@@ -355,7 +395,7 @@ mod tests {
     };
     use anyhow::Error;
 
-    #[test_case]
+    #[test]
     fn first_frame_encode_should_succeed() -> Result<(), Error> {
         let first_frame = Frame::new(
             FrameType::First((1, 4)),
@@ -375,7 +415,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn following_frame_encode_should_succeed() -> Result<(), Error> {
         let first_frame = Frame::new(
             FrameType::Following,
@@ -395,7 +435,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn first_frame_decode_should_succeed() -> Result<(), Error> {
         let encoded: [u8; 11] = [0x1f, 0x1f, 0x1f, 0x1f, 0, 1, 4, 0xfe, 0xed, 0xbe, 0xef];
 
@@ -418,7 +458,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn following_frame_decode_should_succeed() -> Result<(), Error> {
         let encoded: [u8; 10] = [0xab, 0xab, 0xab, 0xab, 1, 1, 0xde, 0xad, 0xc0, 0xde];
 
@@ -442,7 +482,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn frame_size_smaller_than_header_should_fail() -> Result<(), Error> {
         anyhow::ensure!(
             matches!(FrameCollection::new().with_frame_size(FOLLOWING_FRAME_HEADER_SIZE - 1), Err(FrameCollectionError::InvalidFrameSize)),
@@ -452,7 +492,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn mismatched_magic_should_fail() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -486,7 +526,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn out_of_range_index_should_fail() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -516,7 +556,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn out_of_order_out_of_range_index_should_fail() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -546,7 +586,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn decode_with_missing_index_should_fail() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -570,7 +610,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn valid_encode_should_succeed() -> Result<(), Error> {
         let data = Vec::from([
             0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
@@ -637,7 +677,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn valid_decode_should_succeed() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -681,7 +721,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn valid_decode_with_out_of_order_insertion_should_succeed() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
@@ -732,7 +772,7 @@ mod tests {
         Ok(())
     }
 
-    #[test_case]
+    #[test]
     fn valid_decode_with_first_frame_out_of_order_insertion_should_succeed() -> Result<(), Error> {
         let mut collection = FrameCollection::new();
 
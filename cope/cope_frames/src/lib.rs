@@ -0,0 +1,7 @@
+// Parsers for the layers below COPE packets on the ESP: the promiscuous mode
+// buffer (WifiFrame), the EspNow action frame inside of it (EspNowFrame) and the
+// partial packet frames we split packets into (Frame/FrameCollection).
+// They live in their own crate, so they can be used on the host as well.
+pub mod espnow_frame;
+pub mod frame_collection;
+pub mod wifi_frame;
@@ -1,9 +1,11 @@
 use bitvec::{field::BitField, prelude as bv, view::BitView};
+use serde::Serialize;
 
 pub const WIFI_HEADER_SIZE: usize = 48;
 
 #[derive(Debug)]
 pub enum WifiFrameDecodingError {
+    InvalidLength(usize),
     InvalidSigMode,
     InvalidChannelBandwidth,
     InvalidChannelEstimateSmootingValue,
@@ -13,8 +15,31 @@ pub enum WifiFrameDecodingError {
     InvalidGuideInterval,
 }
 
-#[derive(Default, Debug)]
-enum SigMode {
+impl std::fmt::Display for WifiFrameDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "buffer is {} bytes long, but the header alone is {} bytes",
+                len, WIFI_HEADER_SIZE
+            ),
+            Self::InvalidSigMode => write!(f, "invalid sig mode"),
+            Self::InvalidChannelBandwidth => write!(f, "invalid channel bandwidth"),
+            Self::InvalidChannelEstimateSmootingValue => {
+                write!(f, "invalid channel estimate smoothing value")
+            }
+            Self::InvalidPPDUType => write!(f, "invalid PPDU type"),
+            Self::InvalidAggregationType => write!(f, "invalid aggregation type"),
+            Self::InvalidSTBCValue => write!(f, "invalid STBC value"),
+            Self::InvalidGuideInterval => write!(f, "invalid guard interval"),
+        }
+    }
+}
+
+impl std::error::Error for WifiFrameDecodingError {}
+
+#[derive(Default, Debug, Serialize)]
+pub enum SigMode {
     #[default]
     HT11bg,
     HT11n,
@@ -34,8 +59,8 @@ impl TryFrom<u32> for SigMode {
     }
 }
 
-#[derive(Default, Debug)]
-enum ChannelBandwidth {
+#[derive(Default, Debug, Serialize)]
+pub enum ChannelBandwidth {
     #[default]
     MHz20,
     MHz40,
@@ -53,8 +78,8 @@ impl TryFrom<u32> for ChannelBandwidth {
     }
 }
 
-#[derive(Default, Debug)]
-enum ChannelEstimateSmoothing {
+#[derive(Default, Debug, Serialize)]
+pub enum ChannelEstimateSmoothing {
     #[default]
     Recommended,
     NotRecommended,
@@ -72,8 +97,8 @@ impl TryFrom<u32> for ChannelEstimateSmoothing {
     }
 }
 
-#[derive(Default, Debug)]
-enum PPDUType {
+#[derive(Default, Debug, Serialize)]
+pub enum PPDUType {
     #[default]
     Sounding,
     NotSounding,
@@ -91,8 +116,8 @@ impl TryFrom<u32> for PPDUType {
     }
 }
 
-#[derive(Default, Debug)]
-enum AggregationType {
+#[derive(Default, Debug, Serialize)]
+pub enum AggregationType {
     #[default]
     MPDU,
     AMPDU,
@@ -110,8 +135,8 @@ impl TryFrom<u32> for AggregationType {
     }
 }
 
-#[derive(Default, Debug)]
-enum STBC {
+#[derive(Default, Debug, Serialize)]
+pub enum STBC {
     #[default]
     Yes,
     No,
@@ -129,8 +154,8 @@ impl TryFrom<u32> for STBC {
     }
 }
 
-#[derive(Default, Debug)]
-enum GuideInterval {
+#[derive(Default, Debug, Serialize)]
+pub enum GuideInterval {
     #[default]
     Short,
     Long,
@@ -148,36 +173,38 @@ impl TryFrom<u32> for GuideInterval {
     }
 }
 
-#[derive(Default, Debug)]
-struct RadioMetadataHeader {
-    rssi: i32,
-    rate: u32,
-    sig_mode: SigMode,
-    mcs: u32,
-    cwb: ChannelBandwidth,
-    smoothing: ChannelEstimateSmoothing,
-    ppdu_type: PPDUType,
-    aggregation: AggregationType,
-    stbc: STBC,
-    fec_coding: u32,
-    gi: GuideInterval,
-    noise_floor: i32,
-    ampdu_cnt: u32,
-    channel: u32,
-    secondary_channel: u32,
+#[derive(Default, Debug, Serialize)]
+pub struct RadioMetadataHeader {
+    pub rssi: i32,
+    pub rate: u32,
+    pub sig_mode: SigMode,
+    pub mcs: u32,
+    pub cwb: ChannelBandwidth,
+    pub smoothing: ChannelEstimateSmoothing,
+    pub ppdu_type: PPDUType,
+    pub aggregation: AggregationType,
+    pub stbc: STBC,
+    pub fec_coding: u32,
+    pub gi: GuideInterval,
+    pub noise_floor: i32,
+    pub ampdu_cnt: u32,
+    pub channel: u32,
+    pub secondary_channel: u32,
     // NOTE: If this is micros since device startup, converting to DateTime is meaningless
-    timestamp_us: u32,
-    ant: u32,
-    sig_len: u32,
-    rx_state: u32,
+    pub timestamp_us: u32,
+    pub ant: u32,
+    pub sig_len: u32,
+    pub rx_state: u32,
 }
 
 // FIXME: This is the common header at the beginning of all promiscuous mode RX
 // callback buffers, which is only specific to Espressif. It is not part of an
 // IEEE 802.11 Frame, this struct/file should be renamed to something else!
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct WifiFrame {
     header: RadioMetadataHeader,
+    // NOTE: The data is decoded separately as an EspNowFrame
+    #[serde(skip)]
     data: Vec<u8>,
 }
 
@@ -185,6 +212,10 @@ impl TryFrom<&[u8]> for WifiFrame {
     type Error = WifiFrameDecodingError;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() < WIFI_HEADER_SIZE {
+            return Err(Self::Error::InvalidLength(bytes.len()));
+        }
+
         let mut frame = WifiFrame::default();
         let header = &bytes[..WIFI_HEADER_SIZE];
         let bits = header.view_bits::<bv::Lsb0>();
@@ -218,6 +249,10 @@ impl TryFrom<&[u8]> for WifiFrame {
 }
 
 impl WifiFrame {
+    pub fn header(&self) -> &RadioMetadataHeader {
+        &self.header
+    }
+
     pub fn get_data(&self) -> &[u8] {
         self.data.as_slice()
     }
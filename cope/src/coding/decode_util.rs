@@ -52,31 +52,36 @@ pub fn ids_for_decoding<PP: PacketPool>(
 }
 
 // TODO: This could be a member of PacketPool
-pub fn remove_from_pool<PP: PacketPool>(pool: &mut PP, packet_indices: &Vec<usize>) {
-    for &index in packet_indices {
+pub fn remove_from_pool<PP: PacketPool>(pool: &mut PP, packet_indices: &[usize]) {
+    // NOTE: Removing a packet shifts all following positions,
+    // so we have to start with the last one.
+    let mut packet_indices = packet_indices.to_vec();
+    packet_indices.sort_unstable_by(|a, b| b.cmp(a));
+    for index in packet_indices {
         pool.remove(index).unwrap();
     }
 }
 
 pub fn decode<PP: PacketPool>(
-    packet_indices: &Vec<usize>,
+    packet_indices: &[usize],
+    packet_info: &CodingInfo,
     packet_data: &PacketData,
     pool: &PP,
 ) -> PacketData {
-    let mut data: PacketData = packet_data.clone();
-
-    for &index in packet_indices {
-        let (_, d) = pool.get_ref(index).unwrap();
-        data = data.xor(d);
-    }
-    return data;
+    let known = packet_indices
+        .iter()
+        .map(|&index| &pool.get_ref(index).unwrap().1);
+    let data = PacketData::xor_many(std::iter::once(packet_data).chain(known));
+    // NOTE: Shorter packets are zero padded when encoding,
+    // so we need to remove the padding again.
+    return data.truncate(packet_info.len as usize);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::{
-        packet::{CodingHeader as CH, Packet, PacketBuilder},
+        packet::{CodingHeader as CH, Packet, PacketBuilder, PacketID},
         packet_pool::SimplePacketPool,
     };
 
@@ -135,13 +140,17 @@ mod test {
 
             let coding_info0 = CodingInfo {
                 source: node_a,
-                id: 0,
+                id: PacketID::new(0),
                 nexthop: node_c,
+                destination: node_c,
+                len: 2,
             };
             let coding_info1 = CodingInfo {
                 source: node_c,
-                id: 0,
+                id: PacketID::new(0),
                 nexthop: node_a,
+                destination: node_a,
+                len: 2,
             };
 
             let p0 = PacketBuilder::new()
@@ -0,0 +1,199 @@
+// A generation is a group of natives from the same source, which are coded
+// together by RLNC. Every node keeps the coded packets it has of a generation as
+// the rows of a matrix in reduced row echelon form. Each row consists of the
+// coefficient vector and the payload, which are transformed together.
+//
+// This way, the rank of the matrix tells whether a packet is innovative, and once
+// the matrix has full rank, the coefficients form the identity matrix, so the
+// payloads are the natives. A source simply starts out with its natives.
+
+use rand::Rng;
+
+use super::gf256;
+
+struct Row {
+    coefficients: Vec<u8>,
+    payload: Vec<u8>,
+    // NOTE: Column of the leading 1 in the coefficients
+    pivot: usize,
+}
+
+pub struct Generation {
+    size: usize,
+    rows: Vec<Row>,
+}
+
+impl Generation {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            rows: Vec::with_capacity(size),
+        }
+    }
+
+    pub fn from_natives(natives: &[Vec<u8>]) -> Self {
+        let mut generation = Self::new(natives.len());
+        for (i, native) in natives.iter().enumerate() {
+            let mut coefficients = vec![0; natives.len()];
+            coefficients[i] = 1;
+            generation.insert(&coefficients, native);
+        }
+        generation
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.rank() == self.size
+    }
+
+    // NOTE: Returns false, if the packet is not innovative, i.e. it is a linear
+    // combination of the packets we already have. Shorter payloads are treated
+    // as if they were zero padded.
+    pub fn insert(&mut self, coefficients: &[u8], payload: &[u8]) -> bool {
+        assert_eq!(
+            coefficients.len(),
+            self.size,
+            "Coefficient vector does not match the generation size"
+        );
+        if self.is_complete() {
+            return false;
+        }
+
+        let len = self.rows.first().map_or(0, |r| r.payload.len());
+        let len = len.max(payload.len());
+        for row in self.rows.iter_mut() {
+            row.payload.resize(len, 0);
+        }
+
+        let mut coefficients = coefficients.to_vec();
+        let mut payload = payload.to_vec();
+        payload.resize(len, 0);
+
+        // NOTE: Eliminate the pivots of all existing rows
+        for row in &self.rows {
+            let factor = coefficients[row.pivot];
+            gf256::mul_add_into(&mut coefficients, &row.coefficients, factor);
+            gf256::mul_add_into(&mut payload, &row.payload, factor);
+        }
+
+        let Some(pivot) = coefficients.iter().position(|&c| c != 0) else {
+            return false;
+        };
+
+        let factor = gf256::inv(coefficients[pivot]);
+        gf256::scale(&mut coefficients, factor);
+        gf256::scale(&mut payload, factor);
+
+        // NOTE: Keep the matrix reduced, by eliminating the new pivot
+        // from all existing rows.
+        for row in self.rows.iter_mut() {
+            let factor = row.coefficients[pivot];
+            gf256::mul_add_into(&mut row.coefficients, &coefficients, factor);
+            gf256::mul_add_into(&mut row.payload, &payload, factor);
+        }
+
+        self.rows.push(Row {
+            coefficients,
+            payload,
+            pivot,
+        });
+        true
+    }
+
+    // NOTE: Returns a random linear combination of all rows, as the
+    // coefficient vector and payload of a new coded packet.
+    pub fn recode(&self, rng: &mut impl Rng) -> Option<(Vec<u8>, Vec<u8>)> {
+        let first = self.rows.first()?;
+        let mut coefficients = vec![0; self.size];
+        let mut payload = vec![0; first.payload.len()];
+        for row in &self.rows {
+            let factor = rng.gen_range(1..=255);
+            gf256::mul_add_into(&mut coefficients, &row.coefficients, factor);
+            gf256::mul_add_into(&mut payload, &row.payload, factor);
+        }
+        Some((coefficients, payload))
+    }
+
+    // NOTE: Returns the payloads of the natives in order, once we have all of them.
+    pub fn decode(&self) -> Option<Vec<&[u8]>> {
+        if !self.is_complete() {
+            return None;
+        }
+
+        let mut natives = vec![&[][..]; self.size];
+        for row in &self.rows {
+            natives[row.pivot] = &row.payload;
+        }
+        Some(natives)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn natives() -> Vec<Vec<u8>> {
+        vec![vec![1, 2, 3], vec![0xFF; 5], vec![], vec![42; 4]]
+    }
+
+    fn padded(natives: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let len = natives.iter().map(|n| n.len()).max().unwrap();
+        natives
+            .iter()
+            .map(|n| {
+                let mut n = n.clone();
+                n.resize(len, 0);
+                n
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_recoded_packets() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let source = Generation::from_natives(&natives());
+        assert!(source.is_complete());
+
+        // NOTE: The relay recodes from whatever it has received so far
+        let mut relay = Generation::new(4);
+        let mut sink = Generation::new(4);
+        while !sink.is_complete() {
+            let (coefficients, payload) = source.recode(&mut rng).unwrap();
+            relay.insert(&coefficients, &payload);
+            let (coefficients, payload) = relay.recode(&mut rng).unwrap();
+            sink.insert(&coefficients, &payload);
+        }
+
+        let expected = padded(&natives());
+        let decoded = sink.decode().unwrap();
+        assert_eq!(
+            decoded,
+            expected.iter().map(|n| n.as_slice()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_non_innovative_packets() {
+        let mut generation = Generation::new(3);
+        assert!(generation.recode(&mut rand::thread_rng()).is_none());
+        assert!(generation.decode().is_none());
+
+        assert!(generation.insert(&[1, 2, 0], &[1]));
+        assert!(!generation.insert(&[2, 4, 0], &[2]));
+        assert!(!generation.insert(&[0, 0, 0], &[]));
+        assert!(generation.insert(&[0, 0, 7], &[3]));
+        assert_eq!(generation.rank(), 2);
+        assert!(generation.insert(&[5, 1, 1], &[4, 4]));
+        assert!(generation.is_complete());
+        assert!(!generation.insert(&[1, 1, 1], &[5]));
+    }
+}
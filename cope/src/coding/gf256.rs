@@ -0,0 +1,130 @@
+// Arithmetic in GF(2^8), the field random linear network coding works in.
+//
+// Elements are bytes, addition is XOR and multiplication is done with log and
+// exp tables for the primitive polynomial x^8 + x^4 + x^3 + x^2 + 1 (0x11D),
+// which is also used by Reed-Solomon codes.
+
+use crate::packet::xor;
+
+const POLYNOMIAL: u16 = 0x11D;
+
+// NOTE: EXP is twice as long as needed, so the sum of two logs can be
+// looked up without a modulo.
+const EXP: [u8; 510] = exp_table();
+const LOG: [u8; 256] = log_table();
+
+const fn exp_table() -> [u8; 510] {
+    let mut table = [0; 510];
+    let mut value: u16 = 1;
+    let mut i = 0;
+    while i < 510 {
+        table[i] = value as u8;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= POLYNOMIAL;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn log_table() -> [u8; 256] {
+    let exp = exp_table();
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 255 {
+        table[exp[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+pub fn inv(a: u8) -> u8 {
+    assert_ne!(a, 0, "0 has no inverse in GF(2^8)");
+    EXP[255 - LOG[a as usize] as usize]
+}
+
+// NOTE: dst += factor * src, src may be shorter than dst.
+pub fn mul_add_into(dst: &mut [u8], src: &[u8], factor: u8) {
+    match factor {
+        0 => (),
+        1 => xor::xor_into(dst, src),
+        _ => {
+            let log_factor = LOG[factor as usize] as usize;
+            for (d, &s) in dst.iter_mut().zip(src) {
+                if s != 0 {
+                    *d ^= EXP[LOG[s as usize] as usize + log_factor];
+                }
+            }
+        }
+    }
+}
+
+pub fn scale(values: &mut [u8], factor: u8) {
+    if factor == 1 {
+        return;
+    }
+    for value in values.iter_mut() {
+        *value = mul(*value, factor);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // NOTE: Multiplication by shifting and reducing, to check the tables against
+    fn mul_reference(mut a: u8, mut b: u8) -> u8 {
+        let mut product = 0;
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+            let carry = a & 0x80 != 0;
+            a <<= 1;
+            if carry {
+                a ^= (POLYNOMIAL & 0xFF) as u8;
+            }
+            b >>= 1;
+        }
+        product
+    }
+
+    #[test]
+    fn test_mul_matches_reference() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                assert_eq!(mul(a, b), mul_reference(a, b), "{} * {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_inv() {
+        for a in 1..=255 {
+            assert_eq!(mul(a, inv(a)), 1, "inverse of {}", a);
+        }
+    }
+
+    #[test]
+    fn test_mul_add_into() {
+        let src = [1, 2, 3, 0, 255];
+        let mut dst = [7, 7, 7, 7, 7, 7];
+        mul_add_into(&mut dst, &src, 3);
+        let expected: Vec<u8> = (0..6)
+            .map(|i| 7 ^ src.get(i).map_or(0, |&s| mul(s, 3)))
+            .collect();
+        assert_eq!(dst.to_vec(), expected);
+
+        // NOTE: Adding the same thing twice cancels out
+        mul_add_into(&mut dst, &src, 3);
+        assert_eq!(dst, [7; 6]);
+    }
+}
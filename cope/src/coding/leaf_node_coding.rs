@@ -1,10 +1,12 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::{
     coding::decode_util::{decode, remove_from_pool},
     config::CONFIG,
     packet::{packet::CodingHeader, Ack, CodingInfo, PacketBuilder, PacketData},
     packet_pool::{PacketPool, SimplePacketPool},
+    security::NativeAuth,
+    stats::Stats,
     topology::Topology,
     traffic_generator::TrafficGenerator,
     Packet,
@@ -12,6 +14,8 @@ use crate::{
 
 use super::{
     decode_util::{ids_for_decoding, is_next_hop},
+    pending_store::PendingStore,
+    reception_tracker::ReceptionTracker,
     retrans_queue::RetransQueue,
     CodingError, CodingStrategy,
 };
@@ -20,8 +24,20 @@ pub struct LeafNodeCoding {
     generator: TrafficGenerator,
     packet_pool: SimplePacketPool,
     retrans_queue: RetransQueue,
+    reception_tracker: ReceptionTracker,
     acks: Vec<CodingInfo>,
+    // NOTE: Packets for us, that we could not decode, see PendingStore
+    nacks: Vec<CodingInfo>,
     last_packet_send: Instant,
+    native_auth: Option<NativeAuth>,
+    pending: PendingStore,
+    late_decoded: u32,
+    late_decoded_data: u32,
+    // NOTE: Pending packets we got the native of after all, and how long it took
+    recovered: u32,
+    recovery_time: Duration,
+    implicit_acks: u32,
+    use_nacks: bool,
 }
 
 impl LeafNodeCoding {
@@ -31,15 +47,101 @@ impl LeafNodeCoding {
 
         Self {
             generator,
-            packet_pool: SimplePacketPool::new(sz),
+            packet_pool: SimplePacketPool::new(sz).with_overheard_size(sz),
             retrans_queue: RetransQueue::new(sz, rtt),
+            reception_tracker: ReceptionTracker::new(),
             acks: vec![],
+            nacks: vec![],
             last_packet_send: Instant::now(),
+            native_auth: NativeAuth::from_config(),
+            pending: PendingStore::new(sz, rtt),
+            late_decoded: 0,
+            late_decoded_data: 0,
+            recovered: 0,
+            recovery_time: Duration::ZERO,
+            implicit_acks: 0,
+            use_nacks: CONFIG.use_nacks,
+        }
+    }
+
+    // NOTE: Overrides CONFIG.use_nacks, e.g. to compare both in a test
+    pub fn with_nacks(mut self, use_nacks: bool) -> Self {
+        self.use_nacks = use_nacks;
+        self
+    }
+
+    // NOTE: Lets tests of other codings make us send a control packet right away
+    #[cfg(test)]
+    pub fn set_last_packet_send(&mut self, last_packet_send: Instant) {
+        self.last_packet_send = last_packet_send;
+    }
+
+    // NOTE: Natives carry an end-to-end tag, if the network is protected.
+    // It is removed here, once the native reached its final receiver.
+    fn verify_native(
+        &self,
+        info: &CodingInfo,
+        data: PacketData,
+    ) -> Result<PacketData, CodingError> {
+        match &self.native_auth {
+            Some(auth) => auth
+                .verify(info, data)
+                .map_err(|e| CodingError::AuthenticationError(e.to_string())),
+            None => Ok(data),
+        }
+    }
+
+    fn decode_coded(
+        &mut self,
+        infos: &[CodingInfo],
+        data: &PacketData,
+        topology: &Topology,
+    ) -> Result<PacketData, CodingError> {
+        let (ids, info) = ids_for_decoding(topology.id(), infos, &self.packet_pool)?;
+        let decoded_data = decode(&ids, &info, data, &self.packet_pool);
+        remove_from_pool(&mut self.packet_pool, &ids);
+        // NOTE: Only keep the native, if another pending packet was coded with it
+        if self.pending.needs(topology.id(), &info) {
+            self.packet_pool
+                .push_overheard_native(info.clone(), decoded_data.clone());
+        }
+        // NOTE: Packets that fail verification are not acked, so they get retransmitted
+        let decoded_data = self.verify_native(&info, decoded_data)?;
+        log::debug!("[Node {}]: Decoded into {}", topology.id(), decoded_data);
+        // NOTE: A retransmission or the answer to our NACK can carry a native,
+        // which we are still waiting for in a pending packet.
+        for entry in self.pending.take_resolved(topology.id(), &info) {
+            self.recovered += 1;
+            self.recovery_time += entry.age();
+        }
+        self.nacks.retain(|nacked| nacked.key() != info.key());
+        self.acks.push(info);
+        Ok(decoded_data)
+    }
+
+    // NOTE: Decodes all pending packets, whose natives have arrived by now. Every
+    // decoded packet can make further pending packets decodable, so this repeats
+    // until none are left.
+    fn decode_pending(&mut self, topology: &Topology) {
+        let id = topology.id();
+        while let Some(entry) = self
+            .pending
+            .take_decodable(|infos| ids_for_decoding(id, infos, &self.packet_pool).is_ok())
+        {
+            match self.decode_coded(&entry.infos, &entry.data, topology) {
+                Ok(data) => {
+                    self.late_decoded += 1;
+                    self.late_decoded_data += data.len() as u32;
+                    self.recovered += 1;
+                    self.recovery_time += entry.age();
+                }
+                Err(e) => log::warn!("[Node {}]: Failed to decode pending packet, {}", id, e),
+            }
         }
     }
 
     fn should_tx_control(&self) -> bool {
-        if self.acks.len() == 0 {
+        if self.acks.len() == 0 && self.nacks.is_empty() && !self.reception_tracker.has_pending() {
             return false;
         }
         self.last_packet_send.elapsed() > CONFIG.control_packet_duration
@@ -53,7 +155,25 @@ impl CodingStrategy for LeafNodeCoding {
         topology: &Topology,
     ) -> Result<Option<PacketData>, CodingError> {
         let original_data = packet.data().clone();
-        let is_from_relay = packet.sender() == topology.relay();
+
+        // NOTE: Remember overheard natives, so the relay can learn about them
+        // through our reception reports and code them with packets for us.
+        // Relays send their own natives coded on their own, see LocalQueue.
+        let overheard = match packet.coding_header() {
+            CodingHeader::Native(info) => Some(info),
+            CodingHeader::Encoded(infos) if infos.len() == 1 => {
+                Some(&infos[0]).filter(|info| info.source == packet.sender())
+            }
+            _ => None,
+        };
+        if let Some(coding_info) = overheard.filter(|info| info.nexthop != topology.id()) {
+            self.reception_tracker.record(coding_info);
+            self.packet_pool
+                .push_overheard_native(coding_info.clone(), packet.data().clone());
+            self.decode_pending(topology);
+        }
+
+        let is_from_relay = topology.is_relay_node(packet.sender());
         if !is_from_relay {
             // store for coding
             return Ok(Some(original_data));
@@ -67,6 +187,20 @@ impl CodingStrategy for LeafNodeCoding {
             }
         }
 
+        // NOTE: The relay only forwards packets it has received, so overhearing
+        // the forward of one of our packets is as good as an ack for it.
+        let forwarded: &[CodingInfo] = match packet.coding_header() {
+            CodingHeader::Native(info) => std::slice::from_ref(info),
+            CodingHeader::Encoded(infos) => infos,
+            _ => &[],
+        };
+        for info in forwarded.iter().filter(|info| info.source == topology.id()) {
+            if self.retrans_queue.remove_packet(info) {
+                log::debug!("[Node {}]: Packet {} was forwarded.", topology.id(), info);
+                self.implicit_acks += 1;
+            }
+        }
+
         log::debug!(
             "[Node{}]: Retrans Queue Size {}",
             topology.id(),
@@ -76,10 +210,10 @@ impl CodingStrategy for LeafNodeCoding {
         match packet.coding_header() {
             CodingHeader::Native(coding_info) => {
                 let is_next_hop = topology.id() == coding_info.nexthop;
-                if !is_next_hop {
-                    // store for coding
-                    return Ok(Some(original_data));
+                if is_next_hop {
+                    return self.verify_native(coding_info, original_data).map(Some);
                 }
+                // store for coding
             }
             CodingHeader::Encoded(coding_info) => {
                 // check if node is next_hop for packet
@@ -87,30 +221,44 @@ impl CodingStrategy for LeafNodeCoding {
                     log::debug!("[Node {}]: Not a next hop of Packet.", topology.id());
                     return Ok(Some(original_data));
                 }
-                // decode
+                // NOTE: Packets we can't decode yet are kept, until the missing natives arrive.
+                // We also ask the relay to resend our native, which is often faster.
+                if let Err(e) = ids_for_decoding(topology.id(), coding_info, &self.packet_pool) {
+                    let is_new = self.pending.push(coding_info.clone(), original_data);
+                    let wanted = coding_info.iter().find(|i| i.nexthop == topology.id());
+                    if let (true, true, Some(info)) = (is_new, self.use_nacks, wanted) {
+                        log::debug!("[Node {}]: Nack {}", topology.id(), info);
+                        self.nacks.push(info.clone());
+                    }
+                    return Err(e);
+                }
                 // TODO: add acks to the thing
-                let (ids, info) = ids_for_decoding(topology.id(), coding_info, &self.packet_pool)?;
-                let decoded_data = decode(&ids, packet.data(), &self.packet_pool);
-                log::debug!("[Node {}]: Decoded into {}", topology.id(), decoded_data);
-                remove_from_pool(&mut self.packet_pool, &ids);
-                self.acks.push(info);
+                let decoded_data = self.decode_coded(coding_info, packet.data(), topology)?;
+                self.decode_pending(topology);
                 return Ok(Some(decoded_data));
             }
-            CodingHeader::Control(_) => {
+            CodingHeader::Control(_) | CodingHeader::Routing(_) | CodingHeader::Beacon(_) => {
                 return Ok(Some(original_data));
             }
+            CodingHeader::Linear(_) => {
+                return Err(CodingError::DefectPacketError(
+                    "COPE can not handle Linear Packets".into(),
+                ));
+            }
         }
 
         Ok(Some(original_data))
     }
 
     fn handle_tx(&mut self, topology: &Topology) -> Result<Option<Packet>, CodingError> {
+        self.pending.expire();
         if let Some((info, data)) = self.retrans_queue.packet_to_retrans() {
             let builder = PacketBuilder::new()
                 .sender(topology.id())
                 .data(data)
-                .native_header(info);
-            // TODO: add reception report
+                .native_header(info)
+                .reception_header(self.reception_tracker.take_reports())
+                .nack_header(std::mem::take(&mut self.nacks));
 
             // add acks to header
             let ack = Ack {
@@ -137,7 +285,7 @@ impl CodingStrategy for LeafNodeCoding {
         }
 
         if self.should_tx_control() {
-            let receiver = *topology.txlist().first().unwrap();
+            let receiver = topology.control_receiver();
             let ack = Ack {
                 source: topology.id(),
                 packets: std::mem::take(&mut self.acks),
@@ -145,7 +293,9 @@ impl CodingStrategy for LeafNodeCoding {
             let result = PacketBuilder::new()
                 .sender(topology.id())
                 .control_header(receiver)
+                .reception_header(self.reception_tracker.take_reports())
                 .ack_header(vec![ack])
+                .nack_header(std::mem::take(&mut self.nacks))
                 .build();
             log::debug!("[Relay {}]: Send Control Packet", topology.id());
             match result {
@@ -160,8 +310,10 @@ impl CodingStrategy for LeafNodeCoding {
             }
         }
 
-        if let Some(builder) = self.generator.generate() {
-            // TODO: add reception report
+        if let Some(builder) = self.generator.generate(topology) {
+            let builder = builder
+                .reception_header(self.reception_tracker.take_reports())
+                .nack_header(std::mem::take(&mut self.nacks));
 
             // add acks to header
             let ack = Ack {
@@ -169,7 +321,13 @@ impl CodingStrategy for LeafNodeCoding {
                 packets: std::mem::take(&mut self.acks),
             };
 
-            let packet = builder.ack_header(vec![ack]).build().unwrap();
+            let mut packet = builder.ack_header(vec![ack]).build().unwrap();
+            if let (Some(auth), CodingHeader::Native(info)) =
+                (&self.native_auth, packet.coding_header())
+            {
+                let data = auth.tag(info, packet.data());
+                packet = packet.set_data(data).unwrap();
+            }
             self.packet_pool.push_packet(packet.clone());
 
             let CodingHeader::Native(info) = packet.coding_header() else {
@@ -188,4 +346,141 @@ impl CodingStrategy for LeafNodeCoding {
     fn update_last_packet_send(&mut self) {
         self.last_packet_send = Instant::now();
     }
+
+    fn update_stats(&mut self, stats: &mut Stats) {
+        stats.add_late_decoded(
+            std::mem::take(&mut self.late_decoded),
+            std::mem::take(&mut self.late_decoded_data),
+        );
+        stats.add_pending_dropped(self.pending.take_dropped());
+        stats.add_recovered(
+            std::mem::take(&mut self.recovered),
+            std::mem::take(&mut self.recovery_time),
+        );
+        stats.add_retrans_dropped(self.retrans_queue.take_dropped());
+        stats.set_rto(self.retrans_queue.rto());
+        stats.add_implicit_acks(std::mem::take(&mut self.implicit_acks));
+    }
+
+    fn update_neighbors(&mut self, topology: &Topology) {
+        self.generator.set_tx_whitelist(topology.txlist().to_vec());
+    }
+
+    fn take_queued(&mut self) -> Vec<(CodingInfo, PacketData)> {
+        self.retrans_queue.take_all()
+    }
+
+    // NOTE: Packets the old relay did not forward yet would only expire there
+    fn update_relay(&mut self, topology: &Topology) {
+        self.retrans_queue
+            .readdress(|info| topology.nexthop_for_target(info.destination));
+    }
+
+    // NOTE: Natives of others, that we still had to forward as a relay, are
+    // sent to the new relay like our own. Their destinations ack them as usual.
+    fn requeue(&mut self, packets: Vec<(CodingInfo, PacketData)>, topology: &Topology) {
+        for (info, data) in packets {
+            if info.destination == topology.id() {
+                continue;
+            }
+            let nexthop = topology.nexthop_for_target(info.destination);
+            self.retrans_queue
+                .push_due((CodingInfo { nexthop, ..info }, data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::coding::test_util::leaf;
+    use cope_config::types::node_id::NodeID;
+
+    // NOTE: Info of the native, as the relay forwards it to its destination
+    fn native_info(packet: &Packet) -> CodingInfo {
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        CodingInfo {
+            nexthop: info.destination,
+            ..info.clone()
+        }
+    }
+
+    // NOTE: The relay coded the packet of E for C with the packet of A for D,
+    // but C receives the coded packet before it overhears the packet of A.
+    #[test]
+    fn test_decode_pending_packet() {
+        let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+        let data_e: Vec<u8> = vec![0xEE; 30];
+        let (mut leaf_a, topo_a) = leaf(a, d, vec![0xAA; 20]);
+        let (mut leaf_e, topo_e) = leaf(e, c, data_e.clone());
+        let (mut leaf_c, topo_c) = leaf(c, e, vec![]);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_e = leaf_e.handle_tx(&topo_e).unwrap().unwrap();
+        let coded = PacketBuilder::new()
+            .sender(b)
+            .encoded_header(vec![native_info(&packet_e), native_info(&packet_a)])
+            .data(PacketData::xor_many([packet_e.data(), packet_a.data()]))
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+
+        assert!(leaf_c.handle_rx(&coded, &topo_c).is_err());
+        assert_eq!(leaf_c.pending.len(), 1);
+        assert!(leaf_c.acks.is_empty());
+        assert_eq!(leaf_c.nacks, vec![native_info(&packet_e)]);
+
+        // NOTE: A retransmission is buffered, but not nacked again
+        assert!(leaf_c.handle_rx(&coded, &topo_c).is_err());
+        assert_eq!(leaf_c.nacks.len(), 1);
+
+        leaf_c.handle_rx(&packet_a, &topo_c).unwrap();
+        assert!(leaf_c.pending.is_empty());
+        assert!(leaf_c.nacks.is_empty());
+        assert_eq!(leaf_c.late_decoded, 1);
+        assert_eq!(leaf_c.late_decoded_data, data_e.len() as u32);
+        assert_eq!(leaf_c.recovered, 1);
+        assert_eq!(leaf_c.acks, vec![native_info(&packet_e)]);
+    }
+
+    // NOTE: A overhears the relay forwarding its packet for D, coded with the
+    // packet of E for C, so it does not need to wait for the ack of D.
+    #[test]
+    fn test_implicit_ack() {
+        let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+        let (mut leaf_a, topo_a) = leaf(a, d, vec![0xAA; 20]);
+        let (mut leaf_e, topo_e) = leaf(e, c, vec![0xEE; 30]);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_e = leaf_e.handle_tx(&topo_e).unwrap().unwrap();
+        let coded = PacketBuilder::new()
+            .sender(b)
+            .encoded_header(vec![native_info(&packet_e), native_info(&packet_a)])
+            .data(PacketData::xor_many([packet_e.data(), packet_a.data()]))
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+        assert_eq!(leaf_a.retrans_queue.len(), 1);
+
+        leaf_a.handle_rx(&coded, &topo_a).unwrap();
+        assert_eq!(leaf_a.retrans_queue.len(), 0);
+        assert_eq!(leaf_a.implicit_acks, 1);
+
+        // NOTE: Retransmissions of the relay and the ack of D don't count again
+        let ack = Ack {
+            source: d,
+            packets: vec![native_info(&packet_a)],
+        };
+        leaf_a.handle_rx(&coded.set_sender(b), &topo_a).unwrap();
+        let control = PacketBuilder::new()
+            .sender(b)
+            .control_header(a)
+            .ack_header(vec![ack])
+            .build()
+            .unwrap();
+        leaf_a.handle_rx(&control, &topo_a).unwrap();
+        assert_eq!(leaf_a.implicit_acks, 1);
+    }
 }
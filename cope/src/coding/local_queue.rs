@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use cope_config::types::{fairness_type::FairnessType, node_id::NodeID};
+
+use super::CodingError;
+use crate::{
+    packet::{packet::CodingHeader, CodingInfo, PacketData},
+    packet_pool::PPEntry,
+    security::NativeAuth,
+    topology::Topology,
+    traffic_generator::TrafficGenerator,
+};
+
+// NOTE: Own packets of a relay that generates traffic as well. They wait here,
+// until the fairness lets them into the packet pool of the relay, where they
+// are coded with forwarded packets like any other native. Nobody else has them
+// before they were sent once, so that is usually their retransmission.
+pub struct LocalQueue {
+    generator: TrafficGenerator,
+    native_auth: Option<NativeAuth>,
+    queue: VecDeque<PPEntry>,
+    max_size: usize,
+    fairness: FairnessType,
+    // NOTE: Earned by forwarded packets and spent by own ones, see FairnessType
+    credit: u32,
+}
+
+impl LocalQueue {
+    pub fn new(generator: TrafficGenerator, fairness: FairnessType, max_size: usize) -> Self {
+        Self {
+            generator,
+            native_auth: NativeAuth::from_config(),
+            queue: VecDeque::new(),
+            max_size,
+            fairness,
+            credit: 0,
+        }
+    }
+
+    pub fn set_tx_whitelist(&mut self, tx_whitelist: Vec<NodeID>) {
+        self.generator.set_tx_whitelist(tx_whitelist);
+    }
+
+    pub fn is_own(&self, info: &CodingInfo) -> bool {
+        info.source == self.generator.sender_id()
+    }
+
+    // NOTE: Nothing is generated while the queue is full, like leaves don't
+    // generate while their retransmission queue is full
+    pub fn generate(&mut self, topology: &Topology) -> Result<(), CodingError> {
+        if self.queue.len() >= self.max_size {
+            return Ok(());
+        }
+        let Some(builder) = self.generator.generate(topology) else {
+            return Ok(());
+        };
+        let defect = |e| {
+            CodingError::DefectPacketError(format!(
+                "[Relay {}]: Failed to build Native Packet, because {}",
+                topology.id(),
+                e
+            ))
+        };
+        let mut packet = builder.ack_header(vec![]).build().map_err(defect)?;
+        // NOTE: Natives are tagged by their source, see LeafNodeCoding
+        if let (Some(auth), CodingHeader::Native(info)) =
+            (&self.native_auth, packet.coding_header())
+        {
+            let data = auth.tag(info, packet.data());
+            packet = packet.set_data(data).map_err(defect)?;
+        }
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            unreachable!("Traffic generators only generate Native Packets");
+        };
+        self.queue.push_back((info.clone(), packet.data().clone()));
+        Ok(())
+    }
+
+    pub fn take_queued(&mut self) -> Vec<PPEntry> {
+        self.queue.drain(..).collect()
+    }
+
+    // NOTE: A forwarded packet was added to the pool
+    pub fn forwarded(&mut self) {
+        if let FairnessType::Weighted(local, forwarded) = self.fairness {
+            self.credit = (self.credit + local).min(local + forwarded);
+        }
+    }
+
+    // NOTE: Returns the next own packet, if it may be added to the pool now.
+    // Without forwarded packets waiting, it always may.
+    pub fn pop(&mut self, is_idle: bool) -> Option<(CodingInfo, PacketData)> {
+        self.queue.front()?;
+        match self.fairness {
+            FairnessType::Fifo => (),
+            FairnessType::Weighted(_, forwarded) if self.credit >= forwarded => {
+                self.credit -= forwarded;
+            }
+            FairnessType::Weighted(_, _) if is_idle => (),
+            FairnessType::Weighted(_, _) => return None,
+        }
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+
+    fn local_queue(fairness: FairnessType) -> (LocalQueue, Topology) {
+        let [b, c] = ['B', 'C'].map(NodeID::new);
+        let generator = TrafficGenerator::from_tg_type(TrafficGeneratorType::Greedy, vec![c], b);
+        let topology = Topology::new(b, b, vec![c], vec![c]);
+        (LocalQueue::new(generator, fairness, 8), topology)
+    }
+
+    #[test]
+    fn test_weighted_fairness() {
+        let (mut local, topology) = local_queue(FairnessType::Weighted(1, 2));
+        for _ in 0..10 {
+            local.generate(&topology).unwrap();
+        }
+        assert_eq!(local.queue.len(), 8);
+
+        // NOTE: One own packet for every second forwarded one
+        assert!(local.pop(false).is_none());
+        local.forwarded();
+        assert!(local.pop(false).is_none());
+        local.forwarded();
+        assert!(local.pop(false).is_some());
+        assert!(local.pop(false).is_none());
+
+        // NOTE: Credit is capped, so a burst of forwarded packets doesn't
+        // let a burst of own packets through later
+        for _ in 0..10 {
+            local.forwarded();
+        }
+        assert!(local.pop(false).is_some());
+        assert!(local.pop(false).is_none());
+        assert!(local.pop(true).is_some());
+        assert_eq!(local.queue.len(), 5);
+    }
+
+    #[test]
+    fn test_fifo_fairness() {
+        let (mut local, topology) = local_queue(FairnessType::Fifo);
+        local.generate(&topology).unwrap();
+        let (info, _) = local.pop(false).unwrap();
+        assert_eq!(info.source, NodeID::new('B'));
+        assert_eq!(info.nexthop, NodeID::new('C'));
+        assert!(local.pop(false).is_none());
+    }
+}
@@ -1,12 +1,36 @@
 mod decode_util;
+pub mod generation;
+pub mod gf256;
 pub mod leaf_node_coding;
+pub mod local_queue;
+pub mod pending_store;
+pub mod reception_tracker;
 pub mod relay_node_coding;
 pub mod retrans_queue;
+pub mod rtt_estimator;
+pub mod rlnc_leaf_coding;
+pub mod rlnc_relay_coding;
+#[cfg(test)]
+mod test_util;
 
 use core::fmt;
 
+use cope_config::types::coding_scheme::CodingScheme;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+
 use super::Packet;
-use crate::{packet::PacketData, topology::Topology};
+use crate::config::CONFIG;
+use crate::traffic_generator::TrafficGenerator;
+use crate::{
+    packet::{CodingInfo, PacketData},
+    stats::Stats,
+    topology::Topology,
+};
+use leaf_node_coding::LeafNodeCoding;
+use relay_node_coding::RelayNodeCoding;
+use rlnc_leaf_coding::RlncLeafCoding;
+use rlnc_relay_coding::RlncRelayCoding;
 
 pub const MAX_RETRANS_AMOUNT: u8 = 2;
 
@@ -18,6 +42,48 @@ pub trait CodingStrategy {
     ) -> Result<Option<PacketData>, CodingError>;
     fn handle_tx(&mut self, topology: &Topology) -> Result<Option<Packet>, CodingError>;
     fn update_last_packet_send(&mut self);
+    // NOTE: Called after every tick, for statistics only the strategy knows about
+    fn update_stats(&mut self, _stats: &mut Stats) {}
+    // NOTE: Called when discovery::Discovery changed the lists of the topology
+    fn update_neighbors(&mut self, _topology: &Topology) {}
+    // NOTE: Natives that were not delivered yet, when the role changes. The
+    // coding for the new role takes them over with requeue, see from_role.
+    // RLNC codings don't carry anything over, so their generations are lost.
+    fn take_queued(&mut self) -> Vec<(CodingInfo, PacketData)> {
+        vec![]
+    }
+    fn requeue(&mut self, _packets: Vec<(CodingInfo, PacketData)>, _topology: &Topology) {}
+    // NOTE: Called when election::RelayElection changed the relay, but not our role
+    fn update_relay(&mut self, _topology: &Topology) {}
+}
+
+// NOTE: Creates the coding for the role of the node in topology. It is created
+// again, when an election::RelayElection changes the role, so a relay that
+// becomes a leaf only starts to generate traffic then. Queued packets are
+// carried over, see CodingStrategy::take_queued.
+pub fn from_role(
+    id: NodeID,
+    topology: &Topology,
+    tgt: TrafficGeneratorType,
+) -> Box<dyn CodingStrategy + Send> {
+    let txlist = topology.txlist().to_vec();
+    match (CONFIG.coding_scheme, topology.is_relay()) {
+        // NOTE: Relays with a traffic generator are sources as well, see LocalQueue
+        (CodingScheme::Cope, true) => match tgt {
+            TrafficGeneratorType::None => Box::new(RelayNodeCoding::new(txlist)),
+            _ => Box::new(
+                RelayNodeCoding::new(txlist.clone())
+                    .with_generator(TrafficGenerator::from_tg_type(tgt, txlist, id)),
+            ),
+        },
+        (CodingScheme::Cope, false) => Box::new(LeafNodeCoding::new(
+            TrafficGenerator::from_tg_type(tgt, txlist, id),
+        )),
+        (CodingScheme::Rlnc, true) => Box::new(RlncRelayCoding::new()),
+        (CodingScheme::Rlnc, false) => Box::new(RlncLeafCoding::new(
+            TrafficGenerator::from_tg_type(tgt, txlist, id),
+        )),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +91,7 @@ pub enum CodingError {
     DecodeError(String),
     DefectPacketError(String),
     FullRetransQueue(String),
+    AuthenticationError(String),
 }
 
 impl fmt::Display for CodingError {
@@ -33,6 +100,7 @@ impl fmt::Display for CodingError {
             Self::DecodeError(str) => write!(f, "[DecodeError]: {}", str),
             Self::DefectPacketError(str) => write!(f, "[DefectPacketError]: {}", str),
             Self::FullRetransQueue(str) => write!(f, "[FullRetransQueue]: {}", str),
+            Self::AuthenticationError(str) => write!(f, "[AuthenticationError]: {}", str),
         }
     }
 }
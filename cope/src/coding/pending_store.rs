@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::packet::{CodingInfo, PacketData};
+use cope_config::types::node_id::NodeID;
+
+#[derive(Debug)]
+pub struct PendingEntry {
+    pub infos: Vec<CodingInfo>,
+    pub data: PacketData,
+    received: Instant,
+}
+
+impl PendingEntry {
+    pub fn age(&self) -> Duration {
+        self.received.elapsed()
+    }
+}
+
+// NOTE: Coded packets we could not decode yet, because we are missing some of
+// the natives they were coded with. These often arrive a moment later, so the
+// packets are kept for a while and decoded once the natives are there.
+// The store is bounded, if it is full the oldest packet is dropped. Packets
+// that are older than max_age are dropped as well, because the relay will
+// have retransmitted them by then.
+#[derive(Debug)]
+pub struct PendingStore {
+    entries: VecDeque<PendingEntry>,
+    max_size: usize,
+    max_age: Duration,
+    dropped: u32,
+}
+
+impl PendingStore {
+    pub fn new(max_size: usize, max_age: Duration) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max_size,
+            max_age,
+            dropped: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // NOTE: Retransmissions of a pending packet are only stored once,
+    // returns whether the packet was stored.
+    pub fn push(&mut self, infos: Vec<CodingInfo>, data: PacketData) -> bool {
+        if self.max_size == 0 || self.entries.iter().any(|e| e.infos == infos) {
+            return false;
+        }
+        if self.entries.len() >= self.max_size {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(PendingEntry {
+            infos,
+            data,
+            received: Instant::now(),
+        });
+        true
+    }
+
+    pub fn expire(&mut self) {
+        let len = self.entries.len();
+        self.entries.retain(|e| e.received.elapsed() < self.max_age);
+        self.dropped += (len - self.entries.len()) as u32;
+    }
+
+    // NOTE: Whether a pending packet was coded with the native of info, for a
+    // nexthop other than id. This is only the case, if we forwarded the native
+    // before, so it can be decoded by the node after us.
+    pub fn needs(&self, id: NodeID, info: &CodingInfo) -> bool {
+        self.entries
+            .iter()
+            .flat_map(|e| &e.infos)
+            .any(|x| x.key() == info.key() && x.nexthop != id)
+    }
+
+    // NOTE: Removes and returns the oldest packet, which is decodable now
+    pub fn take_decodable(
+        &mut self,
+        is_decodable: impl Fn(&[CodingInfo]) -> bool,
+    ) -> Option<PendingEntry> {
+        let pos = self.entries.iter().position(|e| is_decodable(&e.infos))?;
+        self.entries.remove(pos)
+    }
+
+    // NOTE: Removes the packets that carry the native of info for id, because
+    // id has received it some other way, e.g. in a retransmission of the relay.
+    pub fn take_resolved(&mut self, id: NodeID, info: &CodingInfo) -> Vec<PendingEntry> {
+        let is_resolved = |e: &PendingEntry| {
+            e.infos
+                .iter()
+                .any(|x| x.key() == info.key() && x.nexthop == id)
+        };
+        let (resolved, pending): (VecDeque<_>, _) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(is_resolved);
+        self.entries = pending;
+        resolved.into()
+    }
+
+    // NOTE: Returns the number of dropped packets since the last call
+    pub fn take_dropped(&mut self) -> u32 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::PacketID;
+
+    fn infos(id: u16) -> Vec<CodingInfo> {
+        ['A', 'C']
+            .map(|node| CodingInfo {
+                source: NodeID::new(node),
+                id: PacketID::new(id),
+                nexthop: NodeID::new(node),
+                destination: NodeID::new(node),
+                len: 1,
+            })
+            .to_vec()
+    }
+
+    #[test]
+    fn test_bounded_store() {
+        let mut store = PendingStore::new(2, Duration::from_secs(60));
+        for id in 0..3 {
+            store.push(infos(id), PacketData::new(vec![id as u8]));
+        }
+        store.push(infos(2), PacketData::new(vec![2]));
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.take_dropped(), 1);
+        assert_eq!(store.take_dropped(), 0);
+
+        assert!(store
+            .take_decodable(|infos| infos[0].id.value() == 0)
+            .is_none());
+        let entry = store
+            .take_decodable(|infos| infos[0].id.value() == 2)
+            .unwrap();
+        assert_eq!(entry.data, PacketData::new(vec![2]));
+        assert_eq!(store.len(), 1);
+
+        let resolved = &infos(1)[1];
+        assert!(store.take_resolved(NodeID::new('A'), resolved).is_empty());
+        assert_eq!(store.take_resolved(NodeID::new('C'), resolved).len(), 1);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut store = PendingStore::new(2, Duration::ZERO);
+        store.push(infos(0), PacketData::new(vec![0]));
+        store.expire();
+        assert!(store.is_empty());
+        assert_eq!(store.take_dropped(), 1);
+    }
+}
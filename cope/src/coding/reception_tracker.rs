@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use bitvec::prelude as bv;
+use cope_config::types::node_id::NodeID;
+
+use crate::packet::{reception_report::REPORT_WINDOW, CodingInfo, PacketID, ReceptionReport};
+
+#[derive(Debug)]
+struct ReceptionState {
+    last_id: PacketID,
+    preceding_ids: bv::BitVec,
+    // NOTE: Set if something was received since the last report was sent
+    pending: bool,
+}
+
+// NOTE: Keeps track of all native packets a node has overheard, so they can be
+// reported to the relay using reception reports.
+#[derive(Debug, Default)]
+pub struct ReceptionTracker {
+    states: HashMap<NodeID, ReceptionState>,
+}
+
+impl ReceptionTracker {
+    pub fn new() -> Self {
+        Self {
+            states: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, info: &CodingInfo) {
+        let Some(state) = self.states.get_mut(&info.source) else {
+            self.states.insert(
+                info.source,
+                ReceptionState {
+                    last_id: info.id,
+                    preceding_ids: bv::bitvec![0; REPORT_WINDOW],
+                    pending: true,
+                },
+            );
+            return;
+        };
+
+        match info.id.partial_cmp(&state.last_id) {
+            Some(Ordering::Equal) => (),
+            Some(Ordering::Less) => {
+                let distance = state.last_id.offset_from(info.id) as usize;
+                if distance > REPORT_WINDOW {
+                    return;
+                }
+                state.preceding_ids.set(distance - 1, true);
+            }
+            // NOTE: Ids that are half the id space apart can not be ordered,
+            // so we treat them like a newer id that is too far ahead.
+            Some(Ordering::Greater) | None => {
+                let distance = info.id.offset_from(state.last_id) as usize;
+                if distance > REPORT_WINDOW {
+                    state.preceding_ids.fill(false);
+                } else {
+                    state.preceding_ids.rotate_right(distance);
+                    state.preceding_ids[..distance].fill(false);
+                    state.preceding_ids.set(distance - 1, true);
+                }
+                state.last_id = info.id;
+            }
+        }
+
+        state.pending = true;
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.states.values().any(|state| state.pending)
+    }
+
+    // NOTE: Returns a report for every source we received something new from,
+    // since the last call.
+    pub fn take_reports(&mut self) -> Vec<ReceptionReport> {
+        self.states
+            .iter_mut()
+            .filter(|(_, state)| state.pending)
+            .map(|(&source, state)| {
+                state.pending = false;
+                ReceptionReport::new(source, state.last_id, state.preceding_ids.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(id: u16) -> CodingInfo {
+        CodingInfo {
+            source: NodeID::new('A'),
+            id: PacketID::new(id),
+            nexthop: NodeID::new('C'),
+            destination: NodeID::new('C'),
+            len: 0,
+        }
+    }
+
+    fn ids(report: &ReceptionReport) -> Vec<u16> {
+        report.ids().iter().map(|id| id.value()).collect()
+    }
+
+    #[test]
+    fn test_in_order_reception() {
+        let mut tracker = ReceptionTracker::new();
+        tracker.record(&info(1));
+        tracker.record(&info(2));
+        tracker.record(&info(4));
+
+        let reports = tracker.take_reports();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].last_id(), PacketID::new(4));
+        assert_eq!(ids(&reports[0]), vec![4, 2, 1]);
+    }
+
+    #[test]
+    fn test_out_of_order_reception() {
+        let mut tracker = ReceptionTracker::new();
+        tracker.record(&info(5));
+        tracker.record(&info(3));
+        tracker.record(&info(20));
+        tracker.record(&info(19));
+
+        let reports = tracker.take_reports();
+        assert_eq!(ids(&reports[0]), vec![20, 19]);
+    }
+
+    #[test]
+    fn test_reception_across_wrap() {
+        let mut tracker = ReceptionTracker::new();
+        tracker.record(&info(65534));
+        tracker.record(&info(0));
+        tracker.record(&info(65535));
+        tracker.record(&info(2));
+        tracker.record(&info(65533));
+
+        let reports = tracker.take_reports();
+        assert_eq!(reports[0].last_id(), PacketID::new(2));
+        assert_eq!(ids(&reports[0]), vec![2, 0, 65535, 65534, 65533]);
+    }
+
+    #[test]
+    fn test_reports_are_only_sent_once() {
+        let mut tracker = ReceptionTracker::new();
+        assert!(!tracker.has_pending());
+        tracker.record(&info(1));
+        assert!(tracker.has_pending());
+        assert_eq!(tracker.take_reports().len(), 1);
+        assert!(!tracker.has_pending());
+        assert!(tracker.take_reports().is_empty());
+    }
+}
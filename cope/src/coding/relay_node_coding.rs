@@ -4,21 +4,50 @@ use cope_config::types::node_id::NodeID;
 
 use crate::{
     config::CONFIG,
-    kbase::{KBase, SimpleKBase},
+    kbase::{KBase, LinkEstimator, SimpleKBase},
     packet::{packet::CodingHeader, Ack, CodingInfo, PacketBuilder, PacketData},
-    packet_pool::{PacketPool, SimplePacketPool},
+    packet_pool::{PacketPool, SimplePacketPool, VirtualQueuePool},
+    scheduler::{self, CodingScheduler, PoolState},
+    stats::Stats,
     topology::Topology,
+    traffic_generator::TrafficGenerator,
     Packet,
 };
 
-use super::{retrans_queue::RetransQueue, CodingError, CodingStrategy};
+use super::{
+    decode_util::{decode, ids_for_decoding, is_next_hop},
+    local_queue::LocalQueue,
+    pending_store::PendingStore,
+    retrans_queue::RetransQueue,
+    CodingError, CodingStrategy,
+};
+
+// NOTE: Every native of a coded packet is tracked on its own, so the queue
+// holds more natives than the pool
+const RETRANS_QUEUE_FACTOR: usize = 4;
 
 pub struct RelayNodeCoding {
-    packet_pool: SimplePacketPool,
+    packet_pool: VirtualQueuePool,
+    // NOTE: Natives we have sent or overheard, so we can decode packets that
+    // other relays coded with them.
+    decode_pool: SimplePacketPool,
+    // NOTE: Coded packets for us, that we could not decode yet, see PendingStore
+    pending: PendingStore,
+    late_decoded: u32,
+    late_decoded_data: u32,
     kbase: SimpleKBase,
+    links: LinkEstimator,
     retrans_queue: RetransQueue,
+    scheduler: Box<dyn CodingScheduler + Send>,
     acks: Vec<Ack>,
     last_packet_send: Instant,
+    padding_sent: u32,
+    padding_saved: u32,
+    // NOTE: Only set, if the relay generates traffic of its own
+    local: Option<LocalQueue>,
+    local_sent: u32,
+    forwarded_sent: u32,
+    use_coding: bool,
 }
 
 impl RelayNodeCoding {
@@ -27,14 +56,40 @@ impl RelayNodeCoding {
         let rtt = CONFIG.round_trip_time;
 
         Self {
-            packet_pool: SimplePacketPool::new(sz),
+            packet_pool: VirtualQueuePool::new(sz),
+            decode_pool: SimplePacketPool::new(0).with_overheard_size(sz),
+            pending: PendingStore::new(sz, rtt),
+            late_decoded: 0,
+            late_decoded_data: 0,
             kbase: SimpleKBase::new(tx_list, sz),
-            retrans_queue: RetransQueue::new(sz, rtt),
+            links: LinkEstimator::new(),
+            retrans_queue: RetransQueue::new(sz * RETRANS_QUEUE_FACTOR, rtt),
+            scheduler: scheduler::from_type(CONFIG.coding_scheduler),
             acks: vec![],
             last_packet_send: Instant::now(),
+            padding_sent: 0,
+            padding_saved: 0,
+            local: None,
+            local_sent: 0,
+            forwarded_sent: 0,
+            use_coding: CONFIG.use_coding,
         }
     }
 
+    // NOTE: Overrides CONFIG.use_coding, e.g. to compare both in a test
+    pub fn with_coding(mut self, use_coding: bool) -> Self {
+        self.use_coding = use_coding;
+        self
+    }
+
+    // NOTE: Makes this a hybrid node, that codes its own packets with the
+    // forwarded ones, and shares the pool by CONFIG.hybrid_fairness
+    pub fn with_generator(mut self, generator: TrafficGenerator) -> Self {
+        let sz = CONFIG.packet_pool_size;
+        self.local = Some(LocalQueue::new(generator, CONFIG.hybrid_fairness, sz));
+        self
+    }
+
     fn all_nexhops_can_decode(
         &self,
         packets: &Vec<(CodingInfo, PacketData)>,
@@ -48,20 +103,112 @@ impl RelayNodeCoding {
             return false;
         }
 
-        let iter = std::iter::once(packet).chain(packets);
-
-        for (CodingInfo { nexthop, .. }, _) in iter {
-            let iter1 = std::iter::once(packet).chain(packets);
-            for (info, _) in iter1 {
-                let knows = self.kbase.knows(nexthop, info);
-                let is_nexthop = *nexthop == info.nexthop;
+        let infos: Vec<&CodingInfo> = std::iter::once(packet)
+            .chain(packets)
+            .map(|(info, _)| info)
+            .collect();
+        self.guesses(&infos).is_some()
+    }
 
-                if !knows && !is_nexthop {
-                    return false;
+    // NOTE: Returns the packets each nexthop has to have overheard without us knowing,
+    // or None if the probability that some nexthop can decode is below the threshold.
+    fn guesses(&self, infos: &[&CodingInfo]) -> Option<Vec<(CodingInfo, Vec<CodingInfo>)>> {
+        let mut guesses = vec![];
+        for packet in infos {
+            let nexthop = packet.nexthop;
+            let mut probability = 1.0;
+            let mut guessed = vec![];
+            for &info in infos {
+                if self.kbase.knows(&nexthop, info) || nexthop == info.nexthop {
+                    continue;
+                }
+                // NOTE: Our own packets are only known from reports, nobody
+                // overheard them before we sent them
+                if self.local.as_ref().is_some_and(|local| local.is_own(info)) {
+                    return None;
                 }
+                probability *= self.links.delivery_probability(info.source, nexthop);
+                guessed.push(info.clone());
             }
+
+            if guessed.is_empty() {
+                continue;
+            }
+            if CONFIG.guess_threshold >= 1.0 || probability < CONFIG.guess_threshold {
+                return None;
+            }
+            guesses.push(((*packet).clone(), guessed));
+        }
+        Some(guesses)
+    }
+
+    // NOTE: Acks packets we received as their nexthop from another relay,
+    // or as their destination, which nobody else would ack.
+    fn ack(&mut self, id: NodeID, info: CodingInfo) {
+        match self.acks.iter_mut().find(|ack| ack.source == id) {
+            Some(ack) => ack.packets.push(info),
+            None => self.acks.push(Ack {
+                source: id,
+                packets: vec![info],
+            }),
         }
-        true
+    }
+
+    // NOTE: Queues the native for the next hop to its destination, unless we are it
+    fn forward(&mut self, info: &CodingInfo, data: PacketData, topo: &Topology) {
+        if info.destination == topo.id() {
+            return;
+        }
+        let nexthop = topo.nexthop_for_target(info.destination);
+        self.packet_pool.push_native(
+            CodingInfo {
+                nexthop,
+                ..info.clone()
+            },
+            data,
+        );
+        if let Some(local) = &mut self.local {
+            local.forwarded();
+        }
+    }
+
+    // NOTE: Decodes the pending packets, whose natives are in the decode pool by
+    // now, and forwards them like packets we could decode right away. Their
+    // previous hop is not known anymore, so the knowledge base is not updated.
+    fn decode_pending(&mut self, topo: &Topology) {
+        let id = topo.id();
+        loop {
+            let pool = &self.decode_pool;
+            let Some(entry) = self
+                .pending
+                .take_decodable(|infos| ids_for_decoding(id, infos, pool).is_ok())
+            else {
+                return;
+            };
+            let (ids, info) = ids_for_decoding(id, &entry.infos, &self.decode_pool).unwrap();
+            let data = decode(&ids, &info, &entry.data, &self.decode_pool);
+            log::debug!("[Relay {}]: Decoded pending {}", id, info);
+            self.late_decoded += 1;
+            self.late_decoded_data += data.len() as u32;
+            self.ack(id, info.clone());
+            self.forward(&info, data, topo);
+        }
+    }
+
+    // NOTE: Own packets only take free room in the pool, so they never push
+    // out forwarded ones
+    fn admit_local(&mut self, topo: &Topology) -> Result<(), CodingError> {
+        let Some(local) = &mut self.local else {
+            return Ok(());
+        };
+        local.generate(topo)?;
+        while self.packet_pool.size() < CONFIG.packet_pool_size {
+            let Some((info, data)) = local.pop(self.packet_pool.size() == 0) else {
+                break;
+            };
+            self.packet_pool.push_native(info, data);
+        }
+        Ok(())
     }
 
     fn should_tx_control(&self) -> bool {
@@ -71,36 +218,81 @@ impl RelayNodeCoding {
         self.last_packet_send.elapsed() > CONFIG.control_packet_duration
     }
 
-    fn has_coding_opp(&self) -> bool {
-        self.packet_pool.unique_nexthops() >= 2
-            || self.last_packet_send.elapsed() > CONFIG.control_packet_duration
+    fn has_coding_opp(&self, topo: &Topology) -> bool {
+        let Some(oldest_age) = self.packet_pool.oldest_age() else {
+            return false;
+        };
+        self.scheduler.should_transmit(&PoolState {
+            packets: self.packet_pool.size(),
+            waiting_nexthops: self.packet_pool.unique_nexthops(),
+            nexthops: topo.txlist().len(),
+            oldest_age,
+        })
     }
 
+    // NOTE: is_retransmission is set for packets from the retransmission queue.
+    // They are still in there, unless it was their last retransmission.
     fn code_packet(
         &mut self,
         packet: (CodingInfo, PacketData),
+        is_retransmission: bool,
         topo: &Topology,
     ) -> Result<Packet, CodingError> {
+        let len = packet.1.len();
         let mut packets: Vec<(CodingInfo, PacketData)> = vec![packet];
-        if CONFIG.use_coding {
+        // NOTE: Lengths of the packets we would have coded, if we always took the
+        // oldest packet of each nexthop, to see how much padding we saved
+        let mut oldest_lens = vec![len];
+        if self.use_coding {
             for &nexthop in topo.txlist() {
-                let Some(packet) = self.packet_pool.peek_nexthop_front(nexthop) else {
+                let Some(packet) = self.packet_pool.peek_nexthop_similar(nexthop, len) else {
                     continue;
                 };
 
                 if self.all_nexhops_can_decode(&packets, packet) {
-                    let p = self.packet_pool.pop_nexthop_front(nexthop).unwrap();
+                    let oldest = self.packet_pool.peek_nexthop_front(nexthop).unwrap();
+                    let oldest_len = match self.all_nexhops_can_decode(&packets, oldest) {
+                        true => oldest.1.len(),
+                        false => packet.1.len(),
+                    };
+                    oldest_lens.push(oldest_len);
+                    let p = self.packet_pool.pop_nexthop_similar(nexthop, len).unwrap();
                     packets.push(p);
                 }
             }
         }
+        let lens: Vec<usize> = packets.iter().map(|(_, data)| data.len()).collect();
+        self.padding_sent += padding(&lens) as u32;
+        self.padding_saved += padding(&oldest_lens).saturating_sub(padding(&lens)) as u32;
+
+        if packets.len() > 1 {
+            let infos: Vec<&CodingInfo> = packets.iter().map(|(info, _)| info).collect();
+            for (info, guessed) in self.guesses(&infos).unwrap_or_default() {
+                log::debug!(
+                    "[Relay {}]: Guessed that {} can decode.",
+                    topo.id(),
+                    info.nexthop
+                );
+                self.links.record_guess(info.nexthop, info, guessed);
+            }
+        }
         let (header, data) = encode(&packets);
-        // schedule retransmission
-        for p in &packets {
-            if self.retrans_queue.conatains(&p.0) {
+        // NOTE: Every native is tracked on its own, so each nexthop acks only its own
+        // native, and unacked natives can be coded with different partners next time.
+        // Natives can be in the pool and the retransmission queue at the same time,
+        // if their source retransmitted them.
+        for (i, p) in packets.iter().enumerate() {
+            let is_new = i > 0 || !is_retransmission;
+            if is_new && !self.retrans_queue.conatains(&p.0) {
+                match p.0.source == topo.id() {
+                    true => self.local_sent += 1,
+                    false => self.forwarded_sent += 1,
+                }
                 self.retrans_queue.push_new(p.clone());
             }
+            self.decode_pool.push_overheard_native(p.0.clone(), p.1.clone());
         }
+        self.decode_pending(topo);
 
         let coded_packet = PacketBuilder::new()
             .sender(topo.id())
@@ -113,15 +305,40 @@ impl RelayNodeCoding {
 
         Ok(coded_packet)
     }
+
+    // NOTE: Answers a NACK with the native on its own, because the nexthop
+    // was missing a packet it was coded with before.
+    fn resend_decoded(
+        &mut self,
+        packet: (CodingInfo, PacketData),
+        topo: &Topology,
+    ) -> Result<Packet, CodingError> {
+        let (header, data) = encode(&vec![packet]);
+        PacketBuilder::new()
+            .sender(topo.id())
+            .data(data)
+            .encoded_header(header)
+            .ack_header(std::mem::take(&mut self.acks))
+            .build()
+            .map_err(|e| {
+                CodingError::DefectPacketError(format!(
+                    "[Relay {}]: Failed to build Encoded Packet, because {}",
+                    topo.id(),
+                    e
+                ))
+            })
+    }
+}
+
+// NOTE: Bytes of zero padding, needed to xor packets with the given lengths
+fn padding(lens: &[usize]) -> usize {
+    let max = lens.iter().copied().max().unwrap_or(0);
+    lens.iter().map(|len| max - len).sum()
 }
 
 fn encode(packets: &Vec<(CodingInfo, PacketData)>) -> (Vec<CodingInfo>, PacketData) {
     let info = packets.iter().cloned().map(|p| p.0).collect();
-    let data = packets
-        .iter()
-        .cloned()
-        .map(|p| p.1)
-        .fold(PacketData::new(vec![]), |acc, x| acc.xor(&x));
+    let data = PacketData::xor_many(packets.iter().map(|p| &p.1));
     (info, data)
 }
 
@@ -133,36 +350,95 @@ impl CodingStrategy for RelayNodeCoding {
     ) -> Result<Option<PacketData>, CodingError> {
         let original_data = packet.data().clone();
 
-        if let CodingHeader::Control(_) = packet.coding_header() {
-            let acks = packet.ack_header();
-            for ack in acks {
-                for info in ack.packets() {
-                    log::debug!("[Relay {}]: Packet {} was acked.", topology.id(), info);
-                    self.retrans_queue.remove_packet(info);
-                }
-                self.acks.push(ack.clone());
-            }
-            return Ok(Some(original_data));
+        for report in packet.reception_header() {
+            log::debug!(
+                "[Relay {}]: {} reports {:?} from {}.",
+                topology.id(),
+                packet.sender(),
+                report.ids(),
+                report.source()
+            );
+            self.kbase.insert_report(packet.sender(), report);
+            self.links.insert_report(packet.sender(), report);
         }
 
-        let CodingHeader::Native(coding_info) = packet.coding_header() else {
-            return Err(CodingError::DefectPacketError(
-                "Expected to receive Native Packet".into(),
-            ));
-        };
+        // NOTE: Only the nexthop of a packet can nack it
+        for info in packet.nack_header() {
+            if info.nexthop == packet.sender() && self.retrans_queue.nack(info) {
+                log::debug!("[Relay {}]: Packet {} was nacked.", topology.id(), info);
+            }
+        }
 
         let acks = packet.ack_header();
         for ack in acks {
             for info in ack.packets() {
                 log::debug!("[Relay {}]: Packet {} was acked.", topology.id(), info);
                 self.retrans_queue.remove_packet(info);
+                self.links.ack_received(info);
+            }
+            // NOTE: Leaves send an ack with every packet, even if it is empty.
+            // Acks are only forwarded by the relay that heard them from their
+            // source, other relays overhear it and acks of relays are per hop.
+            // Acks of our own packets end here.
+            let is_from_source = ack.source == packet.sender();
+            let packets: Vec<CodingInfo> = ack
+                .packets()
+                .iter()
+                .filter(|info| info.source != topology.id())
+                .cloned()
+                .collect();
+            if !packets.is_empty() && is_from_source && !topology.is_relay_node(ack.source) {
+                self.acks.push(Ack {
+                    source: ack.source,
+                    packets,
+                });
             }
-            self.acks.push(ack.clone());
         }
+
+        let (coding_info, data) = match packet.coding_header() {
+            CodingHeader::Control(_) | CodingHeader::Routing(_) | CodingHeader::Beacon(_) => {
+                return Ok(Some(original_data))
+            }
+            // NOTE: Natives for other relays are kept, in case they code them with packets for us
+            CodingHeader::Native(info) if info.nexthop != topology.id() => {
+                self.decode_pool.push_overheard(packet.clone());
+                self.decode_pending(topology);
+                return Ok(Some(original_data));
+            }
+            CodingHeader::Native(info) => {
+                if info.destination == topology.id() {
+                    self.ack(topology.id(), info.clone());
+                }
+                (info.clone(), original_data)
+            }
+            CodingHeader::Encoded(infos) => {
+                if !is_next_hop(topology.id(), infos) {
+                    return Ok(Some(original_data));
+                }
+                // NOTE: Like leaves, we keep packets we can't decode yet, until the
+                // missing natives arrive. Relays on a chain forward them afterwards.
+                let (ids, info) = match ids_for_decoding(topology.id(), infos, &self.decode_pool) {
+                    Ok(decodable) => decodable,
+                    Err(e) => {
+                        self.pending.push(infos.clone(), original_data);
+                        return Err(e);
+                    }
+                };
+                let data = decode(&ids, &info, packet.data(), &self.decode_pool);
+                self.ack(topology.id(), info.clone());
+                (info, data)
+            }
+            CodingHeader::Linear(_) => {
+                return Err(CodingError::DefectPacketError(
+                    "COPE can not handle Linear Packets".into(),
+                ));
+            }
+        };
+
         // append knowledge base
         self.kbase.insert(packet.sender(), coding_info.clone());
         // add to packet pool
-        self.packet_pool.push_packet(packet.clone());
+        self.forward(&coding_info, data.clone(), topology);
         log::debug!(
             "[Relay {}]: Has stored {} packages and knows about {}",
             topology.id(),
@@ -170,25 +446,36 @@ impl CodingStrategy for RelayNodeCoding {
             self.kbase.size()
         );
 
-        Ok(Some(original_data))
+        Ok(Some(data))
     }
 
     fn handle_tx(&mut self, topology: &Topology) -> Result<Option<Packet>, CodingError> {
+        self.pending.expire();
+        self.links.expire_guesses(self.retrans_queue.rto());
+        if let Some(packet) = self.retrans_queue.take_nacked() {
+            log::debug!("[Relay {}]: Resend nacked {}", topology.id(), packet.0);
+            return self.resend_decoded(packet, topology).map(Some);
+        }
         if let Some(packet) = self.retrans_queue.packet_to_retrans() {
-            let coded_packet = self.code_packet(packet, topology)?;
+            log::debug!("[Relay {}]: Retransmit {}", topology.id(), packet.0);
+            let coded_packet = self.code_packet(packet, true, topology)?;
             return Ok(Some(coded_packet));
         }
 
-        if self.retrans_queue.is_full() {
-            return Err(CodingError::FullRetransQueue(format!(
-                "[Relay {}]:Cannot send new packet, without dropping old Packet.",
+        // NOTE: With a full queue no new packets are forwarded, until some are
+        // acked or dropped, but pending acks still go out with control packets
+        let is_full = self.retrans_queue.is_full();
+        if is_full {
+            log::debug!(
+                "[Relay {}]: Cannot send new packet, without dropping old Packet.",
                 topology.id()
-            )));
+            );
         }
 
-        if !self.has_coding_opp() {
+        self.admit_local(topology)?;
+        if is_full || !self.has_coding_opp(topology) {
             if self.should_tx_control() {
-                let receiver = *topology.txlist().first().unwrap();
+                let receiver = topology.control_receiver();
                 let result = PacketBuilder::new()
                     .sender(topology.id())
                     .control_header(receiver)
@@ -212,7 +499,7 @@ impl CodingStrategy for RelayNodeCoding {
         let Some(packet) = self.packet_pool.pop_front() else {
             return Ok(None);
         };
-        let coded_packet = self.code_packet(packet, topology)?;
+        let coded_packet = self.code_packet(packet, false, topology)?;
 
         Ok(Some(coded_packet))
     }
@@ -220,4 +507,574 @@ impl CodingStrategy for RelayNodeCoding {
     fn update_last_packet_send(&mut self) {
         self.last_packet_send = Instant::now();
     }
+
+    fn update_neighbors(&mut self, topology: &Topology) {
+        for &next_hop in topology.txlist() {
+            self.kbase.insert_next_hop(next_hop);
+        }
+        if let Some(local) = &mut self.local {
+            local.set_tx_whitelist(topology.txlist().to_vec());
+        }
+    }
+
+    // NOTE: Unacked packets come first, since they were sent before the others.
+    // Natives can be in the pool and the retransmission queue, see code_packet.
+    fn take_queued(&mut self) -> Vec<(CodingInfo, PacketData)> {
+        let mut queued = self.retrans_queue.take_all();
+        while let Some(packet) = self.packet_pool.pop_front() {
+            if !queued.iter().any(|(info, _)| info.key() == packet.0.key()) {
+                queued.push(packet);
+            }
+        }
+        if let Some(local) = &mut self.local {
+            queued.extend(local.take_queued());
+        }
+        queued
+    }
+
+    // NOTE: Packets that don't fit into the pool are dropped
+    fn requeue(&mut self, packets: Vec<(CodingInfo, PacketData)>, topology: &Topology) {
+        let mut dropped = 0;
+        for (info, data) in packets {
+            if info.destination == topology.id() {
+                continue;
+            }
+            if self.packet_pool.size() >= CONFIG.packet_pool_size {
+                dropped += 1;
+                continue;
+            }
+            let nexthop = topology.nexthop_for_target(info.destination);
+            self.packet_pool
+                .push_native(CodingInfo { nexthop, ..info }, data);
+        }
+        if dropped > 0 {
+            log::warn!(
+                "[Relay {}]: Dropped {} queued packets",
+                topology.id(),
+                dropped
+            );
+        }
+    }
+
+    fn update_stats(&mut self, stats: &mut Stats) {
+        let (correct, wrong) = self.links.take_outcomes();
+        stats.add_guesses(correct, wrong);
+        stats.add_padding(
+            std::mem::take(&mut self.padding_sent),
+            std::mem::take(&mut self.padding_saved),
+        );
+        stats.add_late_decoded(
+            std::mem::take(&mut self.late_decoded),
+            std::mem::take(&mut self.late_decoded_data),
+        );
+        stats.add_pending_dropped(self.pending.take_dropped());
+        stats.add_retrans_dropped(self.retrans_queue.take_dropped());
+        stats.set_rto(self.retrans_queue.rto());
+        stats.add_relay_natives(
+            std::mem::take(&mut self.local_sent),
+            std::mem::take(&mut self.forwarded_sent),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bitvec::prelude as bv;
+
+    use super::*;
+    use crate::{
+        coding::{
+            leaf_node_coding::LeafNodeCoding,
+            test_util::{leaf, once},
+        },
+        packet::{reception_report::REPORT_WINDOW, PacketID, ReceptionReport},
+        traffic_generator::TrafficGenerator,
+    };
+    use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+
+    #[test]
+    fn test_mixed_size_encode_decode() {
+        let node_a = NodeID::new('A');
+        let node_b = NodeID::new('B');
+        let node_c = NodeID::new('C');
+        let data_a: Vec<u8> = (0..10).collect();
+        let data_c: Vec<u8> = (0..100).map(|i| 0xFF - i).collect();
+
+        let (mut leaf_a, topo_a) = leaf(node_a, node_c, data_a.clone());
+        let (mut leaf_c, topo_c) = leaf(node_c, node_a, data_c.clone());
+        let topo_b = Topology::new(node_b, node_b, vec![node_a, node_c], vec![node_a, node_c]);
+        let mut relay = RelayNodeCoding::new(vec![node_a, node_c]);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        relay.handle_rx(&packet_a, &topo_b).unwrap();
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let coded = relay.code_packet(packet, false, &topo_b).unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+        assert_eq!(coded.data().len(), data_c.len());
+
+        let decoded_a = leaf_a.handle_rx(&coded, &topo_a).unwrap().unwrap();
+        let decoded_c = leaf_c.handle_rx(&coded, &topo_c).unwrap().unwrap();
+        assert_eq!(decoded_a, PacketData::new(data_c));
+        assert_eq!(decoded_c, PacketData::new(data_a));
+    }
+
+    // NOTE: The "X" topology, A sends to D and E sends to C. C overhears A and
+    // D overhears E, so the relay can code both packets together.
+    #[test]
+    fn test_decode_with_overheard_packets() {
+        let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+        let data_a: Vec<u8> = vec![0xAA; 20];
+        let data_e: Vec<u8> = vec![0xEE; 30];
+
+        let (mut leaf_a, topo_a) = leaf(a, d, data_a.clone());
+        let (mut leaf_e, topo_e) = leaf(e, c, data_e.clone());
+        let (mut leaf_c, topo_c) = leaf(c, e, vec![0xCC]);
+        let (mut leaf_d, topo_d) = leaf(d, c, vec![0xDD]);
+        let nodes = vec![a, c, d, e];
+        let topo_b = Topology::new(b, b, nodes.clone(), nodes.clone());
+        let mut relay = RelayNodeCoding::new(nodes);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_e = leaf_e.handle_tx(&topo_e).unwrap().unwrap();
+        leaf_c.handle_rx(&packet_a, &topo_c).unwrap();
+        leaf_d.handle_rx(&packet_e, &topo_d).unwrap();
+
+        // NOTE: The reception reports tell the relay what C and D overheard
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        let packet_d = leaf_d.handle_tx(&topo_d).unwrap().unwrap();
+        for packet in [&packet_a, &packet_e, &packet_c, &packet_d] {
+            relay.handle_rx(packet, &topo_b).unwrap();
+        }
+
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let coded = relay.code_packet(packet, false, &topo_b).unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[1].source, e);
+
+        let decoded_c = leaf_c.handle_rx(&coded, &topo_c).unwrap().unwrap();
+        let decoded_d = leaf_d.handle_rx(&coded, &topo_d).unwrap().unwrap();
+        assert_eq!(decoded_c, PacketData::new(data_e));
+        assert_eq!(decoded_d, PacketData::new(data_a));
+    }
+
+    // NOTE: Same as above, but the reports of C and D are lost. The relay
+    // only knows from earlier reports that C and D overhear A and E well.
+    #[test]
+    fn test_guess_without_reports() {
+        let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+        let data_a: Vec<u8> = vec![0xAA; 20];
+        let data_e: Vec<u8> = vec![0xEE; 30];
+
+        let (mut leaf_a, topo_a) = leaf(a, d, data_a.clone());
+        let (mut leaf_e, topo_e) = leaf(e, c, data_e.clone());
+        let (mut leaf_c, topo_c) = leaf(c, e, vec![0xCC]);
+        let (mut leaf_d, topo_d) = leaf(d, c, vec![0xDD]);
+        let nodes = vec![a, c, d, e];
+        let topo_b = Topology::new(b, b, nodes.clone(), nodes.clone());
+        let mut relay = RelayNodeCoding::new(nodes);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_e = leaf_e.handle_tx(&topo_e).unwrap().unwrap();
+        leaf_c.handle_rx(&packet_a, &topo_c).unwrap();
+        leaf_d.handle_rx(&packet_e, &topo_d).unwrap();
+        relay.handle_rx(&packet_a, &topo_b).unwrap();
+        relay.handle_rx(&packet_e, &topo_b).unwrap();
+
+        // NOTE: Without any estimates, the relay does not guess
+        let packet = relay.packet_pool.peek_nexthop_front(d).unwrap().clone();
+        let other = relay.packet_pool.peek_nexthop_front(c).unwrap();
+        assert!(!relay.all_nexhops_can_decode(&vec![packet], other));
+
+        let all = bv::bitvec![1; REPORT_WINDOW];
+        let old_id = PacketID::new(100);
+        relay
+            .links
+            .insert_report(c, &ReceptionReport::new(a, old_id, all.clone()));
+        relay
+            .links
+            .insert_report(d, &ReceptionReport::new(e, old_id, all));
+
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let coded = relay.code_packet(packet, false, &topo_b).unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+
+        let decoded_c = leaf_c.handle_rx(&coded, &topo_c).unwrap().unwrap();
+        let decoded_d = leaf_d.handle_rx(&coded, &topo_d).unwrap().unwrap();
+        assert_eq!(decoded_c, PacketData::new(data_e));
+        assert_eq!(decoded_d, PacketData::new(data_a));
+
+        // NOTE: The acks of C and D confirm both guesses
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        let packet_d = leaf_d.handle_tx(&topo_d).unwrap().unwrap();
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+        relay.handle_rx(&packet_d, &topo_b).unwrap();
+        assert_eq!(relay.links.take_outcomes(), (2, 0));
+    }
+
+    #[test]
+    fn test_code_similar_size() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let native = |source: NodeID, id: u16, destination: NodeID, len: usize| {
+            PacketBuilder::new()
+                .sender(source)
+                .native_header(CodingInfo {
+                    source,
+                    id: PacketID::new(id),
+                    nexthop: b,
+                    destination,
+                    len: 0,
+                })
+                .ack_header(vec![])
+                .data_raw(vec![0x42; len])
+                .build()
+                .unwrap()
+        };
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RelayNodeCoding::new(vec![a, c]);
+        relay.handle_rx(&native(a, 1, c, 10), &topo_b).unwrap();
+        relay.handle_rx(&native(c, 1, a, 500), &topo_b).unwrap();
+        relay.handle_rx(&native(c, 2, a, 20), &topo_b).unwrap();
+
+        // NOTE: The oldest packet for A is much larger, so the newer one is used
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let coded = relay.code_packet(packet, false, &topo_b).unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[1].id, PacketID::new(2));
+        assert_eq!(coded.data().len(), 20);
+        assert_eq!(relay.padding_sent, 10);
+        assert_eq!(relay.padding_saved, 480);
+    }
+
+    #[test]
+    fn test_retransmit_with_new_partner() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let (mut leaf_a, topo_a) = leaf(a, c, vec![0xAA; 10]);
+        let (mut leaf_c, topo_c) = leaf(c, a, vec![0xCC; 10]);
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RelayNodeCoding::new(vec![a, c]);
+        relay.retrans_queue = RetransQueue::new(8, Duration::ZERO);
+
+        // NOTE: The packet of A is sent on its own, but C never receives it
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        relay.handle_rx(&packet_a, &topo_b).unwrap();
+        let packet = relay.packet_pool.pop_front().unwrap();
+        relay.code_packet(packet, false, &topo_b).unwrap();
+        assert_eq!(relay.retrans_queue.len(), 1);
+
+        // NOTE: Meanwhile C has sent a packet, which the retransmission is coded with
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+        let coded = relay.handle_tx(&topo_b).unwrap().unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+        assert_eq!(relay.retrans_queue.len(), 2);
+
+        // NOTE: Both nexthops ack their own native
+        assert_eq!(
+            leaf_c.handle_rx(&coded, &topo_c).unwrap().unwrap(),
+            PacketData::new(vec![0xAA; 10])
+        );
+        leaf_a.handle_rx(&coded, &topo_a).unwrap();
+        for (leaf, topo) in [(&mut leaf_c, &topo_c), (&mut leaf_a, &topo_a)] {
+            leaf.set_last_packet_send(Instant::now() - CONFIG.control_packet_duration * 2);
+            let ack = leaf.handle_tx(topo).unwrap().unwrap();
+            relay.handle_rx(&ack, &topo_b).unwrap();
+        }
+        assert_eq!(relay.retrans_queue.len(), 0);
+    }
+
+    // NOTE: The packet of A is unacked and fills the queue, so the packet of C
+    // waits in the pool, but the pending ack still goes out.
+    #[test]
+    fn test_full_retrans_queue_sends_acks() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let (mut leaf_a, topo_a) = leaf(a, c, vec![0xAA; 10]);
+        let (mut leaf_c, topo_c) = leaf(c, a, vec![0xCC; 10]);
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RelayNodeCoding::new(vec![a, c]);
+        relay.retrans_queue = RetransQueue::new(1, Duration::from_secs(10));
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        relay.handle_rx(&packet_a, &topo_b).unwrap();
+        let packet = relay.packet_pool.pop_front().unwrap();
+        relay.code_packet(packet, false, &topo_b).unwrap();
+        assert!(relay.retrans_queue.is_full());
+
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+        let CodingHeader::Native(info) = packet_c.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        relay.acks.push(Ack {
+            source: c,
+            packets: vec![info.clone()],
+        });
+        relay.last_packet_send = Instant::now() - CONFIG.control_packet_duration * 2;
+
+        let control = relay.handle_tx(&topo_b).unwrap().unwrap();
+        assert!(matches!(control.coding_header(), CodingHeader::Control(_)));
+        assert_eq!(control.ack_header().len(), 1);
+        assert_eq!(relay.packet_pool.size(), 1);
+    }
+
+    // NOTE: C could not decode the coded packet, because it never overheard
+    // the packet of A. The NACK of C gets its packet resent on its own.
+    #[test]
+    fn test_resend_nacked_packet() {
+        let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+        let data_e: Vec<u8> = vec![0xEE; 30];
+        let (mut leaf_a, topo_a) = leaf(a, d, vec![0xAA; 20]);
+        let (mut leaf_e, topo_e) = leaf(e, c, data_e.clone());
+        let (mut leaf_c, topo_c) = leaf(c, e, vec![0xCC]);
+        let nodes = vec![a, c, d, e];
+        let topo_b = Topology::new(b, b, nodes.clone(), nodes.clone());
+        let mut relay = RelayNodeCoding::new(nodes);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let packet_e = leaf_e.handle_tx(&topo_e).unwrap().unwrap();
+        relay.handle_rx(&packet_a, &topo_b).unwrap();
+        relay.handle_rx(&packet_e, &topo_b).unwrap();
+        let all = bv::bitvec![1; REPORT_WINDOW];
+        let old_id = PacketID::new(100);
+        relay
+            .links
+            .insert_report(c, &ReceptionReport::new(a, old_id, all.clone()));
+        relay
+            .links
+            .insert_report(d, &ReceptionReport::new(e, old_id, all));
+
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let coded = relay.code_packet(packet, false, &topo_b).unwrap();
+        assert!(leaf_c.handle_rx(&coded, &topo_c).is_err());
+
+        // NOTE: The NACK is sent with the next packet of C
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        assert_eq!(packet_c.nack_header().len(), 1);
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+
+        let resent = relay.handle_tx(&topo_b).unwrap().unwrap();
+        let CodingHeader::Encoded(infos) = resent.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].nexthop, c);
+        assert_eq!(
+            leaf_c.handle_rx(&resent, &topo_c).unwrap().unwrap(),
+            PacketData::new(data_e)
+        );
+        assert!(relay.retrans_queue.take_nacked().is_none());
+    }
+
+    // NOTE: The chain A - B - C - D, where A and D send to each other over the
+    // relays B and C. C codes the packet of A with the packet of D, and B decodes
+    // it with the packet of A, which it forwarded itself.
+    #[test]
+    fn test_chain_recode() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let data_a: Vec<u8> = vec![0xAA; 20];
+        let data_d: Vec<u8> = vec![0xDD; 30];
+        let (mut leaf_a, topo_a) = leaf(a, d, data_a.clone());
+        let mut leaf_d = LeafNodeCoding::new(once(d, a, data_d.clone()));
+        let topo_d = Topology::new(d, c, vec![c], vec![a]);
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(d, c)]);
+        let topo_c = Topology::new(c, b, vec![b, d], vec![b, d])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(a, b)]);
+        let mut relay_b = RelayNodeCoding::new(vec![a, c]);
+        let mut relay_c = RelayNodeCoding::new(vec![b, d]);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        relay_b.handle_rx(&packet_a, &topo_b).unwrap();
+        let packet = relay_b.packet_pool.pop_front().unwrap();
+        assert_eq!(packet.0.nexthop, c);
+        let to_c = relay_b.code_packet(packet, false, &topo_b).unwrap();
+        relay_c.handle_rx(&to_c, &topo_c).unwrap();
+
+        let packet_d = leaf_d.handle_tx(&topo_d).unwrap().unwrap();
+        relay_c.handle_rx(&packet_d, &topo_c).unwrap();
+        let packet = relay_c.packet_pool.pop_front().unwrap();
+        let coded = relay_c.code_packet(packet, false, &topo_c).unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+
+        // NOTE: The coded packet also carries the ack of C for the packet of A
+        relay_b.handle_rx(&coded, &topo_b).unwrap();
+        assert_eq!(relay_b.retrans_queue.len(), 0);
+        assert_eq!(relay_b.acks.len(), 1);
+        assert_eq!(relay_b.acks[0].source, b);
+        assert_eq!(
+            leaf_d.handle_rx(&coded, &topo_d).unwrap().unwrap(),
+            PacketData::new(data_a)
+        );
+
+        let packet = relay_b.packet_pool.pop_front().unwrap();
+        assert_eq!(packet.0.nexthop, a);
+        let to_a = relay_b.code_packet(packet, false, &topo_b).unwrap();
+        assert_eq!(
+            leaf_a.handle_rx(&to_a, &topo_a).unwrap().unwrap(),
+            PacketData::new(data_d)
+        );
+    }
+
+    // NOTE: The chain from test_chain_recode, but B gets the packet of C, before
+    // it sent the packet of A it was coded with. B keeps it until then.
+    #[test]
+    fn test_chain_pending() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let data_d: Vec<u8> = vec![0xDD; 30];
+        let (mut leaf_a, topo_a) = leaf(a, d, vec![0xAA; 20]);
+        let mut leaf_d = LeafNodeCoding::new(once(d, a, data_d.clone()));
+        let topo_d = Topology::new(d, c, vec![c], vec![a]);
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(d, c)]);
+        let topo_c = Topology::new(c, b, vec![b, d], vec![b, d])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(a, b)]);
+        let mut relay_b = RelayNodeCoding::new(vec![a, c]);
+        let mut relay_c = RelayNodeCoding::new(vec![b, d]);
+
+        // NOTE: C got the packet of A from an earlier B, e.g. before a reboot
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        let mut earlier_b = RelayNodeCoding::new(vec![a, c]);
+        earlier_b.handle_rx(&packet_a, &topo_b).unwrap();
+        let packet = earlier_b.packet_pool.pop_front().unwrap();
+        let to_c = earlier_b.code_packet(packet, false, &topo_b).unwrap();
+        relay_c.handle_rx(&to_c, &topo_c).unwrap();
+        let packet_d = leaf_d.handle_tx(&topo_d).unwrap().unwrap();
+        relay_c.handle_rx(&packet_d, &topo_c).unwrap();
+        let packet = relay_c.packet_pool.pop_front().unwrap();
+        let coded = relay_c.code_packet(packet, false, &topo_c).unwrap();
+
+        assert!(relay_b.handle_rx(&coded, &topo_b).is_err());
+        assert_eq!(relay_b.pending.len(), 1);
+        assert_eq!(relay_b.packet_pool.size(), 0);
+
+        // NOTE: Sending the packet of A puts it into the decode pool
+        relay_b.handle_rx(&packet_a, &topo_b).unwrap();
+        let packet = relay_b.packet_pool.pop_front().unwrap();
+        relay_b.code_packet(packet, false, &topo_b).unwrap();
+        assert!(relay_b.pending.is_empty());
+        assert_eq!(relay_b.late_decoded, 1);
+
+        let packet = relay_b.packet_pool.pop_front().unwrap();
+        assert_eq!(packet.0.nexthop, a);
+        let to_a = relay_b.code_packet(packet, false, &topo_b).unwrap();
+        assert_eq!(
+            leaf_a.handle_rx(&to_a, &topo_a).unwrap().unwrap(),
+            PacketData::new(data_d)
+        );
+    }
+
+    // NOTE: B generates traffic for C as well, but C misses the first packet of
+    // B. A overheard it, so its retransmission is coded with the packet of C for A.
+    #[test]
+    fn test_code_own_retransmission() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let data_b: Vec<u8> = vec![0xBB; 20];
+        let data_c: Vec<u8> = vec![0xCC; 20];
+        let none = TrafficGenerator::from_tg_type(TrafficGeneratorType::None, vec![c], a);
+        let mut leaf_a = LeafNodeCoding::new(none);
+        let topo_a = Topology::new(a, b, vec![b], vec![c]);
+        let (mut leaf_c, topo_c) = leaf(c, a, data_c.clone());
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RelayNodeCoding::new(vec![a, c]).with_generator(once(b, c, data_b.clone()));
+        relay.retrans_queue = RetransQueue::new(8, Duration::ZERO);
+
+        relay.admit_local(&topo_b).unwrap();
+        let packet = relay.packet_pool.pop_front().unwrap();
+        let own = relay.code_packet(packet, false, &topo_b).unwrap();
+        leaf_a.handle_rx(&own, &topo_a).unwrap();
+        let CodingHeader::Encoded(infos) = own.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        let own = (infos[0].clone(), own.data().clone());
+
+        // NOTE: Even with a perfect link to A, the relay doesn't guess
+        let all = bv::bitvec![1; REPORT_WINDOW];
+        relay
+            .links
+            .insert_report(a, &ReceptionReport::new(b, PacketID::new(100), all));
+        let packet_c = leaf_c.handle_tx(&topo_c).unwrap().unwrap();
+        relay.handle_rx(&packet_c, &topo_b).unwrap();
+        let other = relay.packet_pool.peek_front().unwrap().clone();
+        assert!(!relay.all_nexhops_can_decode(&vec![own.clone()], &other));
+
+        // NOTE: The report of A tells the relay that it overheard the packet
+        leaf_a.set_last_packet_send(Instant::now() - CONFIG.control_packet_duration * 2);
+        let report = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        relay.handle_rx(&report, &topo_b).unwrap();
+        assert!(relay.all_nexhops_can_decode(&vec![own], &other));
+        let coded = relay.handle_tx(&topo_b).unwrap().unwrap();
+        let CodingHeader::Encoded(infos) = coded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos.len(), 2);
+        assert_eq!(relay.local_sent, 1);
+        assert_eq!(relay.forwarded_sent, 1);
+
+        assert_eq!(
+            leaf_a.handle_rx(&coded, &topo_a).unwrap().unwrap(),
+            PacketData::new(data_c)
+        );
+        assert_eq!(
+            leaf_c.handle_rx(&coded, &topo_c).unwrap().unwrap(),
+            PacketData::new(data_b)
+        );
+    }
+
+    // NOTE: B loses the election to C, after it received the packet of A for D,
+    // so it sends the packet to C as a leaf. A leaf that gets elected hands its
+    // unacked packets to its relay coding as well.
+    #[test]
+    fn test_requeue_on_role_change() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let (mut leaf_a, topo_a) = leaf(a, d, vec![0xAA; 20]);
+        let topo_b = Topology::new(b, b, vec![a, d], vec![a, d]);
+        let mut relay_b = RelayNodeCoding::new(vec![a, d]);
+
+        let packet_a = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+        relay_b.handle_rx(&packet_a, &topo_b).unwrap();
+        let queued = relay_b.take_queued();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(relay_b.packet_pool.size(), 0);
+
+        let topo_b = Topology::new(b, c, vec![c], vec![a, d]);
+        let mut leaf_b = LeafNodeCoding::new(once(b, d, vec![]));
+        leaf_b.requeue(queued, &topo_b);
+        let resent = leaf_b.handle_tx(&topo_b).unwrap().unwrap();
+        let CodingHeader::Native(info) = resent.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        assert_eq!((info.source, info.nexthop, info.destination), (a, c, d));
+
+        let topo_a = Topology::new(a, a, vec![c, d], vec![c, d]);
+        let mut relay_a = RelayNodeCoding::new(vec![c, d]);
+        relay_a.requeue(leaf_a.take_queued(), &topo_a);
+        let (info, _) = relay_a.packet_pool.pop_front().unwrap();
+        assert_eq!((info.source, info.nexthop), (a, d));
+    }
 }
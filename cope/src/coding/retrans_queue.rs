@@ -1,35 +1,82 @@
 use std::{
+    collections::HashMap,
     time::{Duration, Instant},
     usize,
 };
 
+use cope_config::types::node_id::NodeID;
+
 use crate::{
     config::CONFIG,
-    packet::{CodingInfo, PacketData},
+    packet::{packet_id::MAX_ID_AGE, CodingInfo, PacketData, PacketID},
 };
 
+use super::rtt_estimator::RttEstimator;
+
 #[derive(Debug)]
 pub struct RetransEntry {
     data: PacketData,
     info: CodingInfo,
     retrans_count: u8,
     last_trans: Instant,
+    nacked: bool,
+    // NOTE: Only set while the packet was sent once, see RttEstimator::add_sample
+    first_trans: Option<Instant>,
 }
 
 #[derive(Debug)]
 pub struct RetransQueue {
     queue: Vec<RetransEntry>,
     max_count: usize,
-    retrans_duration: Duration,
+    rtt: RttEstimator,
+    dropped: u32,
+    // NOTE: Entries and acks are matched by id, so ids that are stale compared
+    // to the newest one of their source are ignored, see SimpleKBase
+    newest_ids: HashMap<NodeID, PacketID>,
 }
 
 impl RetransQueue {
-    pub fn new(max_count: usize, retrans_duration: Duration) -> Self {
+    pub fn new(max_count: usize, initial_rto: Duration) -> Self {
         Self {
             queue: vec![],
             max_count,
-            retrans_duration,
+            rtt: RttEstimator::new(initial_rto),
+            dropped: 0,
+            newest_ids: HashMap::new(),
+        }
+    }
+
+    fn is_stale(&self, info: &CodingInfo) -> bool {
+        self.newest_ids
+            .get(&info.source)
+            .is_some_and(|&newest| !info.id.is_within(newest, MAX_ID_AGE))
+    }
+
+    // NOTE: Entries that went stale are given up on, because an ack for them
+    // could not be told apart from one for the id after the wraparound
+    fn update_newest(&mut self, info: &CodingInfo) {
+        let newest = self.newest_ids.entry(info.source).or_insert(info.id);
+        if info.id > *newest {
+            *newest = info.id;
+        }
+        let len = self.queue.len();
+        let newest_ids = &self.newest_ids;
+        self.queue.retain(|entry| {
+            entry
+                .info
+                .id
+                .is_within(newest_ids[&entry.info.source], MAX_ID_AGE)
+        });
+        self.dropped += (len - self.queue.len()) as u32;
+    }
+
+    fn position(&self, info: &CodingInfo) -> Option<usize> {
+        if self.is_stale(info) {
+            return None;
         }
+        self.queue
+            .iter()
+            .position(|entry| entry.info.key() == info.key())
     }
 
     pub fn is_full(&self) -> bool {
@@ -37,52 +84,243 @@ impl RetransQueue {
     }
 
     pub fn conatains(&self, info: &CodingInfo) -> bool {
-        self.queue
-            .iter()
-            .find(|entry| entry.info == *info)
-            .is_some()
+        self.position(info).is_some()
     }
 
     pub fn len(&self) -> usize {
         self.queue.len()
     }
 
+    pub fn rto(&self) -> Duration {
+        self.rtt.rto()
+    }
+
+    // NOTE: Of the packets that are due, the oldest id of the first source is
+    // retransmitted first, ids of different sources can't be compared
     pub fn packet_to_retrans(&mut self) -> Option<(CodingInfo, PacketData)> {
-        let Some(entry_pos) = self.queue.iter().position(|entry| {
+        let mut due = self.queue.iter().enumerate().filter(|(_, entry)| {
             let duration = entry.last_trans.elapsed();
-            duration >= self.retrans_duration
-        }) else {
-            return None;
-        };
+            duration >= self.rtt.backoff(entry.retrans_count)
+        });
+        let (mut entry_pos, mut oldest) = due.next()?;
+        for (pos, entry) in due {
+            if entry.info.source == oldest.info.source && entry.info.id < oldest.info.id {
+                (entry_pos, oldest) = (pos, entry);
+            }
+        }
 
         let new_instant = Instant::now();
         if self.queue[entry_pos].retrans_count < CONFIG.max_retrans_amount {
             let entry = &mut self.queue[entry_pos];
             entry.last_trans = new_instant;
             entry.retrans_count += 1;
+            entry.first_trans = None;
             return Some((entry.info.clone(), entry.data.clone()));
         }
+        // NOTE: The last retransmission is not tracked anymore, so the packet is lost
+        // if it is not received this time.
         let mut entry = self.queue.remove(entry_pos);
         entry.last_trans = new_instant;
         entry.retrans_count += 1;
+        self.dropped += 1;
         return Some((entry.info, entry.data));
     }
 
+    // NOTE: Marks the packet to be sent again right away, returns false
+    // if the packet is not in the queue (anymore).
+    pub fn nack(&mut self, info: &CodingInfo) -> bool {
+        let Some(pos) = self.position(info) else {
+            return false;
+        };
+        self.queue[pos].nacked = true;
+        true
+    }
+
+    // NOTE: Nacked packets stay in the queue until they are acked. They don't
+    // count as retransmissions, but the timeout starts over.
+    pub fn take_nacked(&mut self) -> Option<(CodingInfo, PacketData)> {
+        let entry = self.queue.iter_mut().find(|entry| entry.nacked)?;
+        entry.nacked = false;
+        entry.last_trans = Instant::now();
+        entry.first_trans = None;
+        Some((entry.info.clone(), entry.data.clone()))
+    }
+
+    // NOTE: Packets another coding of this node had queued are sent right away,
+    // with all retransmissions left. They are dropped, if the queue is full.
+    pub fn push_due(&mut self, packet: (CodingInfo, PacketData)) {
+        self.update_newest(&packet.0);
+        if self.is_full() || self.is_stale(&packet.0) {
+            self.dropped += 1;
+            return;
+        }
+        let entry = RetransEntry {
+            data: packet.1,
+            info: packet.0,
+            retrans_count: 0,
+            last_trans: self.due_now(),
+            nacked: false,
+            first_trans: None,
+        };
+        self.queue.push(entry);
+    }
+
+    // NOTE: Packets for a relay that went away are sent to their new nexthop
+    // right away, with all retransmissions left
+    pub fn readdress(&mut self, nexthop_for: impl Fn(&CodingInfo) -> NodeID) {
+        let due = self.due_now();
+        for entry in self.queue.iter_mut() {
+            let nexthop = nexthop_for(&entry.info);
+            if entry.info.nexthop == nexthop {
+                continue;
+            }
+            entry.info.nexthop = nexthop;
+            entry.retrans_count = 0;
+            entry.last_trans = due;
+            entry.first_trans = None;
+        }
+    }
+
+    // NOTE: Last transmission time, that makes a new entry due right away
+    fn due_now(&self) -> Instant {
+        let now = Instant::now();
+        now.checked_sub(self.rtt.backoff(0)).unwrap_or(now)
+    }
+
+    // NOTE: Removes all packets, oldest first
+    pub fn take_all(&mut self) -> Vec<(CodingInfo, PacketData)> {
+        self.queue
+            .drain(..)
+            .map(|entry| (entry.info, entry.data))
+            .collect()
+    }
+
     pub fn push_new(&mut self, packet: (CodingInfo, PacketData)) {
+        self.update_newest(&packet.0);
+        if self.is_stale(&packet.0) {
+            return;
+        }
         let instant = Instant::now();
         let entry = RetransEntry {
             data: packet.1,
             info: packet.0,
             retrans_count: 0,
             last_trans: instant,
+            nacked: false,
+            first_trans: Some(instant),
         };
         self.queue.push(entry);
     }
 
-    pub fn remove_packet(&mut self, info: &CodingInfo) {
-        let Some(pos) = self.queue.iter().position(|entry| entry.info == *info) else {
-            return;
+    // NOTE: Called for acked packets, which gives us a sample of the RTT.
+    // Returns false, if the packet was not in the queue (anymore).
+    pub fn remove_packet(&mut self, info: &CodingInfo) -> bool {
+        let Some(pos) = self.position(info) else {
+            return false;
+        };
+        let entry = self.queue.remove(pos);
+        if let Some(first_trans) = entry.first_trans {
+            self.rtt.add_sample(first_trans.elapsed());
+        }
+        true
+    }
+
+    // NOTE: Returns the number of packets given up on since the last call
+    pub fn take_dropped(&mut self) -> u32 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::PacketID;
+    use cope_config::types::node_id::NodeID;
+
+    fn packet(id: u16) -> (CodingInfo, PacketData) {
+        let info = CodingInfo {
+            source: NodeID::new('A'),
+            id: PacketID::new(id),
+            nexthop: NodeID::new('C'),
+            destination: NodeID::new('C'),
+            len: 1,
         };
-        self.queue.remove(pos);
+        (info, PacketData::new(vec![id as u8]))
+    }
+
+    #[test]
+    fn test_sample_only_unambiguous_acks() {
+        let initial_rto = Duration::from_secs(10);
+        let mut queue = RetransQueue::new(4, initial_rto);
+        queue.push_new(packet(1));
+        queue.push_new(packet(2));
+        queue.nack(&packet(2).0);
+        queue.take_nacked().unwrap();
+
+        // NOTE: Packet 2 was sent twice, so its ack says nothing about the RTT
+        queue.remove_packet(&packet(2).0);
+        assert_eq!(queue.rto(), initial_rto);
+        queue.remove_packet(&packet(1).0);
+        assert!(queue.rto() < initial_rto);
+        assert!(queue.packet_to_retrans().is_none());
+    }
+
+    #[test]
+    fn test_retrans_oldest_id_first() {
+        let mut queue = RetransQueue::new(4, Duration::ZERO);
+        queue.push_new(packet(1));
+        queue.push_new(packet(u16::MAX));
+        let (info, _) = queue.packet_to_retrans().unwrap();
+        assert_eq!(info.id, PacketID::new(u16::MAX));
+    }
+
+    #[test]
+    fn test_stale_ids_after_wraparound() {
+        let mut queue = RetransQueue::new(4, Duration::from_secs(10));
+        let old = packet(u16::MAX - 1);
+        queue.push_new(old.clone());
+        queue.push_new(packet(MAX_ID_AGE - 3));
+        assert_eq!(queue.len(), 2);
+
+        // NOTE: Once the old id went stale, it is given up on and acks for it
+        // are ignored
+        queue.push_new(packet(MAX_ID_AGE - 2));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.take_dropped(), 1);
+        assert!(!queue.conatains(&old.0));
+        assert!(!queue.remove_packet(&old.0));
+        queue.push_new(old);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn test_drop_after_max_retrans() {
+        let mut queue = RetransQueue::new(4, Duration::ZERO);
+        queue.push_new(packet(1));
+        for _ in 0..CONFIG.max_retrans_amount {
+            assert!(queue.packet_to_retrans().is_some());
+            assert_eq!(queue.take_dropped(), 0);
+        }
+        assert!(queue.packet_to_retrans().is_some());
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.take_dropped(), 1);
+    }
+
+    #[test]
+    fn test_readdress() {
+        let mut queue = RetransQueue::new(4, Duration::from_secs(10));
+        queue.push_new(packet(1));
+        queue.push_new(packet(2));
+        assert!(queue.packet_to_retrans().is_none());
+
+        // NOTE: Both are due right away, with all retransmissions left
+        let relay = NodeID::new('B');
+        queue.readdress(|_| relay);
+        for _ in 0..2 {
+            let (info, _) = queue.packet_to_retrans().unwrap();
+            assert_eq!(info.nexthop, relay);
+        }
+        assert!(queue.packet_to_retrans().is_none());
+        assert_eq!(queue.len(), 2);
     }
 }
@@ -0,0 +1,480 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use cope_config::types::node_id::NodeID;
+
+use crate::{
+    config::CONFIG,
+    packet::{
+        packet::CodingHeader, Ack, CodingInfo, LinearHeader, PacketBuilder, PacketData, PacketID,
+    },
+    security::NativeAuth,
+    topology::Topology,
+    traffic_generator::TrafficGenerator,
+    Packet,
+};
+
+use super::{generation::Generation, CodingError, CodingStrategy};
+
+// NOTE: Every native is prefixed with its id and length before it is coded,
+// so the receiver can remove the zero padding again.
+const FRAME_HEADER_SIZE: usize = 4;
+
+fn frame(id: PacketID, data: &PacketData) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + data.len());
+    frame.extend_from_slice(&id.value().to_be_bytes());
+    frame.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    frame.extend_from_slice(data.as_slice());
+    frame
+}
+
+fn unframe(frame: &[u8]) -> Result<(PacketID, &[u8]), CodingError> {
+    let Some(header) = frame.get(..FRAME_HEADER_SIZE) else {
+        return Err(CodingError::DecodeError("Native frame is too short".into()));
+    };
+    let id = PacketID::new(u16::from_be_bytes([header[0], header[1]]));
+    let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let Some(data) = frame.get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + len) else {
+        return Err(CodingError::DecodeError(format!(
+            "Native {} is longer than its frame",
+            id
+        )));
+    };
+    Ok((id, data))
+}
+
+// NOTE: Generations are acked as a whole, using the generation as the id.
+pub fn generation_ack(source: NodeID, generation: PacketID, nexthop: NodeID) -> CodingInfo {
+    CodingInfo {
+        source,
+        id: generation,
+        nexthop,
+        destination: nexthop,
+        len: 0,
+    }
+}
+
+// NOTE: Natives for nexthop, that are waiting for the generation to fill up
+struct OpenGeneration {
+    nexthop: NodeID,
+    frames: Vec<Vec<u8>>,
+    opened: Instant,
+}
+
+struct OutgoingGeneration {
+    id: PacketID,
+    nexthop: NodeID,
+    generation: Generation,
+    // NOTE: Number of coded packets we still have to send
+    credit: usize,
+    retrans_count: u8,
+    last_send: Instant,
+}
+
+struct IncomingGeneration {
+    source: NodeID,
+    id: PacketID,
+    generation: Generation,
+}
+
+// NOTE: Random linear network coding in the style of MORE. Sources group their
+// natives into generations and send random combinations of them, until the
+// receiver acks the generation. The relay recodes what it has received, see
+// RlncRelayCoding, and receivers decode once they have enough innovative packets.
+pub struct RlncLeafCoding {
+    generator: TrafficGenerator,
+    generation_size: usize,
+    next_generation: PacketID,
+    open: Vec<OpenGeneration>,
+    outgoing: VecDeque<OutgoingGeneration>,
+    incoming: VecDeque<IncomingGeneration>,
+    delivered: VecDeque<(NodeID, PacketID)>,
+    acks: Vec<CodingInfo>,
+    last_packet_send: Instant,
+    native_auth: Option<NativeAuth>,
+}
+
+impl RlncLeafCoding {
+    pub fn new(generator: TrafficGenerator) -> Self {
+        Self {
+            generator,
+            generation_size: CONFIG.generation_size,
+            next_generation: PacketID::default(),
+            open: vec![],
+            outgoing: VecDeque::new(),
+            incoming: VecDeque::new(),
+            delivered: VecDeque::new(),
+            acks: vec![],
+            last_packet_send: Instant::now(),
+            native_auth: NativeAuth::from_config(),
+        }
+    }
+
+    // NOTE: Like the retransmission queue of COPE, this limits
+    // the amount of natives that are not acked yet.
+    fn max_outgoing(&self) -> usize {
+        usize::max(1, CONFIG.packet_pool_size / self.generation_size)
+    }
+
+    fn has_room(&self) -> bool {
+        self.outgoing.len() < self.max_outgoing()
+    }
+
+    fn should_tx_control(&self) -> bool {
+        if self.acks.is_empty() {
+            return false;
+        }
+        self.last_packet_send.elapsed() > CONFIG.control_packet_duration
+    }
+
+    fn push_ack(&mut self, info: CodingInfo) {
+        if !self.acks.contains(&info) {
+            self.acks.push(info);
+        }
+    }
+
+    fn take_ack(&mut self, topology: &Topology) -> Vec<Ack> {
+        vec![Ack {
+            source: topology.id(),
+            packets: std::mem::take(&mut self.acks),
+        }]
+    }
+
+    fn schedule_retransmissions(&mut self, topology: &Topology) {
+        let redundancy = usize::max(1, self.generation_size / 2);
+        self.outgoing.retain_mut(|g| {
+            if g.credit > 0 || g.last_send.elapsed() < CONFIG.round_trip_time {
+                return true;
+            }
+            if g.retrans_count >= CONFIG.max_retrans_amount {
+                log::warn!(
+                    "[Node {}]: Dropping generation {}, because it was not acked.",
+                    topology.id(),
+                    g.id
+                );
+                return false;
+            }
+            g.retrans_count += 1;
+            g.credit += redundancy;
+            true
+        });
+    }
+
+    fn generate_native(&mut self, topology: &Topology) -> Result<(), CodingError> {
+        let Some(builder) = self.generator.generate(topology) else {
+            return Ok(());
+        };
+        let packet = builder.ack_header(vec![]).build().map_err(|e| {
+            CodingError::DefectPacketError(format!("Failed to build native, because {}", e))
+        })?;
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            return Err(CodingError::DefectPacketError(
+                "Expected to generate Native Packet".into(),
+            ));
+        };
+
+        let data = match &self.native_auth {
+            Some(auth) => auth.tag(info, packet.data()),
+            None => packet.data().clone(),
+        };
+
+        let frame = frame(info.id, &data);
+        // NOTE: Generations are only decoded by their destination, so RLNC needs
+        // the relay to be the only hop in between, which build.rs makes sure of.
+        match self.open.iter_mut().find(|g| g.nexthop == info.destination) {
+            Some(open) => open.frames.push(frame),
+            None => self.open.push(OpenGeneration {
+                nexthop: info.destination,
+                frames: vec![frame],
+                opened: Instant::now(),
+            }),
+        }
+        Ok(())
+    }
+
+    // NOTE: Generations are closed once they are full, or if their first
+    // native has waited for too long, so slow traffic isn't delayed forever.
+    fn close_generations(&mut self) {
+        while self.has_room() {
+            let Some(pos) = self.open.iter().position(|g| {
+                g.frames.len() >= self.generation_size
+                    || g.opened.elapsed() > CONFIG.control_packet_duration
+            }) else {
+                return;
+            };
+
+            let open = self.open.remove(pos);
+            self.outgoing.push_back(OutgoingGeneration {
+                id: self.next_generation,
+                nexthop: open.nexthop,
+                credit: open.frames.len(),
+                generation: Generation::from_natives(&open.frames),
+                retrans_count: 0,
+                last_send: Instant::now(),
+            });
+            self.next_generation = self.next_generation.next();
+        }
+    }
+
+    fn handle_linear(
+        &mut self,
+        header: &LinearHeader,
+        data: &PacketData,
+        topology: &Topology,
+    ) -> Result<Option<PacketData>, CodingError> {
+        let key = (header.source, header.generation);
+        let ack = generation_ack(header.source, header.generation, topology.id());
+        if self.delivered.contains(&key) {
+            // NOTE: Our ack was probably lost, so we send it again
+            self.push_ack(ack);
+            return Ok(Some(PacketData::new(vec![])));
+        }
+
+        let pos = match self.incoming.iter().position(|g| (g.source, g.id) == key) {
+            Some(pos) => pos,
+            None => {
+                if self.incoming.len() >= CONFIG.packet_pool_size {
+                    self.incoming.pop_front();
+                }
+                self.incoming.push_back(IncomingGeneration {
+                    source: header.source,
+                    id: header.generation,
+                    generation: Generation::new(header.coefficients.len()),
+                });
+                self.incoming.len() - 1
+            }
+        };
+
+        let incoming = &mut self.incoming[pos];
+        if incoming.generation.size() != header.coefficients.len() {
+            return Err(CodingError::DefectPacketError(format!(
+                "Generation {}@{} has size {}, but packet has {} coefficients",
+                header.generation,
+                header.source,
+                incoming.generation.size(),
+                header.coefficients.len()
+            )));
+        }
+
+        incoming
+            .generation
+            .insert(&header.coefficients, data.as_slice());
+        if !incoming.generation.is_complete() {
+            return Ok(Some(PacketData::new(vec![])));
+        }
+
+        let incoming = self.incoming.remove(pos).unwrap();
+        let mut decoded = vec![];
+        for frame in incoming.generation.decode().unwrap() {
+            let (id, native) = unframe(frame)?;
+            let native = PacketData::new(native.to_vec());
+            let native = match &self.native_auth {
+                Some(auth) => {
+                    let info = generation_ack(header.source, id, topology.id());
+                    auth.verify(&info, native)
+                        .map_err(|e| CodingError::AuthenticationError(e.to_string()))?
+                }
+                None => native,
+            };
+            decoded.extend_from_slice(native.as_slice());
+        }
+        log::debug!(
+            "[Node {}]: Decoded generation {}@{}",
+            topology.id(),
+            header.generation,
+            header.source
+        );
+
+        if self.delivered.len() >= 2 * CONFIG.packet_pool_size {
+            self.delivered.pop_front();
+        }
+        self.delivered.push_back(key);
+        self.push_ack(ack);
+        // NOTE: All natives of the generation are returned at once
+        Ok(Some(PacketData::new(decoded)))
+    }
+}
+
+impl CodingStrategy for RlncLeafCoding {
+    fn handle_rx(
+        &mut self,
+        packet: &Packet,
+        topology: &Topology,
+    ) -> Result<Option<PacketData>, CodingError> {
+        for ack in packet.ack_header() {
+            for info in ack.packets() {
+                if info.source == topology.id() {
+                    log::debug!(
+                        "[Node {}]: Generation {} was acked.",
+                        topology.id(),
+                        info.id
+                    );
+                    self.outgoing.retain(|g| g.id != info.id);
+                }
+            }
+        }
+
+        match packet.coding_header() {
+            CodingHeader::Linear(header) if header.nexthop == topology.id() => {
+                self.handle_linear(header, packet.data(), topology)
+            }
+            _ => Ok(Some(packet.data().clone())),
+        }
+    }
+
+    fn handle_tx(&mut self, topology: &Topology) -> Result<Option<Packet>, CodingError> {
+        self.schedule_retransmissions(topology);
+        if self.has_room() {
+            self.generate_native(topology)?;
+        }
+        self.close_generations();
+
+        if let Some(outgoing) = self.outgoing.iter_mut().find(|g| g.credit > 0) {
+            let (coefficients, data) = outgoing.generation.recode(&mut rand::thread_rng()).unwrap();
+            outgoing.credit -= 1;
+            outgoing.last_send = Instant::now();
+
+            let header = LinearHeader {
+                source: topology.id(),
+                nexthop: outgoing.nexthop,
+                generation: outgoing.id,
+                coefficients,
+            };
+            let packet = PacketBuilder::new()
+                .sender(topology.id())
+                .linear_header(header)
+                .data_raw(data)
+                .ack_header(self.take_ack(topology))
+                .build()
+                .map_err(|e| {
+                    CodingError::DefectPacketError(format!(
+                        "[Node {}]: Failed to build Linear Packet, because {}",
+                        topology.id(),
+                        e
+                    ))
+                })?;
+            return Ok(Some(packet));
+        }
+
+        if self.should_tx_control() {
+            let receiver = topology.control_receiver();
+            let result = PacketBuilder::new()
+                .sender(topology.id())
+                .control_header(receiver)
+                .ack_header(self.take_ack(topology))
+                .build();
+            return match result {
+                Ok(control_packet) => Ok(Some(control_packet)),
+                Err(e) => Err(CodingError::DefectPacketError(format!(
+                    "[Node {}]: Failed to build Control Packet, because {}",
+                    topology.id(),
+                    e
+                ))),
+            };
+        }
+
+        Ok(None)
+    }
+
+    fn update_last_packet_send(&mut self) {
+        self.last_packet_send = Instant::now();
+    }
+
+    fn update_neighbors(&mut self, topology: &Topology) {
+        self.generator.set_tx_whitelist(topology.txlist().to_vec());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{coding::rlnc_relay_coding::RlncRelayCoding, traffic_generator::TGStrategy};
+
+    // NOTE: Generates the given packets, one per call
+    struct ListStrategy(VecDeque<Vec<u8>>);
+
+    impl TGStrategy for ListStrategy {
+        fn generate(&mut self) -> Option<PacketBuilder> {
+            self.0
+                .pop_front()
+                .map(|data| PacketBuilder::new().data_raw(data))
+        }
+    }
+
+    fn leaf(id: NodeID, target: NodeID, data: Vec<Vec<u8>>) -> (RlncLeafCoding, Topology) {
+        let relay = NodeID::new('B');
+        let strategy = ListStrategy(data.into());
+        let generator = TrafficGenerator::new(Box::new(strategy), vec![target], id);
+        let topology = Topology::new(id, relay, vec![relay], vec![target]);
+        (RlncLeafCoding::new(generator), topology)
+    }
+
+    #[test]
+    fn test_frames() {
+        let data = PacketData::new(vec![1, 2, 3]);
+        let mut framed = frame(PacketID::new(258), &data);
+        assert_eq!(framed, vec![1, 2, 0, 3, 1, 2, 3]);
+
+        // NOTE: Zero padding from coding is ignored
+        framed.extend([0; 5]);
+        let (id, native) = unframe(&framed).unwrap();
+        assert_eq!(id, PacketID::new(258));
+        assert_eq!(native, data.as_slice());
+
+        assert!(unframe(&[0, 1, 0]).is_err());
+        assert!(unframe(&[0, 1, 0, 4, 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_generation_over_relay() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let natives: Vec<Vec<u8>> = (0..CONFIG.generation_size)
+            .map(|i| vec![i as u8; 10 + i])
+            .collect();
+
+        let (mut leaf_a, topo_a) = leaf(a, c, natives.clone());
+        let (mut leaf_c, topo_c) = leaf(c, a, vec![]);
+        let topo_b = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RlncRelayCoding::new();
+
+        // NOTE: The generation is sent once it is full
+        for _ in 1..natives.len() {
+            assert!(leaf_a.handle_tx(&topo_a).unwrap().is_none());
+        }
+
+        let mut decoded = PacketData::new(vec![]);
+        let mut last = None;
+        for _ in 0..4 * natives.len() {
+            // NOTE: Random coefficients can be linearly dependent, in which
+            // case the source has to send more than the generation size.
+            if let Some(outgoing) = leaf_a.outgoing.front_mut() {
+                outgoing.credit = usize::max(outgoing.credit, 1);
+            }
+            let packet = leaf_a.handle_tx(&topo_a).unwrap().unwrap();
+            relay.handle_rx(&packet, &topo_b).unwrap();
+            let packet = relay.handle_tx(&topo_b).unwrap().unwrap();
+            decoded = leaf_c.handle_rx(&packet, &topo_c).unwrap().unwrap();
+            last = Some(packet);
+            if !leaf_c.acks.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(decoded.as_slice(), natives.concat().as_slice());
+
+        // NOTE: The relay forwards the ack, which ends the generation at A
+        let ack = PacketBuilder::new()
+            .sender(c)
+            .control_header(b)
+            .ack_header(leaf_c.take_ack(&topo_c))
+            .build()
+            .unwrap();
+        relay.handle_rx(&ack, &topo_b).unwrap();
+        leaf_a.handle_rx(&ack, &topo_a).unwrap();
+        assert!(leaf_a.outgoing.is_empty());
+
+        // NOTE: Late packets of a decoded generation are only acked again
+        let decoded = leaf_c.handle_rx(&last.unwrap(), &topo_c).unwrap().unwrap();
+        assert_eq!(decoded.len(), 0);
+        assert_eq!(leaf_c.acks.len(), 1);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use cope_config::types::node_id::NodeID;
+
+use crate::{
+    config::CONFIG,
+    packet::{packet::CodingHeader, Ack, LinearHeader, PacketBuilder, PacketData, PacketID},
+    topology::Topology,
+    Packet,
+};
+
+use super::{generation::Generation, CodingError, CodingStrategy};
+
+struct RelayGeneration {
+    source: NodeID,
+    id: PacketID,
+    nexthop: NodeID,
+    generation: Generation,
+    // NOTE: Like in MORE, every packet we receive of a generation
+    // allows us to send one recoded packet.
+    credit: usize,
+}
+
+// NOTE: The relay does not decode, it only recodes the packets it has received
+// of each generation, until the destination acks it.
+pub struct RlncRelayCoding {
+    generations: VecDeque<RelayGeneration>,
+    // NOTE: Generations that were already acked, so late packets are ignored
+    finished: VecDeque<(NodeID, PacketID)>,
+    acks: Vec<Ack>,
+    last_packet_send: Instant,
+}
+
+impl RlncRelayCoding {
+    pub fn new() -> Self {
+        Self {
+            generations: VecDeque::new(),
+            finished: VecDeque::new(),
+            acks: vec![],
+            last_packet_send: Instant::now(),
+        }
+    }
+
+    fn should_tx_control(&self) -> bool {
+        if self.acks.is_empty() {
+            return false;
+        }
+        self.last_packet_send.elapsed() > CONFIG.control_packet_duration
+    }
+
+    fn finish(&mut self, source: NodeID, id: PacketID) {
+        self.generations
+            .retain(|g| (g.source, g.id) != (source, id));
+        if self.finished.contains(&(source, id)) {
+            return;
+        }
+        if self.finished.len() >= 2 * CONFIG.packet_pool_size {
+            self.finished.pop_front();
+        }
+        self.finished.push_back((source, id));
+    }
+
+    fn insert(&mut self, header: &LinearHeader, data: &PacketData) -> Result<(), CodingError> {
+        let key = (header.source, header.generation);
+        if self.finished.contains(&key) {
+            return Ok(());
+        }
+
+        let pos = match self
+            .generations
+            .iter()
+            .position(|g| (g.source, g.id) == key)
+        {
+            Some(pos) => pos,
+            None => {
+                if self.generations.len() >= CONFIG.packet_pool_size {
+                    self.generations.pop_front();
+                }
+                self.generations.push_back(RelayGeneration {
+                    source: header.source,
+                    id: header.generation,
+                    nexthop: header.nexthop,
+                    generation: Generation::new(header.coefficients.len()),
+                    credit: 0,
+                });
+                self.generations.len() - 1
+            }
+        };
+
+        let relay_generation = &mut self.generations[pos];
+        if relay_generation.generation.size() != header.coefficients.len() {
+            return Err(CodingError::DefectPacketError(format!(
+                "Generation {}@{} has size {}, but packet has {} coefficients",
+                header.generation,
+                header.source,
+                relay_generation.generation.size(),
+                header.coefficients.len()
+            )));
+        }
+
+        relay_generation
+            .generation
+            .insert(&header.coefficients, data.as_slice());
+        relay_generation.credit += 1;
+        Ok(())
+    }
+}
+
+impl Default for RlncRelayCoding {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodingStrategy for RlncRelayCoding {
+    fn handle_rx(
+        &mut self,
+        packet: &Packet,
+        topology: &Topology,
+    ) -> Result<Option<PacketData>, CodingError> {
+        for ack in packet.ack_header() {
+            for info in ack.packets() {
+                log::debug!(
+                    "[Relay {}]: Generation {}@{} was acked.",
+                    topology.id(),
+                    info.id,
+                    info.source
+                );
+                self.finish(info.source, info.id);
+            }
+            if !ack.packets().is_empty() {
+                self.acks.push(ack.clone());
+            }
+        }
+
+        match packet.coding_header() {
+            CodingHeader::Linear(header) => self.insert(header, packet.data())?,
+            CodingHeader::Control(_) | CodingHeader::Routing(_) | CodingHeader::Beacon(_) => (),
+            _ => {
+                return Err(CodingError::DefectPacketError(
+                    "Expected to receive Linear Packet".into(),
+                ))
+            }
+        }
+
+        Ok(Some(packet.data().clone()))
+    }
+
+    fn handle_tx(&mut self, topology: &Topology) -> Result<Option<Packet>, CodingError> {
+        if let Some(pos) = self.generations.iter().position(|g| g.credit > 0) {
+            // NOTE: Round robin between generations, so one source can't starve the others
+            let mut relay_generation = self.generations.remove(pos).unwrap();
+            let (coefficients, data) = relay_generation
+                .generation
+                .recode(&mut rand::thread_rng())
+                .unwrap();
+            relay_generation.credit -= 1;
+
+            let header = LinearHeader {
+                source: relay_generation.source,
+                nexthop: relay_generation.nexthop,
+                generation: relay_generation.id,
+                coefficients,
+            };
+            self.generations.push_back(relay_generation);
+
+            let packet = PacketBuilder::new()
+                .sender(topology.id())
+                .linear_header(header)
+                .data_raw(data)
+                .ack_header(std::mem::take(&mut self.acks))
+                .build()
+                .map_err(|e| {
+                    CodingError::DefectPacketError(format!(
+                        "[Relay {}]: Failed to build Linear Packet, because {}",
+                        topology.id(),
+                        e
+                    ))
+                })?;
+            return Ok(Some(packet));
+        }
+
+        if self.should_tx_control() {
+            let receiver = topology.control_receiver();
+            let result = PacketBuilder::new()
+                .sender(topology.id())
+                .control_header(receiver)
+                .ack_header(std::mem::take(&mut self.acks))
+                .build();
+            log::debug!("[Relay {}]: Send Control Packet", topology.id());
+            return match result {
+                Ok(control_packet) => Ok(Some(control_packet)),
+                Err(e) => Err(CodingError::DefectPacketError(format!(
+                    "[Relay {}]: Failed to build Control Packet, because {}",
+                    topology.id(),
+                    e
+                ))),
+            };
+        }
+
+        Ok(None)
+    }
+
+    fn update_last_packet_send(&mut self) {
+        self.last_packet_send = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::coding::rlnc_leaf_coding::generation_ack;
+
+    fn linear(sender: NodeID, source: NodeID, nexthop: NodeID, acks: Vec<Ack>) -> Packet {
+        let header = LinearHeader {
+            source,
+            nexthop,
+            generation: PacketID::new(7),
+            coefficients: vec![1, 2],
+        };
+        PacketBuilder::new()
+            .sender(sender)
+            .linear_header(header)
+            .data_raw(vec![0xAB; 4])
+            .ack_header(acks)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_recode_until_acked() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let topology = Topology::new(b, b, vec![a, c], vec![a, c]);
+        let mut relay = RlncRelayCoding::new();
+
+        relay
+            .handle_rx(&linear(a, a, c, vec![]), &topology)
+            .unwrap();
+        relay
+            .handle_rx(&linear(a, a, c, vec![]), &topology)
+            .unwrap();
+        relay
+            .handle_rx(&linear(c, c, a, vec![]), &topology)
+            .unwrap();
+
+        // NOTE: Every received packet is worth one recoded packet, sent round robin
+        let mut sources = vec![];
+        while let Ok(Some(packet)) = relay.handle_tx(&topology) {
+            let CodingHeader::Linear(header) = packet.coding_header() else {
+                panic!("Expected Linear Packet");
+            };
+            sources.push(header.source);
+        }
+        assert_eq!(sources, vec![a, c, a]);
+
+        // NOTE: Once C acks the generation of A, late packets of it are ignored
+        let ack = Ack {
+            source: c,
+            packets: vec![generation_ack(a, PacketID::new(7), c)],
+        };
+        relay
+            .handle_rx(&linear(c, c, a, vec![ack.clone()]), &topology)
+            .unwrap();
+        relay
+            .handle_rx(&linear(a, a, c, vec![]), &topology)
+            .unwrap();
+        assert_eq!(relay.generations.len(), 1);
+        assert_eq!(relay.generations[0].source, c);
+        assert_eq!(relay.acks, vec![ack]);
+    }
+}
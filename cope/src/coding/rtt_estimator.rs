@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+// NOTE: Bounds of the retransmission timeout. Acks are only sent with the next
+// packet or after control_packet_duration, so RTTs below MIN_RTO are not
+// realistic, and MAX_RTO keeps the exponential backoff from growing forever.
+pub const MIN_RTO: Duration = Duration::from_millis(100);
+pub const MAX_RTO: Duration = Duration::from_secs(60);
+
+// NOTE: Gains of the smoothed RTT and RTT variation, see RFC 6298
+const ALPHA: f64 = 1.0 / 8.0;
+const BETA: f64 = 1.0 / 4.0;
+
+// NOTE: Estimates the retransmission timeout from measured round trip times, as
+// described by Jacobson and Karels. Until the first sample arrives, the initial
+// timeout is used, which is CONFIG.round_trip_time.
+#[derive(Debug)]
+pub struct RttEstimator {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+}
+
+impl RttEstimator {
+    pub fn new(initial_rto: Duration) -> Self {
+        Self {
+            srtt: None,
+            rttvar: Duration::ZERO,
+            rto: initial_rto,
+        }
+    }
+
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    pub fn rto(&self) -> Duration {
+        self.rto
+    }
+
+    // NOTE: Only packets that were sent once may be sampled (Karn's algorithm),
+    // otherwise we can't know which transmission was acked.
+    pub fn add_sample(&mut self, rtt: Duration) {
+        let srtt = match self.srtt {
+            None => {
+                self.rttvar = rtt / 2;
+                rtt
+            }
+            Some(srtt) => {
+                let deviation = srtt.abs_diff(rtt);
+                self.rttvar = self.rttvar.mul_f64(1.0 - BETA) + deviation.mul_f64(BETA);
+                srtt.mul_f64(1.0 - ALPHA) + rtt.mul_f64(ALPHA)
+            }
+        };
+        self.srtt = Some(srtt);
+        self.rto = (srtt + 4 * self.rttvar).clamp(MIN_RTO, MAX_RTO);
+    }
+
+    // NOTE: Timeout of a packet that was already retransmitted count times,
+    // it doubles with every retransmission.
+    pub fn backoff(&self, count: u8) -> Duration {
+        let factor = 1u32.checked_shl(count as u32).unwrap_or(u32::MAX);
+        self.rto.saturating_mul(factor).min(MAX_RTO.max(self.rto))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let mut rtt = RttEstimator::new(Duration::from_millis(1500));
+        assert_eq!(rtt.rto(), Duration::from_millis(1500));
+        assert_eq!(rtt.srtt(), None);
+
+        rtt.add_sample(Duration::from_millis(200));
+        assert_eq!(rtt.srtt(), Some(Duration::from_millis(200)));
+        assert_eq!(rtt.rto(), Duration::from_millis(600));
+
+        // NOTE: srtt = 7/8 * 200 + 1/8 * 600, rttvar = 3/4 * 100 + 1/4 * 400
+        rtt.add_sample(Duration::from_millis(600));
+        assert_eq!(rtt.srtt(), Some(Duration::from_millis(250)));
+        assert_eq!(rtt.rto(), Duration::from_millis(950));
+
+        for _ in 0..100 {
+            rtt.add_sample(Duration::from_millis(1));
+        }
+        assert_eq!(rtt.rto(), MIN_RTO);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut rtt = RttEstimator::new(Duration::from_secs(1));
+        assert_eq!(rtt.backoff(0), Duration::from_secs(1));
+        assert_eq!(rtt.backoff(2), Duration::from_secs(4));
+        assert_eq!(rtt.backoff(200), MAX_RTO);
+
+        // NOTE: A zero timeout stays zero, which tests use to retransmit right away
+        rtt = RttEstimator::new(Duration::ZERO);
+        assert_eq!(rtt.backoff(3), Duration::ZERO);
+    }
+}
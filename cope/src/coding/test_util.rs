@@ -0,0 +1,33 @@
+// Fixtures shared by the tests of the COPE codings.
+
+use cope_config::types::node_id::NodeID;
+
+use super::leaf_node_coding::LeafNodeCoding;
+use crate::{
+    packet::PacketBuilder,
+    topology::Topology,
+    traffic_generator::{TGStrategy, TrafficGenerator},
+};
+
+// NOTE: Generates a single packet with the given data
+pub struct OnceStrategy(Option<Vec<u8>>);
+
+impl TGStrategy for OnceStrategy {
+    fn generate(&mut self) -> Option<PacketBuilder> {
+        self.0
+            .take()
+            .map(|data| PacketBuilder::new().data_raw(data))
+    }
+}
+
+// NOTE: A generator of id, that sends a single packet with data to target
+pub fn once(id: NodeID, target: NodeID, data: Vec<u8>) -> TrafficGenerator {
+    TrafficGenerator::new(Box::new(OnceStrategy(Some(data))), vec![target], id)
+}
+
+// NOTE: A leaf of the relay B, that sends a single packet with data to target
+pub fn leaf(id: NodeID, target: NodeID, data: Vec<u8>) -> (LeafNodeCoding, Topology) {
+    let relay = NodeID::new('B');
+    let topology = Topology::new(id, relay, vec![relay], vec![target]);
+    (LeafNodeCoding::new(once(id, target, data)), topology)
+}
@@ -1,26 +1,46 @@
 // This file is auto generated by a build.rs file and cope_config
 use cope_config::config::*;
+use cope_config::types::coding_scheduler_type::CodingSchedulerType;
+use cope_config::types::coding_scheme::CodingScheme;
+use cope_config::types::discovery_type::DiscoveryType;
+use cope_config::types::election_type::ElectionType;
+use cope_config::types::fairness_type::FairnessType;
 use cope_config::types::node_id::NodeID;
 use cope_config::types::mac_address::MacAddress;
+use cope_config::types::routing_type::RoutingType;
 use cope_config::types::traffic_generator_type::TrafficGeneratorType;
 use std::time::Duration;
 
 pub const CONFIG: Config<3> = Config{
     simulator_packet_loss: 0.000,
+    routing: RoutingType::Static,
+    discovery: DiscoveryType::Static,
+    election: ElectionType::Static,
     round_trip_time: Duration::new(1, 500000000),
     control_packet_duration: Duration::new(0, 200000000),
     max_retrans_amount: 2,
     use_coding: true,
+    use_nacks: true,
+    coding_scheduler: CodingSchedulerType::WaitForPartner(Duration::new(0, 200000000)),
+    hybrid_fairness: FairnessType::Fifo,
+    guess_threshold: 0.800,
+    coding_scheme: CodingScheme::Cope,
+    generation_size: 4,
     packet_pool_size: 8,
+    max_packet_size: 2048,
     stats_log_duration: Duration::new(1, 0),
     log_node_stats: true,
     log_espnow_stats: true,
+    network_key: None,
     nodes: [
         (NodeID::new('A'), MacAddress::new(52, 133, 24, 185, 27, 156)),
         (NodeID::new('B'), MacAddress::new(52, 133, 24, 185, 26, 212)),
         (NodeID::new('C'), MacAddress::new(52, 133, 24, 185, 27, 192)),
     ],
-    relay: NodeID::new('B'),
+    relays: [
+            Some(NodeID::new('B')),
+            None, None, 
+        ],
     rx_whitelist: [
         (NodeID::new('A'), [
             Some(NodeID::new('B')),
@@ -51,9 +71,26 @@ pub const CONFIG: Config<3> = Config{
             None, None, 
         ]),
     ],
+    routes: [
+        (NodeID::new('A'), [
+            None, None, None, 
+        ]),
+        (NodeID::new('B'), [
+            None, None, None, 
+        ]),
+        (NodeID::new('C'), [
+            None, None, None, 
+        ]),
+    ],
     traffic_generators: [
         (NodeID::new('A'), TrafficGeneratorType::Periodic(Duration::new(1, 0))),
         (NodeID::new('B'), TrafficGeneratorType::None),
         (NodeID::new('C'), TrafficGeneratorType::Periodic(Duration::new(1, 0))),
     ],
+    simulator_link_loss: &[
+    ],
+    neighbor_denylist: &[
+    ],
+    simulator_node_failures: &[
+    ],
 };
@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::config::CONFIG;
+use crate::packet::{Packet, PacketBuilder};
+use crate::stats::Stats;
+use cope_config::types::discovery_type::DiscoveryType;
+use cope_config::types::node_id::NodeID;
+
+struct Neighbor {
+    last_heard: Instant,
+    // NOTE: Neighbors listed in its last beacon
+    neighbors: Vec<NodeID>,
+}
+
+// NOTE: Learns the neighbors of a node from beacons, instead of the static
+// whitelists. Every node broadcasts a beacon with the neighbors it hears once
+// per interval, and forgets neighbors it has not heard for the timeout.
+// Packets are received from every neighbor we hear, but only sent to the ones
+// that hear us as well. Leaves send their traffic to the other leaves, that
+// they or their relays hear. With an election::RelayElection, the relays are
+// all candidates, so none of them is sent traffic.
+pub struct Discovery {
+    id: NodeID,
+    relays: Vec<NodeID>,
+    interval: Duration,
+    timeout: Duration,
+    next_beacon: Option<Instant>,
+    neighbors: HashMap<NodeID, Neighbor>,
+    rxlist: Vec<NodeID>,
+    txlist: Vec<NodeID>,
+    changed: bool,
+    neighbor_changes: u32,
+}
+
+impl Discovery {
+    pub fn new(id: NodeID, relays: Vec<NodeID>, interval: Duration, timeout: Duration) -> Self {
+        Self {
+            id,
+            relays,
+            interval,
+            timeout,
+            next_beacon: None,
+            neighbors: HashMap::new(),
+            rxlist: vec![],
+            txlist: vec![],
+            changed: false,
+            neighbor_changes: 0,
+        }
+    }
+
+    pub fn from_config(id: NodeID) -> Option<Self> {
+        match CONFIG.discovery {
+            DiscoveryType::Static => None,
+            DiscoveryType::Beacon(interval, timeout) => {
+                Some(Self::new(id, CONFIG.get_relays(), interval, timeout))
+            }
+        }
+    }
+
+    pub fn handle_rx(&mut self, sender: NodeID, neighbors: &[NodeID], now: Instant) {
+        if sender == self.id {
+            return;
+        }
+        self.neighbors.insert(
+            sender,
+            Neighbor {
+                last_heard: now,
+                neighbors: neighbors.to_vec(),
+            },
+        );
+        self.update_lists();
+    }
+
+    // NOTE: Beacons are sent at fixed times like the adverts of routing::Router
+    pub fn handle_tx(&mut self, now: Instant) -> Option<Packet> {
+        self.expire(now);
+        self.next_beacon = match self.next_beacon {
+            Some(next) if now < next => return None,
+            Some(next) if now < next + self.interval => Some(next + self.interval),
+            _ => Some(now + self.interval),
+        };
+
+        let packet = PacketBuilder::new()
+            .sender(self.id)
+            .beacon_header(self.rxlist.clone())
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+        Some(packet)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let count = self.neighbors.len();
+        self.neighbors
+            .retain(|_, neighbor| now.saturating_duration_since(neighbor.last_heard) < timeout);
+        if self.neighbors.len() != count {
+            self.update_lists();
+        }
+    }
+
+    fn update_lists(&mut self) {
+        let mut rxlist: Vec<NodeID> = self.neighbors.keys().copied().collect();
+        rxlist.sort_by_key(|id| id.unwrap());
+
+        let symmetric = rxlist
+            .iter()
+            .copied()
+            .filter(|id| self.neighbors[id].neighbors.contains(&self.id));
+        let mut txlist: Vec<NodeID> = match self.relays.contains(&self.id) {
+            true => symmetric.collect(),
+            false => symmetric
+                .flat_map(|id| match self.relays.contains(&id) {
+                    true => self.neighbors[&id].neighbors.clone(),
+                    false => vec![id],
+                })
+                .filter(|&id| id != self.id && !self.relays.contains(&id))
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+        txlist.sort_by_key(|id| id.unwrap());
+
+        // NOTE: Neighbors we started or stopped hearing
+        let changes = self
+            .rxlist
+            .iter()
+            .filter(|id| !rxlist.contains(id))
+            .chain(rxlist.iter().filter(|id| !self.rxlist.contains(id)))
+            .count();
+        if changes > 0 || txlist != self.txlist {
+            log::info!(
+                "[Node {}]: New neighbors {:?}, sending to {:?}",
+                self.id,
+                rxlist,
+                txlist
+            );
+            self.rxlist = rxlist;
+            self.txlist = txlist;
+            self.changed = true;
+            self.neighbor_changes += changes as u32;
+        }
+    }
+
+    // NOTE: Neighbors that hear us as well
+    pub fn neighbors(&self) -> Vec<NodeID> {
+        self.rxlist
+            .iter()
+            .copied()
+            .filter(|id| self.neighbors[id].neighbors.contains(&self.id))
+            .collect()
+    }
+
+    pub fn rxlist(&self) -> &[NodeID] {
+        &self.rxlist
+    }
+
+    pub fn txlist(&self) -> &[NodeID] {
+        &self.txlist
+    }
+
+    // NOTE: Returns both lists, if they changed since the last call
+    pub fn take_neighbors(&mut self) -> Option<(Vec<NodeID>, Vec<NodeID>)> {
+        match std::mem::take(&mut self.changed) {
+            true => Some((self.rxlist.clone(), self.txlist.clone())),
+            false => None,
+        }
+    }
+
+    pub fn update_stats(&mut self, stats: &mut Stats) {
+        stats.add_neighbor_changes(std::mem::take(&mut self.neighbor_changes));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::CodingHeader;
+
+    #[test]
+    fn test_beacon_schedule() {
+        let [a, b] = ['A', 'B'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut leaf = Discovery::new(a, vec![b], interval, interval * 3);
+        let start = Instant::now();
+
+        let first = leaf.handle_tx(start).unwrap();
+        assert_eq!(first.coding_header(), &CodingHeader::Beacon(vec![]));
+        assert!(first.is_broadcast());
+        assert!(leaf.handle_tx(start + interval / 2).is_none());
+
+        // NOTE: Late beacons don't shift the schedule
+        leaf.handle_rx(b, &[], start);
+        let next = leaf.handle_tx(start + interval * 3 / 2).unwrap();
+        assert_eq!(next.coding_header(), &CodingHeader::Beacon(vec![b]));
+        assert!(leaf.handle_tx(start + interval * 19 / 10).is_none());
+        assert!(leaf.handle_tx(start + interval * 2).is_some());
+    }
+
+    // NOTE: The star A - B - {C, D}, where B is the relay
+    #[test]
+    fn test_neighbor_lists() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut leaf = Discovery::new(a, vec![b], interval, interval * 3);
+        let mut relay = Discovery::new(b, vec![b], interval, interval * 3);
+        let start = Instant::now();
+
+        // NOTE: Neighbors that don't hear us yet are not sent to
+        relay.handle_rx(a, &[], start);
+        relay.handle_rx(c, &[b], start);
+        assert_eq!(relay.take_neighbors(), Some((vec![a, c], vec![c])));
+        assert_eq!(relay.take_neighbors(), None);
+
+        // NOTE: Leaves send to the leaves their relay hears
+        leaf.handle_rx(b, &[a, c, d], start);
+        assert_eq!(leaf.txlist(), &[c, d]);
+        leaf.handle_rx(d, &[], start + interval);
+        assert_eq!(leaf.txlist(), &[c, d]);
+
+        // NOTE: Neighbors vanish after the timeout
+        leaf.handle_tx(start + interval * 3);
+        assert_eq!(leaf.rxlist(), &[d]);
+        assert!(leaf.txlist().is_empty());
+        leaf.handle_tx(start + interval * 4);
+        assert!(leaf.rxlist().is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use crate::config::CONFIG;
+use crate::stats::Stats;
+use cope_config::types::discovery_type::DiscoveryType;
+use cope_config::types::election_type::ElectionType;
+use cope_config::types::node_id::NodeID;
+
+// NOTE: Elects the relay among the candidates from the config, instead of
+// always using the first one. Every node elects the candidate with the lowest
+// node id among itself and the neighbors found by discovery::Discovery, so all
+// nodes in range of the same candidates agree on one. If the relay fails, it
+// times out like any other neighbor and the next candidate takes over.
+pub struct RelayElection {
+    id: NodeID,
+    candidates: Vec<NodeID>,
+    // NOTE: Nothing is elected, until every neighbor had the chance to be heard
+    holdoff: Duration,
+    started: Option<Instant>,
+    relay: Option<NodeID>,
+    changed: bool,
+    relay_changes: u32,
+}
+
+impl RelayElection {
+    pub fn new(id: NodeID, candidates: Vec<NodeID>, holdoff: Duration) -> Self {
+        Self {
+            id,
+            candidates,
+            holdoff,
+            started: None,
+            relay: None,
+            changed: false,
+            relay_changes: 0,
+        }
+    }
+
+    // NOTE: Neighbors time out after the discovery timeout, so that is how
+    // long it takes until all of them were heard as well
+    pub fn from_config(id: NodeID) -> Option<Self> {
+        match (CONFIG.election, CONFIG.discovery) {
+            (ElectionType::LowestId, DiscoveryType::Beacon(_, timeout)) => {
+                Some(Self::new(id, CONFIG.get_relays(), timeout))
+            }
+            (_, _) => None,
+        }
+    }
+
+    pub fn update(&mut self, neighbors: &[NodeID], now: Instant) {
+        let started = *self.started.get_or_insert(now);
+        if now.saturating_duration_since(started) < self.holdoff {
+            return;
+        }
+
+        // NOTE: Without any reachable candidate, we keep the last relay, in
+        // case it comes back
+        let Some(elected) = self
+            .candidates
+            .iter()
+            .copied()
+            .filter(|&id| id == self.id || neighbors.contains(&id))
+            .min_by_key(|id| id.unwrap())
+        else {
+            return;
+        };
+        if self.relay == Some(elected) {
+            return;
+        }
+
+        log::info!("[Node {}]: Elected relay {}", self.id, elected);
+        if self.relay.is_some() {
+            self.relay_changes += 1;
+        }
+        self.relay = Some(elected);
+        self.changed = true;
+    }
+
+    pub fn relay(&self) -> Option<NodeID> {
+        self.relay
+    }
+
+    // NOTE: Returns the relay, if it changed since the last call
+    pub fn take_relay(&mut self) -> Option<NodeID> {
+        match std::mem::take(&mut self.changed) {
+            true => self.relay,
+            false => None,
+        }
+    }
+
+    pub fn update_stats(&mut self, stats: &mut Stats) {
+        stats.add_relay_changes(std::mem::take(&mut self.relay_changes));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // NOTE: The candidates B and C, as seen by the leaf A and by C
+    #[test]
+    fn test_lowest_id_election() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let holdoff = Duration::from_secs(1);
+        let mut leaf = RelayElection::new(a, vec![b, c], holdoff);
+        let mut candidate = RelayElection::new(c, vec![b, c], holdoff);
+        let start = Instant::now();
+
+        leaf.update(&[c], start);
+        candidate.update(&[a], start);
+        assert_eq!(leaf.relay(), None);
+        assert_eq!(candidate.relay(), None);
+
+        leaf.update(&[b, c], start + holdoff);
+        candidate.update(&[a, b], start + holdoff);
+        assert_eq!(leaf.take_relay(), Some(b));
+        assert_eq!(leaf.take_relay(), None);
+        assert_eq!(candidate.take_relay(), Some(b));
+
+        // NOTE: B failed and timed out
+        leaf.update(&[c], start + holdoff * 3);
+        candidate.update(&[a], start + holdoff * 3);
+        assert_eq!(leaf.take_relay(), Some(c));
+        assert_eq!(candidate.take_relay(), Some(c));
+
+        // NOTE: The leaf lost all candidates, so it keeps waiting for C
+        leaf.update(&[], start + holdoff * 4);
+        assert_eq!(leaf.take_relay(), None);
+        assert_eq!(leaf.relay(), Some(c));
+        assert_eq!(leaf.relay_changes, 1);
+    }
+}
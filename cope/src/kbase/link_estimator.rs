@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::packet::{reception_report::REPORT_WINDOW, CodingInfo, ReceptionReport};
+use cope_config::types::node_id::NodeID;
+
+// NOTE: Weight of a new sample in the moving average of a delivery probability
+const SAMPLE_WEIGHT: f64 = 0.2;
+
+// NOTE: A coded packet, that was sent although the relay did not know
+// whether nexthop has overheard all natives in guessed.
+struct Guess {
+    nexthop: NodeID,
+    info: CodingInfo,
+    guessed: Vec<CodingInfo>,
+    sent: Instant,
+}
+
+// NOTE: Estimates the probability that a node overhears packets from another
+// node, as described in section 3.3 of the COPE paper. Reception reports tell
+// us how many packets of a source a neighbor has overheard, and the acks for
+// guesses we made tell us if the neighbor really had the guessed packets.
+pub struct LinkEstimator {
+    // NOTE: (from, to) -> probability that to receives a packet sent by from
+    links: HashMap<(NodeID, NodeID), f64>,
+    guesses: Vec<Guess>,
+    correct_guesses: u32,
+    wrong_guesses: u32,
+}
+
+impl LinkEstimator {
+    pub fn new() -> Self {
+        Self {
+            links: HashMap::new(),
+            guesses: vec![],
+            correct_guesses: 0,
+            wrong_guesses: 0,
+        }
+    }
+
+    // NOTE: Links we know nothing about are assumed to be broken,
+    // so we never guess without having heard a report first.
+    pub fn delivery_probability(&self, from: NodeID, to: NodeID) -> f64 {
+        self.links.get(&(from, to)).copied().unwrap_or(0.0)
+    }
+
+    fn add_sample(&mut self, from: NodeID, to: NodeID, sample: f64) {
+        let estimate = self.links.entry((from, to)).or_insert(sample);
+        *estimate += SAMPLE_WEIGHT * (sample - *estimate);
+    }
+
+    pub fn insert_report(&mut self, reporter: NodeID, report: &ReceptionReport) {
+        // NOTE: Every id up to last_id was sent by the source, so the share of
+        // ids in the report window that were received is the delivery probability.
+        // The window is shorter, as long as the source has sent less packets.
+        let window = usize::min(REPORT_WINDOW + 1, report.last_id().value() as usize + 1);
+        let received = report.ids().len().min(window);
+        self.add_sample(report.source(), reporter, received as f64 / window as f64);
+    }
+
+    pub fn record_guess(&mut self, nexthop: NodeID, info: CodingInfo, guessed: Vec<CodingInfo>) {
+        self.guesses.push(Guess {
+            nexthop,
+            info,
+            guessed,
+            sent: Instant::now(),
+        });
+    }
+
+    // NOTE: Nexthop could only ack info, if it was able to decode it,
+    // so it did have all the packets we guessed.
+    pub fn ack_received(&mut self, info: &CodingInfo) {
+        let Some(pos) = self.guesses.iter().position(|g| g.info.key() == info.key()) else {
+            return;
+        };
+        let guess = self.guesses.remove(pos);
+        for guessed in &guess.guessed {
+            self.add_sample(guessed.source, guess.nexthop, 1.0);
+        }
+        self.correct_guesses += 1;
+    }
+
+    // NOTE: Guesses that are not acked in time are assumed to be wrong. We
+    // don't know which of the guessed packets were missing, so all links are punished.
+    pub fn expire_guesses(&mut self, timeout: Duration) {
+        let (expired, pending) = std::mem::take(&mut self.guesses)
+            .into_iter()
+            .partition::<Vec<_>, _>(|g| g.sent.elapsed() >= timeout);
+        self.guesses = pending;
+        for guess in expired {
+            for guessed in &guess.guessed {
+                self.add_sample(guessed.source, guess.nexthop, 0.0);
+            }
+            self.wrong_guesses += 1;
+        }
+    }
+
+    // NOTE: Returns the number of correct and wrong guesses since the last call
+    pub fn take_outcomes(&mut self) -> (u32, u32) {
+        let outcomes = (self.correct_guesses, self.wrong_guesses);
+        self.correct_guesses = 0;
+        self.wrong_guesses = 0;
+        outcomes
+    }
+}
+
+impl Default for LinkEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bitvec::prelude as bv;
+
+    use super::*;
+    use crate::packet::PacketID;
+
+    fn info(source: NodeID, id: u16) -> CodingInfo {
+        CodingInfo {
+            source,
+            id: PacketID::new(id),
+            nexthop: NodeID::new('D'),
+            destination: NodeID::new('D'),
+            len: 0,
+        }
+    }
+
+    #[test]
+    fn test_estimate_from_reports() {
+        let [a, c] = ['A', 'C'].map(NodeID::new);
+        let mut links = LinkEstimator::new();
+        assert_eq!(links.delivery_probability(a, c), 0.0);
+
+        // NOTE: C has overheard 3 of the 9 packets covered by the report
+        let mut bits = bv::bitvec![0; REPORT_WINDOW];
+        bits.set(1, true);
+        bits.set(4, true);
+        links.insert_report(c, &ReceptionReport::new(a, PacketID::new(20), bits));
+        let estimate = links.delivery_probability(a, c);
+        assert!((estimate - 3.0 / 9.0).abs() < 1e-9);
+        assert_eq!(links.delivery_probability(c, a), 0.0);
+
+        // NOTE: Only packet 0 was sent yet, so the window is shorter
+        let bits = bv::bitvec![0; REPORT_WINDOW];
+        links.insert_report(a, &ReceptionReport::new(c, PacketID::new(0), bits));
+        assert_eq!(links.delivery_probability(c, a), 1.0);
+    }
+
+    #[test]
+    fn test_guess_outcomes() {
+        let [a, c, e] = ['A', 'C', 'E'].map(NodeID::new);
+        let mut links = LinkEstimator::new();
+        links.add_sample(a, c, 0.5);
+        links.add_sample(e, c, 0.5);
+
+        links.record_guess(c, info(e, 1), vec![info(a, 1)]);
+        links.record_guess(c, info(e, 2), vec![info(a, 2)]);
+        links.ack_received(&info(e, 1));
+        assert!(links.delivery_probability(a, c) > 0.5);
+        assert_eq!(links.delivery_probability(e, c), 0.5);
+
+        links.expire_guesses(Duration::ZERO);
+        assert_eq!(links.take_outcomes(), (1, 1));
+        assert_eq!(links.take_outcomes(), (0, 0));
+
+        // NOTE: Acks for guesses that already expired don't count twice
+        links.ack_received(&info(e, 2));
+        assert_eq!(links.take_outcomes(), (0, 0));
+    }
+}
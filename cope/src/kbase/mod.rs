@@ -1,12 +1,15 @@
-use crate::packet::CodingInfo;
+use crate::packet::{CodingInfo, ReceptionReport};
 use cope_config::types::node_id::NodeID;
 
+pub mod link_estimator;
 pub mod simple_kbase;
 
+pub use link_estimator::LinkEstimator;
 pub use simple_kbase::SimpleKBase;
 
 pub trait KBase {
     fn knows(&self, next_hop: &NodeID, info: &CodingInfo) -> bool;
     fn insert(&mut self, next_hop: NodeID, info: CodingInfo);
+    fn insert_report(&mut self, next_hop: NodeID, report: &ReceptionReport);
     fn size(&self) -> usize;
 }
@@ -1,36 +1,120 @@
 use std::collections::HashMap;
 use super::KBase;
-use crate::packet::CodingInfo;
+use crate::packet::{packet_id::MAX_ID_AGE, CodingInfo, PacketID, ReceptionReport};
 use cope_config::types::node_id::NodeID;
 
+// NOTE: Packets are identified by their source and id only. Reception reports
+// do not contain the nexthop of a packet, so we can not store full CodingInfos.
 pub struct SimpleKBase {
-    table: HashMap<NodeID, Vec<CodingInfo>>,
+    table: HashMap<NodeID, Vec<(NodeID, PacketID)>>,
+    newest_ids: HashMap<NodeID, PacketID>,
     max_size: usize,
 }
 
 impl SimpleKBase {
     pub fn new(next_hops: Vec<NodeID>, max_size: usize) -> Self{
         let table = next_hops.iter().map(|&i| (i, vec![])).collect();
-        Self { table, max_size }
+        Self { table, newest_ids: HashMap::new(), max_size }
+    }
+
+    // NOTE: For neighbors found by discovery::Discovery, entries of neighbors
+    // that vanished are kept in case they come back
+    pub fn insert_next_hop(&mut self, next_hop: NodeID) {
+        self.table.entry(next_hop).or_default();
+    }
+
+    fn is_stale(&self, source: NodeID, id: PacketID) -> bool {
+        let Some(&newest) = self.newest_ids.get(&source) else {
+            return true;
+        };
+        !id.is_within(newest, MAX_ID_AGE)
+    }
+
+    fn update_newest(&mut self, source: NodeID, id: PacketID) {
+        let newest = self.newest_ids.entry(source).or_insert(id);
+        if id > *newest { *newest = id; }
+    }
+
+    fn insert_entry(&mut self, next_hop: NodeID, entry: (NodeID, PacketID)) {
+        if !self.table.contains_key(&next_hop) {
+            log::warn!("KnowledgeBase has no Entry for nexthop {}", next_hop);
+            return;
+        }
+        self.update_newest(entry.0, entry.1);
+        if self.is_stale(entry.0, entry.1) { return; }
+
+        let newest_ids = &self.newest_ids;
+        let list = self.table.get_mut(&next_hop).unwrap();
+        list.retain(|&(source, id)| id.is_within(newest_ids[&source], MAX_ID_AGE));
+        if list.contains(&entry) { return; }
+        let is_at_max_size = list.len() >= self.max_size;
+        if  is_at_max_size { list.remove(0); }
+        list.push(entry);
     }
 }
 
 impl KBase for SimpleKBase {
     fn knows(&self, next_hop: &NodeID, info: &CodingInfo) -> bool{
+        if self.is_stale(info.source, info.id) { return false; }
+        // NOTE: Discovered neighbors may not have an entry yet, see insert_next_hop
         self.table.get(next_hop)
-            .expect("knowledge_base should have a filed for every node!")
-            .contains(info)
+            .is_some_and(|list| list.contains(&info.key()))
     }
 
     fn insert(&mut self, next_hop: NodeID, info: CodingInfo){
-        let list = self.table.get_mut(&next_hop)
-            .expect("KnowledgeBase should contain Entry for nexthop");
-        let is_at_max_size = list.len() >= self.max_size;
-        if  is_at_max_size { list.remove(0); }
-        list.push(info);
+        self.insert_entry(next_hop, info.key());
+    }
+
+    fn insert_report(&mut self, next_hop: NodeID, report: &ReceptionReport) {
+        // NOTE: Insert the oldest ids first, so last_id is evicted last.
+        for id in report.ids().into_iter().rev() {
+            self.insert_entry(next_hop, (report.source(), id));
+        }
     }
 
     fn size(&self) -> usize{
         self.table.iter().map(|(_, list)| list.len()).sum()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(id: u16) -> CodingInfo {
+        CodingInfo {
+            source: NodeID::new('A'),
+            id: PacketID::new(id),
+            nexthop: NodeID::new('C'),
+            destination: NodeID::new('C'),
+            len: 0,
+        }
+    }
+
+    #[test]
+    fn test_knows_across_wrap() {
+        let node_c = NodeID::new('C');
+        let mut kbase = SimpleKBase::new(vec![node_c], 8);
+        kbase.insert(node_c, info(65535));
+        kbase.insert(node_c, info(0));
+        assert!(kbase.knows(&node_c, &info(65535)));
+        assert!(kbase.knows(&node_c, &info(0)));
+    }
+
+    #[test]
+    fn test_stale_entries_are_forgotten() {
+        let node_c = NodeID::new('C');
+        let mut kbase = SimpleKBase::new(vec![node_c], 8);
+        kbase.insert(node_c, info(5));
+        assert!(kbase.knows(&node_c, &info(5)));
+
+        // NOTE: Once the source is far enough ahead, id 5 may be reused
+        // for a new packet after the ids wrap around.
+        kbase.insert(node_c, info(5 + MAX_ID_AGE));
+        assert!(!kbase.knows(&node_c, &info(5)));
+        assert_eq!(kbase.size(), 1);
+
+        kbase.insert(node_c, info(5));
+        assert!(!kbase.knows(&node_c, &info(5)));
+    }
+}
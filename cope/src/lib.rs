@@ -1,10 +1,16 @@
 pub mod channel;
 pub mod coding;
 pub mod config;
+pub mod discovery;
+pub mod election;
 pub mod kbase;
 pub mod node;
 pub mod packet;
 pub mod packet_pool;
+pub mod pcap;
+pub mod routing;
+pub mod scheduler;
+pub mod security;
 pub mod stats;
 pub mod topology;
 pub mod traffic_generator;
@@ -1,12 +1,18 @@
-use crate::coding::leaf_node_coding::LeafNodeCoding;
-use crate::coding::relay_node_coding::RelayNodeCoding;
-use crate::coding::CodingStrategy;
+use crate::coding::{self, CodingError, CodingStrategy};
 use crate::config::CONFIG;
+use crate::discovery::Discovery;
+use crate::election::RelayElection;
+use crate::packet::CodingHeader;
+use crate::pcap::{Direction, PcapError, SharedPcapWriter};
+use crate::routing::Router;
+use crate::security::Security;
 use crate::stats::Stats;
 use crate::topology::Topology;
-use crate::traffic_generator::TrafficGenerator;
 use crate::{benchmark::BenchTimer, channel::Channel};
+use crate::Packet;
 use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+use std::time::Instant;
 
 pub struct Node {
     id: NodeID,
@@ -15,6 +21,13 @@ pub struct Node {
     coding: Box<dyn CodingStrategy + Send>,
     bench: BenchTimer,
     stats: Stats,
+    capture: Option<SharedPcapWriter>,
+    security: Option<Security>,
+    router: Option<Router>,
+    discovery: Option<Discovery>,
+    election: Option<RelayElection>,
+    // NOTE: Needed to create the coding again, see update_relay
+    tgt: TrafficGeneratorType,
 }
 
 impl Node {
@@ -39,20 +52,39 @@ impl Node {
             .get_generator_type_for(id)
             .expect("Config should contain traffic generator type");
 
-        let topology = Topology::new(id, CONFIG.relay, rx_whitelist, tx_whitelist.clone());
-        let generator = TrafficGenerator::from_tg_type(tgt, tx_whitelist.clone(), id);
-        let coding: Box<dyn CodingStrategy + Send> = match topology.is_relay() {
-            true => Box::new(RelayNodeCoding::new(tx_whitelist.clone())),
-            false => Box::new(LeafNodeCoding::new(generator)),
+        // NOTE: With discovery, the whitelists only filter the discovered lists
+        let discovery = Discovery::from_config(id);
+        let (rxlist, txlist) = match discovery {
+            Some(_) => (vec![], vec![]),
+            None => (rx_whitelist.clone(), tx_whitelist.clone()),
         };
 
+        // NOTE: With an election, the relays are only candidates. The first one
+        // is used, until one was elected.
+        let election = RelayElection::from_config(id);
+        let relays = match election {
+            Some(_) => CONFIG.get_relays()[..1].to_vec(),
+            None => CONFIG.get_relays(),
+        };
+        let topology = Topology::new(id, relays[0], rxlist, txlist.clone())
+            .with_relays(relays)
+            .with_routes(CONFIG.get_routes_for(id))
+            .with_filters(rx_whitelist, tx_whitelist, CONFIG.get_denylist_for(id));
+        let coding = coding::from_role(id, &topology, tgt);
+
         Node {
             id,
             topology,
             channel,
             coding,
+            tgt,
             bench: BenchTimer::new(),
             stats,
+            capture: None,
+            security: Security::from_config(),
+            router: Router::from_config(id),
+            discovery,
+            election,
         }
     }
 
@@ -60,13 +92,137 @@ impl Node {
         self.bench.bench_log_path(path);
     }
 
+    // NOTE: Every packet sent and received by this node is written to capture.
+    // The capture can be shared with other nodes to get a merged capture.
+    pub fn set_capture(&mut self, capture: SharedPcapWriter) {
+        self.capture = Some(capture);
+    }
+
+    fn capture(&self, direction: Direction, packet: &Packet) {
+        let Some(capture) = &self.capture else {
+            return;
+        };
+        let result = match capture.lock() {
+            Ok(mut writer) => writer.write_packet(self.id, direction, packet),
+            Err(_) => Err(PcapError::Poisoned),
+        };
+        if let Err(e) = result {
+            log::warn!("[Node {}]: {}", self.id, e);
+        }
+    }
+
     pub fn tick(&mut self) {
         self.receive();
         self.transmit();
+        self.coding.update_stats(&mut self.stats);
+        if let Some(router) = &mut self.router {
+            router.update_stats(&mut self.stats);
+        }
+        if let Some(discovery) = &mut self.discovery {
+            discovery.update_stats(&mut self.stats);
+        }
+        if let Some(election) = &mut self.election {
+            election.update_stats(&mut self.stats);
+        }
         self.bench.log(self.id);
     }
 
+    // NOTE: Beacons go out before adverts, since routes need neighbors first.
+    // Neighbors are updated on every tick, because they also time out.
+    fn transmit_beacon(&mut self) -> bool {
+        let Some(discovery) = &mut self.discovery else {
+            return false;
+        };
+        let packet = discovery.handle_tx(Instant::now());
+        self.update_neighbors();
+        self.update_relay();
+        let Some(packet) = packet else {
+            return false;
+        };
+        log::info!("[Node {}]: Send {}", self.id, packet);
+        self.send(packet);
+        true
+    }
+
+    fn update_neighbors(&mut self) {
+        let Some((rxlist, txlist)) = self.discovery.as_mut().and_then(|d| d.take_neighbors())
+        else {
+            return;
+        };
+        self.topology.set_neighbors(rxlist, txlist);
+        self.coding.update_neighbors(&self.topology);
+    }
+
+    // NOTE: Packets the old coding still had queued are carried over, when the
+    // role changes. Leaves that keep their role send them to the new relay.
+    fn update_relay(&mut self) {
+        let (Some(election), Some(discovery)) = (&mut self.election, &mut self.discovery) else {
+            return;
+        };
+        election.update(&discovery.neighbors(), Instant::now());
+        let Some(relay) = election.take_relay() else {
+            return;
+        };
+        let was_relay = self.topology.is_relay();
+        self.topology.set_relays(vec![relay]);
+        if self.topology.is_relay() != was_relay {
+            log::info!("[Node {}]: Switching role, relay is {}", self.id, relay);
+            let queued = self.coding.take_queued();
+            self.coding = coding::from_role(self.id, &self.topology, self.tgt);
+            self.coding.requeue(queued, &self.topology);
+        } else {
+            self.coding.update_relay(&self.topology);
+        }
+    }
+
+    // NOTE: Adverts go out before any coded traffic, so routes stay fresh
+    // even if the relay is busy.
+    fn transmit_advert(&mut self) -> bool {
+        let Some(router) = &mut self.router else {
+            return false;
+        };
+        let Some(packet) = router.handle_tx(Instant::now()) else {
+            return false;
+        };
+        self.update_routes();
+        log::info!("[Node {}]: Send {}", self.id, packet);
+        self.send(packet);
+        true
+    }
+
+    fn update_routes(&mut self) {
+        if let Some(routes) = self.router.as_mut().and_then(|r| r.take_routes()) {
+            self.topology.set_routes(routes);
+        }
+    }
+
+    // NOTE: Returns false, if the packet could not be sealed
+    fn send(&mut self, packet: Packet) -> bool {
+        let packet = match &mut self.security {
+            Some(security) => match security.seal(packet) {
+                Ok(sealed) => sealed,
+                Err(e) => {
+                    log::error!("[Node {}]: {}", self.id, e);
+                    return false;
+                }
+            },
+            None => packet,
+        };
+        if let Err(e) = self.channel.transmit(&packet) {
+            log::error!("{:?}", e);
+        } else {
+            self.capture(Direction::Tx, &packet);
+            self.stats.add_sent(&packet);
+            self.stats.log_data();
+        }
+        true
+    }
+
     fn transmit(&mut self) {
+        if self.transmit_beacon() || self.transmit_advert() {
+            return;
+        }
+
         self.bench.record("Transmit handle_tx");
         let packet_to_send = match self.coding.handle_tx(&self.topology) {
             Ok(opt) => opt,
@@ -80,11 +236,9 @@ impl Node {
 
         if let Some(packet) = packet_to_send {
             log::info!("[Node {}]: Send {}", self.id, packet);
-            if let Err(e) = self.channel.transmit(&packet) {
-                log::error!("{:?}", e);
-            } else {
-                self.stats.add_sent(&packet);
-                self.stats.log_data();
+            if !self.send(packet) {
+                self.bench.stop("Transmit Channel");
+                return;
             }
             self.coding.update_last_packet_send();
             //TODO: handle error
@@ -97,11 +251,54 @@ impl Node {
         self.bench.record("Receive Channel");
         if let Some(packet) = self.channel.receive() {
             self.bench.stop("Receive Channel");
-            if !self.topology.can_receive_from(packet.sender()) {
+            self.capture(Direction::Rx, &packet);
+            let sender = packet.sender();
+            // NOTE: Beacons are how we hear about new neighbors, see Discovery
+            let is_beacon = matches!(packet.coding_header(), CodingHeader::Beacon(_));
+            if self.topology.is_denied(sender)
+                || !(is_beacon || self.topology.can_receive_from(sender))
+            {
                 return;
             }
 
+            let packet = match &mut self.security {
+                Some(security) => match security.open(packet) {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        log::warn!("[Node {}]: Rejected packet, {}", self.id, e);
+                        self.stats.add_rejected(sender);
+                        self.stats.log_data();
+                        return;
+                    }
+                },
+                None => packet,
+            };
+
             log::info!("[Node {}]: Received {}", self.id, packet);
+
+            if let CodingHeader::Beacon(neighbors) = packet.coding_header() {
+                if let Some(discovery) = &mut self.discovery {
+                    discovery.handle_rx(sender, neighbors, Instant::now());
+                    self.update_neighbors();
+                    self.update_relay();
+                }
+                self.stats
+                    .add_received(sender, packet.coding_header(), 0, true);
+                self.stats.log_data();
+                return;
+            }
+
+            if let CodingHeader::Routing(advert) = packet.coding_header() {
+                if let Some(router) = &mut self.router {
+                    router.handle_rx(sender, advert, Instant::now());
+                    self.update_routes();
+                }
+                self.stats
+                    .add_received(sender, packet.coding_header(), 0, true);
+                self.stats.log_data();
+                return;
+            }
+
             self.bench.record("Receive handle_rx");
 
             match self.coding.handle_rx(&packet, &self.topology) {
@@ -117,6 +314,11 @@ impl Node {
                     );
                     self.stats.log_data();
                 }
+                Err(CodingError::AuthenticationError(e)) => {
+                    log::warn!("[Node {}]: Rejected packet, {}", self.id, e);
+                    self.stats.add_rejected(packet.sender());
+                    self.stats.log_data();
+                }
                 Err(e) => {
                     log::error!("{}", e);
                     self.stats.add_received(
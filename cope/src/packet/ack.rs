@@ -4,7 +4,7 @@ use super::CodingInfo;
 
 
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Ack {
     pub source: NodeID,
     pub packets: Vec<CodingInfo>,
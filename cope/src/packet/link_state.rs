@@ -0,0 +1,56 @@
+use cope_config::types::node_id::NodeID;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use super::PacketID;
+
+// NOTE: Delivery ratios are sent as multiples of 1 / MAX_DELIVERY,
+// so they fit into a single byte.
+pub const MAX_DELIVERY: u8 = u8::MAX;
+
+// NOTE: Share of the adverts from neighbor, that the origin of the advert
+// has received recently, see routing::ProbeTracker.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct LinkQuality {
+    pub neighbor: NodeID,
+    pub delivery: u8,
+}
+
+impl LinkQuality {
+    pub fn new(neighbor: NodeID, ratio: f64) -> Self {
+        Self {
+            neighbor,
+            delivery: (ratio.clamp(0.0, 1.0) * MAX_DELIVERY as f64).round() as u8,
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.delivery as f64 / MAX_DELIVERY as f64
+    }
+}
+
+// NOTE: Every node broadcasts its own advert periodically. Neighbors use them
+// as probes to measure how well they can hear the origin, and rebroadcast the
+// newest advert of every origin once, so all nodes learn the whole network.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct LinkStateAdvert {
+    pub origin: NodeID,
+    pub seq: PacketID,
+    pub links: Vec<LinkQuality>,
+}
+
+impl Display for LinkStateAdvert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}@{} [{}]",
+            self.seq,
+            self.origin,
+            self.links
+                .iter()
+                .map(|l| format!("{} {:.2}", l.neighbor, l.ratio()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
@@ -1,7 +1,16 @@
 pub mod packet;
 pub mod packet_data;
+pub mod packet_id;
 pub mod ack;
+pub mod link_state;
+pub mod reception_report;
+pub mod wire;
+pub mod xor;
 
-pub use packet::{Packet, PacketID, CodingInfo, CodingHeader, PacketBuilder};
+pub use packet::{Packet, CodingInfo, CodingHeader, LinearHeader, PacketBuilder, Seal};
 pub use packet_data::PacketData;
+pub use packet_id::PacketID;
 pub use ack::Ack;
+pub use link_state::{LinkQuality, LinkStateAdvert};
+pub use reception_report::ReceptionReport;
+pub use wire::{DecodeLimits, PacketDecodeError, PacketEncodeError};
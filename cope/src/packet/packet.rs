@@ -1,4 +1,3 @@
-use bitvec::prelude as bv;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::vec::Vec;
@@ -6,30 +5,74 @@ use std::vec::Vec;
 use cope_config::types::node_id::NodeID;
 
 use super::Ack;
+use super::LinkStateAdvert;
 use super::PacketData;
-
-pub type PacketID = u16;
+use super::PacketID;
+use super::ReceptionReport;
+use super::wire::{self, DecodeLimits, PacketDecodeError, PacketEncodeError};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct CodingInfo {
     pub source: NodeID,
     pub id: PacketID,
+    // NOTE: Receiver of the current hop, it is rewritten by every relay on the
+    // way to destination, see topology::Topology::nexthop_for_target.
     pub nexthop: NodeID,
-    // FIXME: Needs to include native packet length,
-    // so we can remove zero padding after decoding.
+    pub destination: NodeID,
+    // NOTE: Length of the native packet data, so we can remove zero padding
+    // after decoding. It is filled in by PacketBuilder::build().
+    pub len: u16,
+}
+
+impl CodingInfo {
+    // NOTE: Packets are identified by their source and id only, like in the
+    // knowledge base. The nexthop changes from hop to hop, but acks, NACKs and
+    // packet pools of all nodes have to agree on which packet is meant.
+    pub fn key(&self) -> (NodeID, PacketID) {
+        (self.source, self.id)
+    }
 }
 
 impl Display for CodingInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}@{} -> {}", self.id, self.source, self.nexthop)
+        write!(f, "{}@{} -> {}", self.id, self.source, self.nexthop)?;
+        if self.destination != self.nexthop {
+            write!(f, " => {}", self.destination)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// NOTE: Header of packets coded with RLNC, see coding::generation. The data
+// is the combination of the natives of the generation with these coefficients.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct LinearHeader {
+    pub source: NodeID,
+    pub nexthop: NodeID,
+    pub generation: PacketID,
+    pub coefficients: Vec<u8>,
+}
+
+impl Display for LinearHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}@{} -> {}, {:?}",
+            self.generation, self.source, self.nexthop, self.coefficients
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum CodingHeader {
     Native(CodingInfo),
     Encoded(Vec<CodingInfo>),
     Control(NodeID),
+    Linear(LinearHeader),
+    // NOTE: Adverts are handled by routing::Router, not by the coding strategies
+    Routing(LinkStateAdvert),
+    // NOTE: Neighbors the sender currently hears, handled by discovery::Discovery
+    Beacon(Vec<NodeID>),
 }
 
 impl Display for CodingHeader {
@@ -45,24 +88,42 @@ impl Display for CodingHeader {
                     .join(", ")
             ),
             Self::Control(n) => write!(f, "Control<{}>", n),
+            Self::Linear(h) => write!(f, "Linear<{}>", h),
+            Self::Routing(a) => write!(f, "Routing<{}>", a),
+            Self::Beacon(neighbors) => write!(
+                f,
+                "Beacon<{}>",
+                neighbors
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ReceptionReport {
-    source: NodeID,
-    last_id: PacketID,
-    preceding_ids: bv::BitVec,
+pub const SEAL_TAG_SIZE: usize = 16;
+
+// NOTE: Added by the sender when packets are protected with a network key,
+// see security::Security. The data of sealed packets is encrypted.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Seal {
+    pub epoch: u32,
+    pub counter: u32,
+    pub tag: [u8; SEAL_TAG_SIZE],
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Packet {
     sender: NodeID,
     coding_header: CodingHeader,
     reception_header: Vec<ReceptionReport>,
     ack_header: Vec<Ack>,
+    // NOTE: Packets the sender could not decode, see coding::LeafNodeCoding
+    nack_header: Vec<CodingInfo>,
     data: PacketData,
+    seal: Option<Seal>,
 }
 
 impl Display for Packet {
@@ -72,9 +133,11 @@ impl Display for Packet {
         // which is IMO more important.
         write!(
             f,
-            "{}, {} ACKs: {}",
+            "{}, {} ACKs, {} NACKs, {} Reports: {}",
             self.coding_header,
             self.ack_header.len(),
+            self.nack_header.len(),
+            self.reception_header.len(),
             self.data
         )
     }
@@ -93,10 +156,22 @@ impl Packet {
         &self.coding_header
     }
 
+    pub fn reception_header(&self) -> &[ReceptionReport] {
+        &self.reception_header
+    }
+
     pub fn ack_header(&self) -> &[Ack] {
         &self.ack_header
     }
 
+    pub fn nack_header(&self) -> &[CodingInfo] {
+        &self.nack_header
+    }
+
+    pub fn seal(&self) -> Option<&Seal> {
+        self.seal.as_ref()
+    }
+
     // FIXME: This is just a hack, we always need `some` NodeID to act as the receiver,
     // because the ESPChannel needs to internally translate the receiver to a single MAC address.
     // Check if we should instead change receiver to what it was before!
@@ -105,20 +180,64 @@ impl Packet {
             CodingHeader::Native(ref info) => Some(info.nexthop),
             CodingHeader::Encoded(ref infos) => Some(infos.first().unwrap().nexthop),
             CodingHeader::Control(node_id) => Some(node_id),
+            CodingHeader::Linear(ref header) => Some(header.nexthop),
+            // NOTE: Adverts and beacons are meant for all neighbors, see is_broadcast
+            CodingHeader::Routing(_) | CodingHeader::Beacon(_) => None,
         }
     }
 
+    // NOTE: Packets without a receiver, that every neighbor should get
+    pub fn is_broadcast(&self) -> bool {
+        matches!(
+            self.coding_header,
+            CodingHeader::Routing(_) | CodingHeader::Beacon(_)
+        )
+    }
+
     pub fn set_sender(mut self, sender: NodeID) -> Self {
         self.sender = sender;
         self
     }
 
-    pub fn deserialize_from(bytes: &[u8]) -> Result<Packet, bincode::Error> {
-        bincode::deserialize(bytes)
+    pub fn set_seal(mut self, seal: Option<Seal>) -> Self {
+        self.seal = seal;
+        self
     }
 
-    pub fn serialize_into(&self) -> Result<Vec<u8>, bincode::Error> {
-        bincode::serialize(self)
+    // NOTE: Goes through PacketBuilder::build() again, so the length
+    // of natives stays in sync with their data.
+    pub fn set_data(self, data: PacketData) -> Result<Self, PacketBuildError> {
+        PacketBuilder {
+            sender: Some(self.sender),
+            coding_header: Some(self.coding_header),
+            reception_header: Some(self.reception_header),
+            ack_header: Some(self.ack_header),
+            nack_header: Some(self.nack_header),
+            data: Some(data),
+            seal: self.seal,
+        }
+        .build()
+    }
+
+    pub fn deserialize_from(bytes: &[u8]) -> Result<Packet, PacketDecodeError> {
+        wire::decode(bytes, &DecodeLimits::from_config())
+    }
+
+    pub fn deserialize_with_limits(
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Packet, PacketDecodeError> {
+        wire::decode(bytes, limits)
+    }
+
+    // NOTE: Like deserialize_from, but the packet data is a slice of buffer
+    // instead of a copy.
+    pub fn deserialize_shared(buffer: &PacketData) -> Result<Packet, PacketDecodeError> {
+        wire::decode_shared(buffer, &DecodeLimits::from_config())
+    }
+
+    pub fn serialize_into(&self) -> Result<Vec<u8>, PacketEncodeError> {
+        wire::encode(self)
     }
 }
 
@@ -126,14 +245,22 @@ impl Packet {
 pub struct PacketBuilder {
     sender: Option<NodeID>,
     coding_header: Option<CodingHeader>,
-    _reception_header: Option<Vec<ReceptionReport>>,
+    reception_header: Option<Vec<ReceptionReport>>,
     ack_header: Option<Vec<Ack>>,
+    nack_header: Option<Vec<CodingInfo>>,
     data: Option<PacketData>,
+    seal: Option<Seal>,
 }
 
 #[derive(Debug)]
 pub struct PacketBuildError(&'static str);
 
+impl PacketBuildError {
+    pub fn reason(&self) -> &'static str {
+        self.0
+    }
+}
+
 impl Display for PacketBuildError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[PacketBuildError]: {}", self.0)
@@ -182,11 +309,41 @@ impl PacketBuilder {
         self
     }
 
+    pub fn linear_header(mut self, header: LinearHeader) -> Self {
+        self.coding_header = Some(CodingHeader::Linear(header));
+        self
+    }
+
+    pub fn routing_header(mut self, advert: LinkStateAdvert) -> Self {
+        self.coding_header = Some(CodingHeader::Routing(advert));
+        self
+    }
+
+    pub fn beacon_header(mut self, neighbors: Vec<NodeID>) -> Self {
+        self.coding_header = Some(CodingHeader::Beacon(neighbors));
+        self
+    }
+
+    pub fn reception_header(mut self, reception_header: Vec<ReceptionReport>) -> Self {
+        self.reception_header = Some(reception_header);
+        self
+    }
+
     pub fn ack_header(mut self, ack_header: Vec<Ack>) -> Self {
         self.ack_header = Some(ack_header);
         self
     }
 
+    pub fn nack_header(mut self, nack_header: Vec<CodingInfo>) -> Self {
+        self.nack_header = Some(nack_header);
+        self
+    }
+
+    pub fn seal(mut self, seal: Seal) -> Self {
+        self.seal = Some(seal);
+        self
+    }
+
     pub fn build(self) -> Result<Packet, PacketBuildError> {
         // check if everything is set correctly
         let Some(sender) = self.sender else {
@@ -197,18 +354,27 @@ impl PacketBuilder {
             return Err(PacketBuildError("Coding Header must be specified."));
         };
 
-        // TODO: add ReceptionReport
-        // let Some(reception_header) = self.reception_header else {
-        //     return Err(PacketBuildError("Reception Report must be specified."));
-        // };
+        // NOTE: Not every packet has something to report, so an empty
+        // reception header is fine.
+        let reception_header = self.reception_header.unwrap_or_default();
 
         let Some(ack_header) = self.ack_header else {
             return Err(PacketBuildError("Ack Header must be specified."));
         };
 
+        // NOTE: Like the reception header, the nack header is usually empty
+        let nack_header = self.nack_header.unwrap_or_default();
+
         use CodingHeader as CH;
-        let data = match (&coding_header, self.data) {
-            (CH::Native(_), Some(data)) => data,
+        let mut coding_header = coding_header;
+        let data = match (&mut coding_header, self.data) {
+            (CH::Native(info), Some(data)) => {
+                let Ok(len) = u16::try_from(data.len()) else {
+                    return Err(PacketBuildError("Native Packet Data is too large."));
+                };
+                info.len = len;
+                data
+            }
             (CH::Native(_), None) => {
                 return Err(PacketBuildError("Native Packet must have Packet Data."));
             }
@@ -222,14 +388,32 @@ impl PacketBuilder {
                 ));
             }
             (CH::Control(_), None) => PacketData::new(vec![]),
+            (CH::Linear(_), Some(data)) => data,
+            (CH::Linear(_), None) => {
+                return Err(PacketBuildError("Linear Packet must have Packet Data."));
+            }
+            (CH::Routing(_), Some(_)) => {
+                return Err(PacketBuildError(
+                    "Routing Packet cannot contain Packet Data.",
+                ));
+            }
+            (CH::Routing(_), None) => PacketData::new(vec![]),
+            (CH::Beacon(_), Some(_)) => {
+                return Err(PacketBuildError(
+                    "Beacon Packet cannot contain Packet Data.",
+                ));
+            }
+            (CH::Beacon(_), None) => PacketData::new(vec![]),
         };
         // build
         Ok(Packet {
             sender,
             coding_header,
-            reception_header: vec![],
+            reception_header,
             ack_header,
+            nack_header,
             data,
+            seal: self.seal,
         })
     }
 }
@@ -1,49 +1,154 @@
 use std::fmt::Display;
 use std::ops::Index;
 use std::ops::IndexMut;
+use std::ops::Range;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Default, Debug, Deserialize, Serialize)]
-pub struct PacketData(Vec<u8>);
+use super::xor;
+
+// NOTE: Packet data is passed around a lot (packet pool, retransmission queue,
+// channels), so it is stored in a reference counted buffer. Cloning a PacketData
+// or taking a slice of it never copies the underlying bytes. They are only
+// copied on write, if the buffer is shared with someone else.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(from = "Vec<u8>", into = "Vec<u8>")]
+pub struct PacketData {
+    buffer: Arc<Vec<u8>>,
+    range: Range<usize>,
+}
 
 impl PacketData {
     pub fn new(raw: Vec<u8>) -> Self {
-        Self(raw)
+        Self {
+            range: 0..raw.len(),
+            buffer: Arc::new(raw),
+        }
     }
 
-    pub fn right_pad(mut self, len: usize, symbol: u8) -> Self {
-        let Some(len_to_pad) = len.checked_sub(self.len()) else {
+    // NOTE: Returns a view into the given range of this PacketData, which shares
+    // the same buffer.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "Slice {:?} is out of bounds for PacketData of length {}",
+            range,
+            self.len()
+        );
+        Self {
+            buffer: self.buffer.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+
+    pub fn right_pad(self, len: usize, symbol: u8) -> Self {
+        if len <= self.len() {
             return self;
-        };
-        self.0.extend(vec![symbol; len_to_pad]);
+        }
+        let mut raw = self.into_vec(len);
+        raw.resize(len, symbol);
+        Self::new(raw)
+    }
+
+    pub fn truncate(mut self, len: usize) -> Self {
+        self.range.end = self.range.start + usize::min(len, self.len());
         self
     }
 
     pub fn xor(mut self, rhs: &PacketData) -> Self {
-        self = self.right_pad(rhs.0.len(), 0);
+        self.xor_in_place(rhs);
+        self
+    }
 
-        for i in 0..usize::min(rhs.0.len(), self.0.len()) {
-            self[i] = self[i] ^ rhs[i];
+    // NOTE: Zero pads self, if rhs is longer.
+    pub fn xor_in_place(&mut self, rhs: &PacketData) {
+        if rhs.len() > self.len() {
+            *self = std::mem::take(self).right_pad(rhs.len(), 0);
         }
+        xor::xor_into(self.make_mut(), rhs.as_slice());
+    }
 
-        self
+    // NOTE: Computes the XOR of all inputs in a single new buffer.
+    pub fn xor_many<'a>(inputs: impl IntoIterator<Item = &'a PacketData>) -> Self {
+        Self::new(xor::xor_many(inputs.into_iter().map(|d| d.as_slice())))
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.range.len()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
+
+    // NOTE: Reuses the buffer if we are the only one referencing it,
+    // otherwise the bytes are copied into a new buffer with the given capacity.
+    fn into_vec(self, capacity: usize) -> Vec<u8> {
+        let Range { start, end } = self.range;
+        match Arc::try_unwrap(self.buffer) {
+            Ok(mut raw) if start == 0 => {
+                raw.truncate(end);
+                raw
+            }
+            Ok(raw) => Self::copy_with_capacity(&raw[start..end], capacity),
+            Err(shared) => Self::copy_with_capacity(&shared[start..end], capacity),
+        }
+    }
+
+    fn copy_with_capacity(bytes: &[u8], capacity: usize) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(usize::max(capacity, bytes.len()));
+        raw.extend_from_slice(bytes);
+        raw
+    }
+
+    fn make_mut(&mut self) -> &mut [u8] {
+        if Arc::get_mut(&mut self.buffer).is_none() {
+            *self = Self::new(self.as_slice().to_vec());
+        }
+        let range = self.range.clone();
+        &mut Arc::get_mut(&mut self.buffer).unwrap()[range]
+    }
+
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.buffer) > 1
+    }
+}
+
+impl From<Vec<u8>> for PacketData {
+    fn from(raw: Vec<u8>) -> Self {
+        Self::new(raw)
+    }
+}
+
+impl From<PacketData> for Vec<u8> {
+    fn from(data: PacketData) -> Self {
+        let len = data.len();
+        data.into_vec(len)
+    }
+}
+
+impl PartialEq for PacketData {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl std::fmt::Debug for PacketData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PacketData").field(&self.as_slice()).finish()
     }
 }
 
 impl Index<usize> for PacketData {
     type Output = u8;
     fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+        &self.as_slice()[index]
     }
 }
 impl IndexMut<usize> for PacketData {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+        &mut self.make_mut()[index]
     }
 }
 
@@ -54,7 +159,7 @@ impl Display for PacketData {
         // NOTE: non-graphic ASCII characters are replaced by a dot
         // (as it is commonly done in hex editors)
         let human_readable: String = self
-            .0
+            .as_slice()
             .iter()
             .take(MAXLEN)
             .map(|b| {
@@ -67,7 +172,7 @@ impl Display for PacketData {
             })
             .collect();
 
-        if self.0.len() <= MAXLEN {
+        if self.len() <= MAXLEN {
             write!(f, "{}", human_readable)
         } else {
             write!(f, "{}<snip>", human_readable)
@@ -89,8 +194,8 @@ mod test {
         let res1 = input0.clone().xor(&input1);
         let exp1 = PacketData::new(vec![0xFF; 4]);
 
-        assert_eq!(exp0.0, res0.0);
-        assert_eq!(exp1.0, res1.0);
+        assert_eq!(exp0, res0);
+        assert_eq!(exp1, res1);
     }
 
     #[test]
@@ -106,8 +211,29 @@ mod test {
         let res1 = input0.clone().xor(&input2);
         let exp1 = PacketData::new(vec![0xFF; 8]);
 
-        assert_eq!(exp0.0, res0.0);
-        assert_eq!(exp1.0, res1.0);
+        assert_eq!(exp0, res0);
+        assert_eq!(exp1, res1);
+    }
+
+    #[test]
+    fn test_xor_many() {
+        let input0 = PacketData::new(vec![0xFF; 8]);
+        let input1 = PacketData::new(vec![0x0F; 4]);
+        let input2 = input0.slice(0..2);
+
+        let res = PacketData::xor_many([&input0, &input1, &input2]);
+        let exp = input0.clone().xor(&input1).xor(&input2);
+        assert_eq!(exp, res);
+        assert_eq!(&[0x0F, 0x0F, 0xF0, 0xF0], &res.as_slice()[..4]);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let input = PacketData::new(vec![0xFF, 0xFF, 0, 0]);
+        let res0 = input.clone().truncate(2);
+        let res1 = input.clone().truncate(8);
+        assert_eq!(&[0xFF, 0xFF], res0.as_slice());
+        assert_eq!(input, res1);
     }
 
     #[test]
@@ -119,8 +245,34 @@ mod test {
         let exp0 = PacketData::new(vec![0xFF, 0xFF, 0xFF]);
         let exp1 = PacketData::new(vec![0xFF, 0xFF, 0xFF]);
         let exp2 = PacketData::new(vec![0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0]);
-        assert_eq!(exp0.0, res0.0);
-        assert_eq!(exp1.0, res1.0);
-        assert_eq!(exp2.0, res2.0);
+        assert_eq!(exp0, res0);
+        assert_eq!(exp1, res1);
+        assert_eq!(exp2, res2);
+    }
+
+    #[test]
+    fn test_slices_share_buffer() {
+        let input = PacketData::new(vec![0, 1, 2, 3, 4, 5]);
+        let slice = input.slice(2..5);
+        assert_eq!(&[2, 3, 4], slice.as_slice());
+        assert!(input.is_shared() && slice.is_shared());
+
+        let inner = slice.slice(1..3);
+        assert_eq!(&[3, 4], inner.as_slice());
+        assert_eq!(4, inner[1]);
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let input = PacketData::new(vec![0xFF; 4]);
+        let mut copy = input.clone();
+        copy[0] = 0x00;
+        assert_eq!(0xFF, input[0]);
+        assert_eq!(0x00, copy[0]);
+        assert!(!input.is_shared() && !copy.is_shared());
+
+        let res = input.slice(1..3).xor(&PacketData::new(vec![0xFF]));
+        assert_eq!(&[0x00, 0xFF], res.as_slice());
+        assert_eq!(&[0xFF; 4], input.as_slice());
     }
 }
@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+// NOTE: Packet ids are 16 bit sequence numbers, that wrap around back to 0.
+// To still be able to tell which of two ids is newer, they are compared using
+// serial number arithmetic as described in RFC 1982: a is newer than b, if it
+// is less than half the id space ahead of b. Ids that are exactly half the id
+// space apart can not be ordered.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Default, Clone, Copy)]
+pub struct PacketID(u16);
+
+// NOTE: Ids that are more than MAX_ID_AGE behind the newest id we know of from
+// the same source are considered stale, because the source will reuse them
+// after its ids wrap around.
+pub const MAX_ID_AGE: u16 = 1 << 10;
+
+impl PacketID {
+    const HALF_RANGE: u16 = 1 << 15;
+
+    pub const fn new(value: u16) -> Self {
+        Self(value)
+    }
+
+    pub fn value(self) -> u16 {
+        self.0
+    }
+
+    pub fn next(self) -> Self {
+        self.wrapping_add(1)
+    }
+
+    pub fn wrapping_add(self, n: u16) -> Self {
+        Self(self.0.wrapping_add(n))
+    }
+
+    pub fn wrapping_sub(self, n: u16) -> Self {
+        Self(self.0.wrapping_sub(n))
+    }
+
+    // NOTE: Returns how many ids self is ahead of other, counting forward
+    // from other and wrapping around if needed.
+    pub fn offset_from(self, other: PacketID) -> u16 {
+        self.0.wrapping_sub(other.0)
+    }
+
+    // NOTE: Returns true, if self is one of the window ids up to and
+    // including newest.
+    pub fn is_within(self, newest: PacketID, window: u16) -> bool {
+        newest.offset_from(self) < window
+    }
+}
+
+impl PartialOrd for PacketID {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.offset_from(*other) {
+            0 => Some(Ordering::Equal),
+            Self::HALF_RANGE => None,
+            offset if offset < Self::HALF_RANGE => Some(Ordering::Greater),
+            _ => Some(Ordering::Less),
+        }
+    }
+}
+
+impl From<u16> for PacketID {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for PacketID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ordering() {
+        let id = PacketID::new(10);
+        assert!(PacketID::new(11) > id);
+        assert!(PacketID::new(9) < id);
+        assert!(PacketID::new(10 + 0x7FFF) > id);
+        assert!(PacketID::new(10 + 0x8001) < id);
+        assert_eq!(PacketID::new(10 + 0x8000).partial_cmp(&id), None);
+    }
+
+    #[test]
+    fn test_wrap_boundary() {
+        let last = PacketID::new(u16::MAX);
+        let first = last.next();
+        assert_eq!(first, PacketID::new(0));
+        assert!(first > last);
+        assert!(last < first);
+        assert!(PacketID::new(100) > PacketID::new(65500));
+        assert_eq!(first.offset_from(last), 1);
+        assert_eq!(PacketID::new(3).wrapping_sub(5), PacketID::new(65534));
+    }
+
+    #[test]
+    fn test_window() {
+        let newest = PacketID::new(2);
+        assert!(newest.is_within(newest, 8));
+        assert!(PacketID::new(65531).is_within(newest, 8));
+        assert!(!PacketID::new(65530).is_within(newest, 8));
+        assert!(!PacketID::new(3).is_within(newest, 8));
+    }
+}
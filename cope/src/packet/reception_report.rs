@@ -0,0 +1,50 @@
+use bitvec::prelude as bv;
+use cope_config::types::node_id::NodeID;
+use serde::{Deserialize, Serialize};
+
+use super::PacketID;
+
+// NOTE: The COPE paper uses an 8 bit map to describe the packets
+// received before last_id, so we do the same.
+pub const REPORT_WINDOW: usize = 8;
+
+// NOTE: preceding_ids[i] is set, if the packet with id last_id - (i + 1)
+// from source was received as well.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ReceptionReport {
+    source: NodeID,
+    last_id: PacketID,
+    preceding_ids: bv::BitVec,
+}
+
+impl ReceptionReport {
+    pub fn new(source: NodeID, last_id: PacketID, preceding_ids: bv::BitVec) -> Self {
+        Self {
+            source,
+            last_id,
+            preceding_ids,
+        }
+    }
+
+    pub fn source(&self) -> NodeID {
+        self.source
+    }
+
+    pub fn last_id(&self) -> PacketID {
+        self.last_id
+    }
+
+    pub fn preceding_ids(&self) -> &bv::BitSlice {
+        self.preceding_ids.as_bitslice()
+    }
+
+    // NOTE: Returns all packet ids from source covered by this report,
+    // starting with last_id.
+    pub fn ids(&self) -> Vec<PacketID> {
+        let preceding = self
+            .preceding_ids
+            .iter_ones()
+            .map(|i| self.last_id.wrapping_sub(i as u16 + 1));
+        std::iter::once(self.last_id).chain(preceding).collect()
+    }
+}
@@ -0,0 +1,931 @@
+// NOTE: Hand-specified binary layout of a Packet on the air. It is loosely
+// based on the compressed header from the COPE paper. All multi-byte fields are
+// stored in big endian.
+//
+// magic:u8 version:u8 flags:u8 sender:u8 coding_type:u8 coding_header
+// report_count:u8 report* ack_count:u8 ack* nack_count:u8 nack* seal? data
+//
+// coding_header:
+//   Native:  source:u8 id:u16 nexthop:u8 destination:u8 (length is implied by the data)
+//   Encoded: info_count:u8 (source:u8 id:u16 nexthop:u8 destination:u8 len:u16)*
+//   Control: receiver:u8
+//   Linear:  source:u8 nexthop:u8 generation:u16 coefficient_count:u8 coefficient:u8*
+//   Routing: origin:u8 seq:u16 link_count:u8 (neighbor:u8 delivery:u8)*
+//   Beacon:  neighbor_count:u8 neighbor:u8*
+// report: source:u8 last_id:u16 preceding_ids:u8
+// ack: source:u8 info_count:u8 (source:u8 id:u16 nexthop:u8 destination:u8 len:u16)*
+// nack: source:u8 id:u16 nexthop:u8 destination:u8 len:u16
+// seal: epoch:u32 counter:u32 tag:[u8; 16] (only if FLAG_SEALED is set)
+//
+// The data takes up all bytes following the headers.
+
+use std::fmt::Display;
+use std::ops::Range;
+
+use bitvec::{field::BitField, prelude as bv};
+use cope_config::types::node_id::NodeID;
+
+use crate::config::CONFIG;
+
+use super::{
+    reception_report::REPORT_WINDOW, Ack, CodingHeader, CodingInfo, LinearHeader, LinkQuality,
+    LinkStateAdvert, Packet, PacketBuilder, PacketData, PacketID, ReceptionReport, Seal,
+};
+
+pub const MAGIC: u8 = 0xC0;
+pub const VERSION: u8 = 6;
+
+pub const FLAG_SEALED: u8 = 1 << 0;
+
+const NATIVE_TYPE: u8 = 0;
+const ENCODED_TYPE: u8 = 1;
+const CONTROL_TYPE: u8 = 2;
+const LINEAR_TYPE: u8 = 3;
+const ROUTING_TYPE: u8 = 4;
+const BEACON_TYPE: u8 = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum PacketEncodeError {
+    NonAsciiNodeID(NodeID),
+    TooManyEntries(&'static str, usize),
+}
+
+impl Display for PacketEncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonAsciiNodeID(id) => write!(f, "Node ID {} is not an ASCII character", id),
+            Self::TooManyEntries(field, count) => {
+                write!(f, "{} has {} entries, but at most 255 fit", field, count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PacketEncodeError {}
+
+#[derive(Debug, PartialEq)]
+pub enum PacketDecodeError {
+    UnexpectedEnd,
+    PacketTooLarge(usize),
+    InvalidMagic(u8),
+    UnsupportedVersion(u8),
+    UnknownFlags(u8),
+    InvalidCodingType(u8),
+    InvalidNodeID(u8),
+    TooManyCodingInfos(usize),
+    TooManyReports(usize),
+    TooManyAcks(usize),
+    TooManyNacks(usize),
+    TooManyLinks(usize),
+    TooManyNeighbors(usize),
+    EmptyCodingHeader,
+    InvalidGenerationSize(usize),
+    DuplicateCodingInfo(CodingInfo),
+    ControlWithData(usize),
+    InvalidPacket(&'static str),
+}
+
+impl Display for PacketDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "packet ended unexpectedly"),
+            Self::PacketTooLarge(size) => write!(f, "packet is too large ({} bytes)", size),
+            Self::InvalidMagic(m) => write!(f, "invalid magic byte {:#04x}", m),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported wire format version {}", v),
+            Self::UnknownFlags(flags) => write!(f, "unknown flags {:#010b}", flags),
+            Self::InvalidCodingType(t) => write!(f, "invalid coding header type {}", t),
+            Self::InvalidNodeID(b) => write!(f, "invalid node id {:#04x}", b),
+            Self::TooManyCodingInfos(c) => write!(f, "too many coding infos ({})", c),
+            Self::TooManyReports(c) => write!(f, "too many reception reports ({})", c),
+            Self::TooManyAcks(c) => write!(f, "too many acked packets ({})", c),
+            Self::TooManyNacks(c) => write!(f, "too many nacked packets ({})", c),
+            Self::TooManyLinks(c) => write!(f, "too many links in advert ({})", c),
+            Self::TooManyNeighbors(c) => write!(f, "too many neighbors in beacon ({})", c),
+            Self::EmptyCodingHeader => write!(f, "encoded packet has no coding infos"),
+            Self::InvalidGenerationSize(s) => write!(f, "invalid generation size {}", s),
+            Self::DuplicateCodingInfo(info) => write!(f, "coding info {} appears twice", info),
+            Self::ControlWithData(len) => {
+                write!(f, "control packet contains {} bytes of data", len)
+            }
+            Self::InvalidPacket(reason) => write!(f, "invalid packet: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PacketDecodeError {}
+
+// NOTE: Packets can be received from anyone in radio range, so we must not trust
+// any sizes or counts they contain. Everything above these limits is rejected
+// before it is allocated.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeLimits {
+    pub max_packet_size: usize,
+    pub max_coding_infos: usize,
+    pub max_reports: usize,
+    // NOTE: Limits the amount of acked packets summed over all ACKs
+    pub max_acked_infos: usize,
+    pub max_nacked_infos: usize,
+    pub max_generation_size: usize,
+    pub max_links: usize,
+    pub max_neighbors: usize,
+}
+
+impl DecodeLimits {
+    pub fn from_config() -> Self {
+        let node_count = CONFIG.nodes.len();
+        Self {
+            max_packet_size: CONFIG.max_packet_size,
+            // NOTE: A coded packet contains at most one packet per nexthop and
+            // nodes only report on one last_id per source.
+            max_coding_infos: node_count,
+            max_reports: node_count,
+            // NOTE: ACKs are collected until the next packet is sent, so we
+            // allow the ACKs for a full packet pool per node.
+            max_acked_infos: node_count * CONFIG.packet_pool_size,
+            // NOTE: Leaves only nack packets they keep, see coding::PendingStore
+            max_nacked_infos: CONFIG.packet_pool_size,
+            max_generation_size: CONFIG.generation_size,
+            // NOTE: Adverts contain at most one link per other node
+            max_links: node_count,
+            max_neighbors: node_count,
+        }
+    }
+}
+
+struct WireWriter {
+    bytes: Vec<u8>,
+}
+
+impl WireWriter {
+    fn new() -> Self {
+        Self { bytes: vec![] }
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_node_id(&mut self, id: NodeID) -> Result<(), PacketEncodeError> {
+        if !id.is_ascii() {
+            return Err(PacketEncodeError::NonAsciiNodeID(id));
+        }
+        self.write_u8(id.unwrap() as u8);
+        Ok(())
+    }
+
+    fn write_count(&mut self, field: &'static str, count: usize) -> Result<(), PacketEncodeError> {
+        let Ok(count) = u8::try_from(count) else {
+            return Err(PacketEncodeError::TooManyEntries(field, count));
+        };
+        self.write_u8(count);
+        Ok(())
+    }
+
+    fn write_info(&mut self, info: &CodingInfo) -> Result<(), PacketEncodeError> {
+        self.write_node_id(info.source)?;
+        self.write_u16(info.id.value());
+        self.write_node_id(info.nexthop)?;
+        self.write_node_id(info.destination)?;
+        self.write_u16(info.len);
+        Ok(())
+    }
+}
+
+struct WireReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, PacketDecodeError> {
+        let Some(&value) = self.bytes.get(self.pos) else {
+            return Err(PacketDecodeError::UnexpectedEnd);
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, PacketDecodeError> {
+        let Some(value) = self.bytes.get(self.pos..self.pos + 2) else {
+            return Err(PacketDecodeError::UnexpectedEnd);
+        };
+        self.pos += 2;
+        Ok(u16::from_be_bytes([value[0], value[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, PacketDecodeError> {
+        let bytes = self.read_array::<4>()?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], PacketDecodeError> {
+        let Some(value) = self.bytes.get(self.pos..self.pos + N) else {
+            return Err(PacketDecodeError::UnexpectedEnd);
+        };
+        self.pos += N;
+        Ok(value.try_into().unwrap())
+    }
+
+    fn read_node_id(&mut self) -> Result<NodeID, PacketDecodeError> {
+        let byte = self.read_u8()?;
+        // NOTE: We only ever write ASCII node ids, see WireWriter::write_node_id
+        if !byte.is_ascii() {
+            return Err(PacketDecodeError::InvalidNodeID(byte));
+        }
+        NodeID::try_from(byte as char).map_err(|_| PacketDecodeError::InvalidNodeID(byte))
+    }
+
+    fn read_info(&mut self) -> Result<CodingInfo, PacketDecodeError> {
+        Ok(CodingInfo {
+            source: self.read_node_id()?,
+            id: PacketID::new(self.read_u16()?),
+            nexthop: self.read_node_id()?,
+            destination: self.read_node_id()?,
+            len: self.read_u16()?,
+        })
+    }
+
+    fn read_infos(&mut self, max_count: usize) -> Result<Vec<CodingInfo>, PacketDecodeError> {
+        let count = self.read_u8()? as usize;
+        if count > max_count {
+            return Err(PacketDecodeError::TooManyCodingInfos(count));
+        }
+        (0..count).map(|_| self.read_info()).collect()
+    }
+
+    fn remaining(&self) -> Range<usize> {
+        self.pos..self.bytes.len()
+    }
+}
+
+pub fn encode(packet: &Packet) -> Result<Vec<u8>, PacketEncodeError> {
+    let mut bytes = encode_header(packet)?;
+    bytes.extend_from_slice(packet.data().as_slice());
+    Ok(bytes)
+}
+
+// NOTE: Encodes everything except the data. This is also used as
+// the associated data when sealing packets.
+pub fn encode_header(packet: &Packet) -> Result<Vec<u8>, PacketEncodeError> {
+    let mut writer = WireWriter::new();
+    writer.write_u8(MAGIC);
+    writer.write_u8(VERSION);
+    match packet.seal() {
+        Some(_) => writer.write_u8(FLAG_SEALED),
+        None => writer.write_u8(0),
+    }
+    writer.write_node_id(packet.sender())?;
+
+    match packet.coding_header() {
+        CodingHeader::Native(info) => {
+            writer.write_u8(NATIVE_TYPE);
+            writer.write_node_id(info.source)?;
+            writer.write_u16(info.id.value());
+            writer.write_node_id(info.nexthop)?;
+            writer.write_node_id(info.destination)?;
+        }
+        CodingHeader::Encoded(infos) => {
+            writer.write_u8(ENCODED_TYPE);
+            writer.write_count("Coding header", infos.len())?;
+            for info in infos {
+                writer.write_info(info)?;
+            }
+        }
+        CodingHeader::Control(receiver) => {
+            writer.write_u8(CONTROL_TYPE);
+            writer.write_node_id(*receiver)?;
+        }
+        CodingHeader::Linear(header) => {
+            writer.write_u8(LINEAR_TYPE);
+            writer.write_node_id(header.source)?;
+            writer.write_node_id(header.nexthop)?;
+            writer.write_u16(header.generation.value());
+            writer.write_count("Coefficient vector", header.coefficients.len())?;
+            writer.bytes.extend_from_slice(&header.coefficients);
+        }
+        CodingHeader::Routing(advert) => {
+            writer.write_u8(ROUTING_TYPE);
+            writer.write_node_id(advert.origin)?;
+            writer.write_u16(advert.seq.value());
+            writer.write_count("Advert", advert.links.len())?;
+            for link in &advert.links {
+                writer.write_node_id(link.neighbor)?;
+                writer.write_u8(link.delivery);
+            }
+        }
+        CodingHeader::Beacon(neighbors) => {
+            writer.write_u8(BEACON_TYPE);
+            writer.write_count("Beacon", neighbors.len())?;
+            for &neighbor in neighbors {
+                writer.write_node_id(neighbor)?;
+            }
+        }
+    }
+
+    let reports = packet.reception_header();
+    writer.write_count("Reception header", reports.len())?;
+    for report in reports {
+        writer.write_node_id(report.source())?;
+        writer.write_u16(report.last_id().value());
+        let window = REPORT_WINDOW.min(report.preceding_ids().len());
+        let preceding_ids = &report.preceding_ids()[..window];
+        match preceding_ids.is_empty() {
+            true => writer.write_u8(0),
+            false => writer.write_u8(preceding_ids.load_le::<u8>()),
+        }
+    }
+
+    let acks = packet.ack_header();
+    writer.write_count("Ack header", acks.len())?;
+    for ack in acks {
+        writer.write_node_id(ack.source())?;
+        writer.write_count("Ack", ack.packets().len())?;
+        for info in ack.packets() {
+            writer.write_info(info)?;
+        }
+    }
+
+    let nacks = packet.nack_header();
+    writer.write_count("Nack header", nacks.len())?;
+    for info in nacks {
+        writer.write_info(info)?;
+    }
+
+    if let Some(seal) = packet.seal() {
+        writer.write_u32(seal.epoch);
+        writer.write_u32(seal.counter);
+        writer.bytes.extend_from_slice(&seal.tag);
+    }
+
+    Ok(writer.bytes)
+}
+
+pub fn decode(bytes: &[u8], limits: &DecodeLimits) -> Result<Packet, PacketDecodeError> {
+    decode_with(bytes, limits, |range| {
+        PacketData::new(bytes[range].to_vec())
+    })
+}
+
+// NOTE: The data of the decoded packet is a slice of buffer,
+// so the payload is not copied.
+pub fn decode_shared(
+    buffer: &PacketData,
+    limits: &DecodeLimits,
+) -> Result<Packet, PacketDecodeError> {
+    decode_with(buffer.as_slice(), limits, |range| buffer.slice(range))
+}
+
+fn decode_with(
+    bytes: &[u8],
+    limits: &DecodeLimits,
+    data: impl FnOnce(Range<usize>) -> PacketData,
+) -> Result<Packet, PacketDecodeError> {
+    if bytes.len() > limits.max_packet_size {
+        return Err(PacketDecodeError::PacketTooLarge(bytes.len()));
+    }
+
+    let mut reader = WireReader::new(bytes);
+
+    let magic = reader.read_u8()?;
+    if magic != MAGIC {
+        return Err(PacketDecodeError::InvalidMagic(magic));
+    }
+
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        return Err(PacketDecodeError::UnsupportedVersion(version));
+    }
+
+    let flags = reader.read_u8()?;
+    if flags & !FLAG_SEALED != 0 {
+        return Err(PacketDecodeError::UnknownFlags(flags));
+    }
+
+    let mut builder = PacketBuilder::new().sender(reader.read_node_id()?);
+
+    let coding_type = reader.read_u8()?;
+    builder = match coding_type {
+        NATIVE_TYPE => builder.native_header(CodingInfo {
+            source: reader.read_node_id()?,
+            id: PacketID::new(reader.read_u16()?),
+            nexthop: reader.read_node_id()?,
+            destination: reader.read_node_id()?,
+            // NOTE: Filled in by PacketBuilder::build()
+            len: 0,
+        }),
+        ENCODED_TYPE => {
+            let infos = reader.read_infos(limits.max_coding_infos)?;
+            validate_encoded_header(&infos)?;
+            builder.encoded_header(infos)
+        }
+        CONTROL_TYPE => builder.control_header(reader.read_node_id()?),
+        LINEAR_TYPE => {
+            let source = reader.read_node_id()?;
+            let nexthop = reader.read_node_id()?;
+            let generation = PacketID::new(reader.read_u16()?);
+            let size = reader.read_u8()? as usize;
+            if size == 0 || size > limits.max_generation_size {
+                return Err(PacketDecodeError::InvalidGenerationSize(size));
+            }
+            let coefficients = (0..size)
+                .map(|_| reader.read_u8())
+                .collect::<Result<Vec<_>, _>>()?;
+            builder.linear_header(LinearHeader {
+                source,
+                nexthop,
+                generation,
+                coefficients,
+            })
+        }
+        ROUTING_TYPE => {
+            let origin = reader.read_node_id()?;
+            let seq = PacketID::new(reader.read_u16()?);
+            let count = reader.read_u8()? as usize;
+            if count > limits.max_links {
+                return Err(PacketDecodeError::TooManyLinks(count));
+            }
+            let links = (0..count)
+                .map(|_| {
+                    Ok(LinkQuality {
+                        neighbor: reader.read_node_id()?,
+                        delivery: reader.read_u8()?,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            builder.routing_header(LinkStateAdvert { origin, seq, links })
+        }
+        BEACON_TYPE => {
+            let count = reader.read_u8()? as usize;
+            if count > limits.max_neighbors {
+                return Err(PacketDecodeError::TooManyNeighbors(count));
+            }
+            let neighbors = (0..count)
+                .map(|_| reader.read_node_id())
+                .collect::<Result<Vec<_>, _>>()?;
+            builder.beacon_header(neighbors)
+        }
+        t => return Err(PacketDecodeError::InvalidCodingType(t)),
+    };
+
+    let report_count = reader.read_u8()? as usize;
+    if report_count > limits.max_reports {
+        return Err(PacketDecodeError::TooManyReports(report_count));
+    }
+    let mut reports = Vec::with_capacity(report_count);
+    for _ in 0..report_count {
+        let source = reader.read_node_id()?;
+        let last_id = PacketID::new(reader.read_u16()?);
+        let mut preceding_ids = bv::bitvec![0; REPORT_WINDOW];
+        preceding_ids.store_le(reader.read_u8()?);
+        reports.push(ReceptionReport::new(source, last_id, preceding_ids));
+    }
+
+    let ack_count = reader.read_u8()? as usize;
+    let mut acked_infos_left = limits.max_acked_infos;
+    let mut acks = Vec::with_capacity(ack_count.min(acked_infos_left));
+    for _ in 0..ack_count {
+        let source = reader.read_node_id()?;
+        let info_count = reader.read_u8()? as usize;
+        let Some(left) = acked_infos_left.checked_sub(info_count) else {
+            return Err(PacketDecodeError::TooManyAcks(
+                limits.max_acked_infos - acked_infos_left + info_count,
+            ));
+        };
+        acked_infos_left = left;
+        let packets = (0..info_count)
+            .map(|_| reader.read_info())
+            .collect::<Result<Vec<_>, _>>()?;
+        acks.push(Ack { source, packets });
+    }
+
+    let nack_count = reader.read_u8()? as usize;
+    if nack_count > limits.max_nacked_infos {
+        return Err(PacketDecodeError::TooManyNacks(nack_count));
+    }
+    let nacks = (0..nack_count)
+        .map(|_| reader.read_info())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    builder = builder
+        .reception_header(reports)
+        .ack_header(acks)
+        .nack_header(nacks);
+
+    if flags & FLAG_SEALED != 0 {
+        builder = builder.seal(Seal {
+            epoch: reader.read_u32()?,
+            counter: reader.read_u32()?,
+            tag: reader.read_array()?,
+        });
+    }
+
+    let range = reader.remaining();
+    // NOTE: Like control packets, adverts and beacons don't carry any data
+    if [CONTROL_TYPE, ROUTING_TYPE, BEACON_TYPE].contains(&coding_type) {
+        if !range.is_empty() {
+            return Err(PacketDecodeError::ControlWithData(range.len()));
+        }
+    } else {
+        builder = builder.data(data(range));
+    }
+
+    builder
+        .build()
+        .map_err(|e| PacketDecodeError::InvalidPacket(e.reason()))
+}
+
+fn validate_encoded_header(infos: &[CodingInfo]) -> Result<(), PacketDecodeError> {
+    if infos.is_empty() {
+        return Err(PacketDecodeError::EmptyCodingHeader);
+    }
+
+    for (i, info) in infos.iter().enumerate() {
+        let is_duplicate = infos[..i]
+            .iter()
+            .any(|other| other.source == info.source && other.id == info.id);
+        if is_duplicate {
+            return Err(PacketDecodeError::DuplicateCodingInfo(info.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMITS: DecodeLimits = DecodeLimits {
+        max_packet_size: 2048,
+        max_coding_infos: 4,
+        max_reports: 4,
+        max_acked_infos: 8,
+        max_nacked_infos: 2,
+        max_generation_size: 4,
+        max_links: 4,
+        max_neighbors: 4,
+    };
+
+    fn assert_round_trip(packet: &Packet) -> Vec<u8> {
+        let bytes = encode(packet).unwrap();
+        let decoded = decode(&bytes, &LIMITS).unwrap();
+        assert_eq!(*packet, decoded);
+        bytes
+    }
+
+    fn info(source: char, id: u16, nexthop: char, len: u16) -> CodingInfo {
+        CodingInfo {
+            source: NodeID::new(source),
+            id: PacketID::new(id),
+            nexthop: NodeID::new(nexthop),
+            destination: NodeID::new(nexthop),
+            len,
+        }
+    }
+
+    #[test]
+    fn test_native_round_trip() {
+        let mut preceding_ids = bv::bitvec![0; REPORT_WINDOW];
+        preceding_ids.set(0, true);
+        preceding_ids.set(5, true);
+
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(info('A', 513, 'C', 0))
+            .reception_header(vec![ReceptionReport::new(
+                NodeID::new('D'),
+                PacketID::new(7),
+                preceding_ids,
+            )])
+            .ack_header(vec![Ack {
+                source: NodeID::new('A'),
+                packets: vec![info('C', 1, 'A', 3), info('C', 2, 'A', 1400)],
+            }])
+            .data_raw(vec![1, 2, 3, 4])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(&bytes[..5], &[MAGIC, VERSION, 0, b'A', NATIVE_TYPE]);
+    }
+
+    #[test]
+    fn test_encoded_round_trip() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .encoded_header(vec![info('A', 1, 'C', 10), info('C', 65535, 'A', 100)])
+            .ack_header(vec![])
+            .data_raw(vec![0xFF; 100])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        // NOTE: 5 bytes preamble, 1 + 2 * 7 bytes coding header,
+        // 1 byte each for the empty reception, ack and nack headers
+        assert_eq!(bytes.len(), 5 + 15 + 3 + 100);
+    }
+
+    // NOTE: The nexthop and the destination are encoded on their own, so a
+    // forwarded native must not end up with the nexthop as its destination.
+    #[test]
+    fn test_destination_round_trip() {
+        let forwarded = CodingInfo {
+            nexthop: NodeID::new('C'),
+            ..info('A', 7, 'D', 10)
+        };
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .encoded_header(vec![forwarded])
+            .ack_header(vec![])
+            .data_raw(vec![0x42; 10])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(&bytes[6..11], &[b'A', 0, 7, b'C', b'D']);
+        let decoded = decode(&bytes, &LIMITS).unwrap();
+        let CodingHeader::Encoded(infos) = decoded.coding_header() else {
+            panic!("Expected Encoded Packet");
+        };
+        assert_eq!(infos[0].nexthop, NodeID::new('C'));
+        assert_eq!(infos[0].destination, NodeID::new('D'));
+    }
+
+    #[test]
+    fn test_shared_decode() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(info('A', 1, 'C', 0))
+            .ack_header(vec![])
+            .data_raw(vec![0xAB; 64])
+            .build()
+            .unwrap();
+
+        let buffer = PacketData::new(encode(&packet).unwrap());
+        let decoded = decode_shared(&buffer, &LIMITS).unwrap();
+        assert_eq!(packet, decoded);
+        assert!(buffer.is_shared());
+        assert!(decoded.data().is_shared());
+    }
+
+    #[test]
+    fn test_linear_round_trip() {
+        let header = LinearHeader {
+            source: NodeID::new('A'),
+            nexthop: NodeID::new('C'),
+            generation: PacketID::new(300),
+            coefficients: vec![1, 0, 255],
+        };
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .linear_header(header.clone())
+            .ack_header(vec![])
+            .data_raw(vec![0x11; 10])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(&bytes[5..11], &[b'A', b'C', 1, 44, 3, 1]);
+
+        let mut bytes = vec![MAGIC, VERSION, 0, b'B', LINEAR_TYPE, b'A', b'C', 0, 1];
+        bytes.push(5);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::InvalidGenerationSize(5))
+        );
+        bytes[9] = 0;
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::InvalidGenerationSize(0))
+        );
+    }
+
+    #[test]
+    fn test_sealed_round_trip() {
+        let seal = Seal {
+            epoch: 0xDEADBEEF,
+            counter: 42,
+            tag: [0x5A; 16],
+        };
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .control_header(NodeID::new('A'))
+            .ack_header(vec![])
+            .seal(seal)
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(bytes[2], FLAG_SEALED);
+        assert_eq!(bytes.len(), 5 + 1 + 3 + 24);
+        assert_eq!(&bytes[bytes.len() - 16..], &[0x5A; 16]);
+    }
+
+    #[test]
+    fn test_nack_round_trip() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('C'))
+            .control_header(NodeID::new('B'))
+            .ack_header(vec![])
+            .nack_header(vec![info('A', 9, 'C', 20), info('E', 3, 'C', 1400)])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(&bytes[8..12], &[2, b'A', 0, 9]);
+
+        let mut bytes = vec![MAGIC, VERSION, 0, b'C', CONTROL_TYPE, b'B', 0, 0, 3];
+        bytes.extend([b'A', 0, 1, b'C', b'C', 0, 1]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::TooManyNacks(3))
+        );
+    }
+
+    #[test]
+    fn test_control_round_trip() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .control_header(NodeID::new('A'))
+            .ack_header(vec![Ack {
+                source: NodeID::new('B'),
+                packets: vec![info('A', 4, 'C', 60)],
+            }])
+            .build()
+            .unwrap();
+
+        assert_round_trip(&packet);
+    }
+
+    #[test]
+    fn test_routing_round_trip() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let packet = PacketBuilder::new()
+            .sender(b)
+            .routing_header(LinkStateAdvert {
+                origin: a,
+                seq: PacketID::new(300),
+                links: vec![LinkQuality::new(b, 1.0), LinkQuality::new(c, 0.5)],
+            })
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(
+            bytes[4..12],
+            [ROUTING_TYPE, b'A', 1, 44, 2, b'B', 255, b'C']
+        );
+
+        let mut bytes = vec![MAGIC, VERSION, 0, b'B', ROUTING_TYPE, b'A', 0, 1, 5];
+        bytes.extend([b'B', 255].repeat(5));
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::TooManyLinks(5))
+        );
+    }
+
+    #[test]
+    fn test_beacon_round_trip() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let packet = PacketBuilder::new()
+            .sender(b)
+            .beacon_header(vec![a, c])
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+
+        let bytes = assert_round_trip(&packet);
+        assert_eq!(bytes[4..8], [BEACON_TYPE, 2, b'A', b'C']);
+
+        let mut bytes = vec![MAGIC, VERSION, 0, b'B', BEACON_TYPE, 5];
+        bytes.extend([b'A'].repeat(5));
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::TooManyNeighbors(5))
+        );
+    }
+
+    #[test]
+    fn test_invalid_preamble() {
+        assert_eq!(decode(&[], &LIMITS), Err(PacketDecodeError::UnexpectedEnd));
+        assert_eq!(
+            decode(&[0x00, VERSION], &LIMITS),
+            Err(PacketDecodeError::InvalidMagic(0x00))
+        );
+        assert_eq!(
+            decode(&[MAGIC, VERSION + 1], &LIMITS),
+            Err(PacketDecodeError::UnsupportedVersion(VERSION + 1))
+        );
+        assert_eq!(
+            decode(&[MAGIC, VERSION, 0, b'A', 7], &LIMITS),
+            Err(PacketDecodeError::InvalidCodingType(7))
+        );
+        assert_eq!(
+            decode(&[MAGIC, VERSION, 0x80], &LIMITS),
+            Err(PacketDecodeError::UnknownFlags(0x80))
+        );
+        assert_eq!(
+            decode(&[MAGIC, VERSION, 0, b'a'], &LIMITS),
+            Err(PacketDecodeError::InvalidNodeID(b'a'))
+        );
+    }
+
+    #[test]
+    fn test_truncated_packet() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('B'))
+            .encoded_header(vec![info('A', 1, 'C', 10), info('C', 2, 'A', 100)])
+            .ack_header(vec![])
+            .data_raw(vec![0xFF; 100])
+            .build()
+            .unwrap();
+        let bytes = encode(&packet).unwrap();
+
+        assert_eq!(
+            decode(&bytes[..10], &LIMITS),
+            Err(PacketDecodeError::UnexpectedEnd)
+        );
+    }
+
+    fn encoded_header(infos: &[CodingInfo]) -> Vec<u8> {
+        let mut bytes = vec![MAGIC, VERSION, 0, b'B', ENCODED_TYPE, infos.len() as u8];
+        for info in infos {
+            bytes.extend([info.source.unwrap() as u8]);
+            bytes.extend(info.id.value().to_be_bytes());
+            bytes.extend([info.nexthop.unwrap() as u8]);
+            bytes.extend([info.destination.unwrap() as u8]);
+            bytes.extend(info.len.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(
+            decode(&vec![0; 2049], &LIMITS),
+            Err(PacketDecodeError::PacketTooLarge(2049))
+        );
+
+        let infos = vec![info('A', 1, 'C', 1); 5];
+        assert_eq!(
+            decode(&encoded_header(&infos), &LIMITS),
+            Err(PacketDecodeError::TooManyCodingInfos(5))
+        );
+
+        let mut bytes = encoded_header(&[info('A', 1, 'C', 1)]);
+        bytes.extend([5, b'A', 0, 1, 0]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::TooManyReports(5))
+        );
+
+        // NOTE: Two ACKs with 5 packets each exceed the 8 acked packets limit
+        let mut bytes = encoded_header(&[info('A', 1, 'C', 1)]);
+        bytes.extend([0, 2, b'A', 5]);
+        for id in 0..5 {
+            bytes.extend([b'C', 0, id, b'A', b'A', 0, 1]);
+        }
+        bytes.extend([b'C', 5]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::TooManyAcks(10))
+        );
+    }
+
+    #[test]
+    fn test_semantic_validation() {
+        let mut bytes = encoded_header(&[]);
+        bytes.extend([0, 0, 0xFF]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::EmptyCodingHeader)
+        );
+
+        let mut bytes = encoded_header(&[info('A', 1, 'C', 1), info('A', 1, 'B', 1)]);
+        bytes.extend([0, 0, 0xFF]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::DuplicateCodingInfo(info('A', 1, 'B', 1)))
+        );
+
+        let mut bytes = vec![MAGIC, VERSION, 0, b'B', CONTROL_TYPE, b'A', 0, 0, 0];
+        bytes.extend([0xFF, 0xFF]);
+        assert_eq!(
+            decode(&bytes, &LIMITS),
+            Err(PacketDecodeError::ControlWithData(2))
+        );
+    }
+}
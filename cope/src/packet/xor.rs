@@ -0,0 +1,124 @@
+// XOR kernel used for coding and decoding packet data.
+//
+// The bytes are processed in blocks of 4 u128 words, which LLVM lowers to SIMD
+// instructions where available (SSE2/AVX2 on x86_64, NEON on aarch64). On targets
+// without SIMD support, like the ESP32-S3, each u128 is split into native words,
+// which is still a lot faster than going byte by byte.
+//
+// simulator/benches/xor_throughput.rs compares it to the byte loop. On x86_64,
+// LLVM vectorizes the byte loop as well in release builds, so the kernel only
+// pulls ahead for payloads of a few hundred bytes and more.
+
+const WORD: usize = std::mem::size_of::<u128>();
+const BLOCK: usize = 4 * WORD;
+
+#[inline(always)]
+fn load(bytes: &[u8]) -> u128 {
+    u128::from_ne_bytes(bytes.try_into().unwrap())
+}
+
+#[inline(always)]
+fn xor_word(dst: &mut [u8], src: &[u8]) {
+    let value = load(dst) ^ load(src);
+    dst.copy_from_slice(&value.to_ne_bytes());
+}
+
+// NOTE: XORs src into the front of dst, the rest of dst is left untouched.
+pub fn xor_into(dst: &mut [u8], src: &[u8]) {
+    assert!(
+        src.len() <= dst.len(),
+        "Cannot XOR {} bytes into {} bytes",
+        src.len(),
+        dst.len()
+    );
+    let dst = &mut dst[..src.len()];
+
+    let mut dst_blocks = dst.chunks_exact_mut(BLOCK);
+    let mut src_blocks = src.chunks_exact(BLOCK);
+    for (d, s) in (&mut dst_blocks).zip(&mut src_blocks) {
+        for (dw, sw) in d.chunks_exact_mut(WORD).zip(s.chunks_exact(WORD)) {
+            xor_word(dw, sw);
+        }
+    }
+
+    let dst_rest = dst_blocks.into_remainder();
+    let src_rest = src_blocks.remainder();
+
+    let mut dst_words = dst_rest.chunks_exact_mut(WORD);
+    let mut src_words = src_rest.chunks_exact(WORD);
+    for (dw, sw) in (&mut dst_words).zip(&mut src_words) {
+        xor_word(dw, sw);
+    }
+
+    for (d, s) in dst_words
+        .into_remainder()
+        .iter_mut()
+        .zip(src_words.remainder())
+    {
+        *d ^= s;
+    }
+}
+
+// NOTE: Returns the XOR of all inputs, shorter inputs are treated as if they
+// were zero padded to the length of the longest one.
+pub fn xor_many<'a>(inputs: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let inputs: Vec<&[u8]> = inputs.into_iter().collect();
+    let len = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+
+    let mut out = Vec::with_capacity(len);
+    let mut inputs = inputs.into_iter();
+    if let Some(first) = inputs.next() {
+        out.extend_from_slice(first);
+    }
+    out.resize(len, 0);
+
+    for input in inputs {
+        xor_into(&mut out, input);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn xor_reference(dst: &mut [u8], src: &[u8]) {
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d ^= s;
+        }
+    }
+
+    #[test]
+    fn test_xor_into_matches_reference() {
+        let src: Vec<u8> = (0..200).map(|i| (i * 7 + 3) as u8).collect();
+        let base: Vec<u8> = (0..220).map(|i| (i * 13 + 1) as u8).collect();
+
+        // NOTE: Covers every remainder case and unaligned slices
+        for offset in 0..3 {
+            for len in 0..src.len() - offset {
+                let src = &src[offset..offset + len];
+                let mut expected = base.clone();
+                let mut actual = base.clone();
+                xor_reference(&mut expected[offset..], src);
+                xor_into(&mut actual[offset..], src);
+                assert_eq!(expected, actual, "offset {}, len {}", offset, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_xor_many() {
+        let a = vec![0xFF; 70];
+        let b = vec![0x0F; 3];
+        let c = vec![0xF0; 100];
+
+        let res = xor_many([a.as_slice(), b.as_slice(), c.as_slice()]);
+        let mut expected = vec![0x00; 3];
+        expected.extend(vec![0x0F; 67]);
+        expected.extend(vec![0xF0; 30]);
+        assert_eq!(expected, res);
+
+        assert!(xor_many([]).is_empty());
+        assert_eq!(a, xor_many([a.as_slice()]));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod simple_packet_pool;
+pub mod virtual_queue_pool;
 
 
 use crate::packet::PacketData;
@@ -6,6 +7,7 @@ use crate::packet::PacketData;
 use super::packet::{Packet, CodingInfo};
 use cope_config::types::node_id::NodeID;
 pub use simple_packet_pool::SimplePacketPool;
+pub use virtual_queue_pool::VirtualQueuePool;
 
 pub type PPEntry = (CodingInfo, PacketData);
 
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use super::{PPEntry, PacketPool};
-use crate::packet::{CodingInfo, Packet, packet::CodingHeader};
+use crate::packet::{CodingInfo, Packet, PacketData, packet::CodingHeader};
 use cope_config::types::node_id::NodeID;
 
 // NOTE: This is the most simple way to implement a packet pool
@@ -10,9 +10,17 @@ use cope_config::types::node_id::NodeID;
 // We could use a ring buffer but this would be more complex
 // Using this setup, the relay will also forget
 // GC does nothing
+//
+// Leaves additionally keep natives they overheard from their neighbors, so
+// they can decode packets that were coded with them. These are kept apart from
+// the packets the node sent itself, so they can't push those out. Positions of
+// overheard packets start after the last own packet. Only get, get_ref, position
+// and remove see them, since the node never sends them.
 pub struct SimplePacketPool {
     queue: Vec<PPEntry>,
     max_size: usize,
+    overheard: Vec<PPEntry>,
+    max_overheard: usize,
 }
 
 impl SimplePacketPool {
@@ -20,9 +28,41 @@ impl SimplePacketPool {
         Self {
             queue: Vec::new(),
             max_size,
+            overheard: Vec::new(),
+            max_overheard: 0,
         }
     }
 
+    pub fn with_overheard_size(mut self, max_overheard: usize) -> Self {
+        self.max_overheard = max_overheard;
+        self
+    }
+
+    // NOTE: Evicts the oldest overheard packet, if there is no room left.
+    // Packets that are overheard again, e.g. because they were retransmitted,
+    // are only stored once.
+    pub fn push_overheard(&mut self, packet: Packet) {
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        self.push_overheard_native(info.clone(), packet.data().clone());
+    }
+
+    pub fn push_overheard_native(&mut self, info: CodingInfo, data: PacketData) {
+        if self.max_overheard == 0 || self.overheard.iter().any(|x| x.0.key() == info.key()) {
+            return;
+        }
+
+        if self.overheard.len() >= self.max_overheard {
+            self.overheard.remove(0);
+        }
+        self.overheard.push((info, data));
+    }
+
+    pub fn overheard_size(&self) -> usize {
+        self.overheard.len()
+    }
+
     pub fn unique_nexthops(&self) -> usize {
         let uniques: HashSet<NodeID> = self.queue.iter().map(|(ci, _)| ci.nexthop).collect();
         uniques.len()
@@ -53,15 +93,25 @@ impl PacketPool for SimplePacketPool {
     }
 
     fn get(&self, pos: usize) -> Option<&PPEntry> {
-        self.queue.get(pos)
+        self.get_ref(pos)
     }
 
     fn position(&self, info: &CodingInfo) -> Option<usize> {
-        self.queue.iter().position(|x| x.0 == *info)
+        self.queue
+            .iter()
+            .chain(&self.overheard)
+            .position(|x| x.0.key() == info.key())
     }
 
     fn remove(&mut self, pos: usize) -> Option<PPEntry> {
-        Some(self.queue.remove(pos))
+        if pos < self.queue.len() {
+            return Some(self.queue.remove(pos));
+        }
+        let pos = pos - self.queue.len();
+        if pos >= self.overheard.len() {
+            return None;
+        }
+        Some(self.overheard.remove(pos))
     }
 
     fn push_packet(&mut self, packet: Packet) {
@@ -83,6 +133,82 @@ impl PacketPool for SimplePacketPool {
     }
 
     fn get_ref(&self, pos: usize) -> Option<&PPEntry> {
-        self.queue.get(pos)
+        match pos.checked_sub(self.queue.len()) {
+            Some(pos) => self.overheard.get(pos),
+            None => self.queue.get(pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::{PacketBuilder, PacketID};
+
+    fn native(source: char, id: u16, nexthop: char) -> Packet {
+        PacketBuilder::new()
+            .sender(NodeID::new(source))
+            .native_header(CodingInfo {
+                source: NodeID::new(source),
+                id: PacketID::new(id),
+                nexthop: NodeID::new(nexthop),
+                destination: NodeID::new(nexthop),
+                len: 0,
+            })
+            .ack_header(vec![])
+            .data_raw(vec![id as u8])
+            .build()
+            .unwrap()
+    }
+
+    fn info(packet: &Packet) -> CodingInfo {
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        info.clone()
+    }
+
+    #[test]
+    fn test_overheard_positions() {
+        let mut pool = SimplePacketPool::new(2).with_overheard_size(2);
+        let own = native('A', 1, 'C');
+        let heard = native('D', 7, 'B');
+        pool.push_packet(own.clone());
+        pool.push_overheard(heard.clone());
+        pool.push_overheard(heard.clone());
+
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.overheard_size(), 1);
+        assert_eq!(pool.position(&info(&own)), Some(0));
+        assert_eq!(pool.position(&info(&heard)), Some(1));
+        assert_eq!(pool.get_ref(1).unwrap().1, *heard.data());
+        assert!(pool.get_ref(2).is_none());
+
+        // NOTE: The relay only codes with own packets
+        assert!(pool.pop_nexthop_front(NodeID::new('B')).is_none());
+
+        assert_eq!(pool.remove(1).unwrap().0, info(&heard));
+        assert!(pool.remove(1).is_none());
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[test]
+    fn test_overheard_eviction() {
+        let mut pool = SimplePacketPool::new(2).with_overheard_size(2);
+        let own = native('A', 1, 'C');
+        pool.push_packet(own.clone());
+        for id in 0..3 {
+            pool.push_overheard(native('D', id, 'B'));
+        }
+
+        // NOTE: Overheard packets only push out other overheard packets
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.overheard_size(), 2);
+        assert!(pool.position(&info(&native('D', 0, 'B'))).is_none());
+        assert_eq!(pool.position(&info(&native('D', 2, 'B'))), Some(2));
+
+        let mut relay_pool = SimplePacketPool::new(2);
+        relay_pool.push_overheard(native('D', 0, 'B'));
+        assert_eq!(relay_pool.overheard_size(), 0);
     }
 }
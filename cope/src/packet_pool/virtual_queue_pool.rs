@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::{PPEntry, PacketPool};
+use crate::packet::{packet::CodingHeader, CodingInfo, Packet, PacketData};
+use cope_config::types::node_id::NodeID;
+
+// NOTE: The COPE paper uses 100 bytes to tell small from large packets
+pub const SMALL_PACKET_SIZE: usize = 100;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SizeClass {
+    Small,
+    Large,
+}
+
+impl SizeClass {
+    pub fn of(len: usize) -> Self {
+        if len <= SMALL_PACKET_SIZE {
+            Self::Small
+        } else {
+            Self::Large
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Self::Small => Self::Large,
+            Self::Large => Self::Small,
+        }
+    }
+}
+
+// NOTE: Sequence number and arrival time of a packet
+type QueueEntry = (u64, Instant, PPEntry);
+
+// NOTE: Packet pool of the relay, as described in section 3.1 of the COPE paper.
+// Packets are kept in one virtual queue per nexthop and size class, so finding
+// a packet for a nexthop does not need to scan all packets, and packets of
+// similar size can be coded together to waste less airtime on padding.
+//
+// Every packet gets a sequence number on arrival, so the pool as a whole is
+// still FIFO: pop_front and eviction take the oldest packet of all queues, and
+// positions are in order of arrival. The arrival time is kept as well, so the
+// relay knows how long packets have been waiting, see scheduler::CodingScheduler.
+pub struct VirtualQueuePool {
+    queues: HashMap<(NodeID, SizeClass), VecDeque<QueueEntry>>,
+    next_sequence: u64,
+    size: usize,
+    max_size: usize,
+}
+
+impl VirtualQueuePool {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            queues: HashMap::new(),
+            next_sequence: 0,
+            size: 0,
+            max_size,
+        }
+    }
+
+    fn front(&self, key: (NodeID, SizeClass)) -> Option<&QueueEntry> {
+        self.queues.get(&key).and_then(|queue| queue.front())
+    }
+
+    // NOTE: Key of the queue whose head arrived first, out of the matching queues
+    fn oldest_queue(
+        &self,
+        matches: impl Fn(&(NodeID, SizeClass)) -> bool,
+    ) -> Option<(NodeID, SizeClass)> {
+        self.queues
+            .iter()
+            .filter(|(key, _)| matches(key))
+            .filter_map(|(key, queue)| queue.front().map(|(seq, _, _)| (*seq, *key)))
+            .min_by_key(|(seq, _)| *seq)
+            .map(|(_, key)| key)
+    }
+
+    fn pop_queue(&mut self, key: (NodeID, SizeClass)) -> Option<PPEntry> {
+        let (_, _, entry) = self.queues.get_mut(&key)?.pop_front()?;
+        self.size -= 1;
+        Some(entry)
+    }
+
+    // NOTE: All packets in order of arrival, which defines their positions
+    fn entries(&self) -> Vec<&QueueEntry> {
+        let mut entries: Vec<_> = self.queues.values().flatten().collect();
+        entries.sort_by_key(|(seq, _, _)| *seq);
+        entries
+    }
+
+    // NOTE: Picks the head of the queue of nexthop, whose length is closest to len.
+    // Usually this is the queue with the same size class, but an empty queue or
+    // a packet right at the border of the classes can make the other one closer.
+    // The oldest packet of nexthop is one of the two heads, so this never results
+    // in more padding than coding the oldest packet.
+    fn similar_queue(&self, nexthop: NodeID, len: usize) -> Option<(NodeID, SizeClass)> {
+        let class = SizeClass::of(len);
+        [class, class.other()]
+            .into_iter()
+            .map(|class| (nexthop, class))
+            .filter_map(|key| {
+                self.front(key)
+                    .map(|(seq, _, (_, data))| (key, *seq, data.len()))
+            })
+            .min_by_key(|&(_, seq, other)| (len.abs_diff(other), seq))
+            .map(|(key, _, _)| key)
+    }
+
+    pub fn peek_nexthop_similar(&self, nexthop: NodeID, len: usize) -> Option<&PPEntry> {
+        let key = self.similar_queue(nexthop, len)?;
+        self.front(key).map(|(_, _, entry)| entry)
+    }
+
+    pub fn pop_nexthop_similar(&mut self, nexthop: NodeID, len: usize) -> Option<PPEntry> {
+        let key = self.similar_queue(nexthop, len)?;
+        self.pop_queue(key)
+    }
+
+    pub fn unique_nexthops(&self) -> usize {
+        let uniques: HashSet<NodeID> = self
+            .queues
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|((nexthop, _), _)| *nexthop)
+            .collect();
+        uniques.len()
+    }
+
+    // NOTE: Natives don't have to arrive on their own, relays also decode
+    // them from packets coded by other relays.
+    pub fn push_native(&mut self, info: CodingInfo, data: PacketData) {
+        let is_at_max_size = self.size >= self.max_size;
+        if is_at_max_size {
+            self.pop_front();
+        }
+        let key = (info.nexthop, SizeClass::of(data.len()));
+        self.queues
+            .entry(key)
+            .or_default()
+            .push_back((self.next_sequence, Instant::now(), (info, data)));
+        self.next_sequence += 1;
+        self.size += 1;
+    }
+
+    // NOTE: How long the oldest packet has been waiting in the pool
+    pub fn oldest_age(&self) -> Option<Duration> {
+        let key = self.oldest_queue(|_| true)?;
+        self.front(key).map(|(_, arrived, _)| arrived.elapsed())
+    }
+}
+
+impl PacketPool for VirtualQueuePool {
+    fn peek_front(&self) -> Option<&PPEntry> {
+        let key = self.oldest_queue(|_| true)?;
+        self.front(key).map(|(_, _, entry)| entry)
+    }
+
+    fn pop_front(&mut self) -> Option<PPEntry> {
+        let key = self.oldest_queue(|_| true)?;
+        self.pop_queue(key)
+    }
+
+    fn peek_nexthop_front(&self, nexthop: NodeID) -> Option<&PPEntry> {
+        let key = self.oldest_queue(|(n, _)| *n == nexthop)?;
+        self.front(key).map(|(_, _, entry)| entry)
+    }
+
+    fn pop_nexthop_front(&mut self, nexthop: NodeID) -> Option<PPEntry> {
+        let key = self.oldest_queue(|(n, _)| *n == nexthop)?;
+        self.pop_queue(key)
+    }
+
+    fn get(&self, pos: usize) -> Option<&PPEntry> {
+        self.get_ref(pos)
+    }
+
+    fn position(&self, info: &CodingInfo) -> Option<usize> {
+        self.entries().iter().position(|(_, _, x)| x.0.key() == info.key())
+    }
+
+    fn remove(&mut self, pos: usize) -> Option<PPEntry> {
+        let (seq, _, entry) = self.entries().get(pos).copied()?;
+        let key = (entry.0.nexthop, SizeClass::of(entry.1.len()));
+        let seq = *seq;
+        let queue = self.queues.get_mut(&key)?;
+        let index = queue.iter().position(|(s, _, _)| *s == seq)?;
+        self.size -= 1;
+        queue.remove(index).map(|(_, _, entry)| entry)
+    }
+
+    fn get_ref(&self, pos: usize) -> Option<&PPEntry> {
+        self.entries().get(pos).map(|(_, _, entry)| entry)
+    }
+
+    fn push_packet(&mut self, packet: Packet) {
+        let CodingHeader::Native(info) = packet.coding_header() else {
+            panic!("Expected Native Packet");
+        };
+        self.push_native(info.clone(), packet.data().clone());
+    }
+
+    fn garbage_collect() {}
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::{PacketBuilder, PacketID};
+
+    fn native(id: u16, nexthop: char, len: usize) -> Packet {
+        PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(CodingInfo {
+                source: NodeID::new('A'),
+                id: PacketID::new(id),
+                nexthop: NodeID::new(nexthop),
+                destination: NodeID::new(nexthop),
+                len: 0,
+            })
+            .ack_header(vec![])
+            .data_raw(vec![id as u8; len])
+            .build()
+            .unwrap()
+    }
+
+    fn id(entry: Option<&PPEntry>) -> Option<u16> {
+        entry.map(|(info, _)| info.id.value())
+    }
+
+    #[test]
+    fn test_fifo_across_queues() {
+        let mut pool = VirtualQueuePool::new(3);
+        pool.push_packet(native(1, 'C', 500));
+        pool.push_packet(native(2, 'D', 10));
+        pool.push_packet(native(3, 'C', 10));
+        assert_eq!(pool.unique_nexthops(), 2);
+        assert_eq!(id(pool.peek_front()), Some(1));
+        assert_eq!(id(pool.peek_nexthop_front(NodeID::new('C'))), Some(1));
+        assert_eq!(id(pool.get_ref(2)), Some(3));
+
+        // NOTE: The oldest packet is evicted, no matter which queue it is in
+        pool.push_packet(native(4, 'D', 500));
+        assert_eq!(pool.size(), 3);
+        assert_eq!(id(pool.pop_front().as_ref()), Some(2));
+        assert_eq!(pool.position(&pool.get_ref(1).unwrap().0.clone()), Some(1));
+        assert_eq!(id(pool.remove(0).as_ref()), Some(3));
+        assert_eq!(
+            id(pool.pop_nexthop_front(NodeID::new('D')).as_ref()),
+            Some(4)
+        );
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.unique_nexthops(), 0);
+    }
+
+    #[test]
+    fn test_similar_size() {
+        let node_c = NodeID::new('C');
+        let mut pool = VirtualQueuePool::new(8);
+        pool.push_packet(native(1, 'C', 1000));
+        pool.push_packet(native(2, 'C', 20));
+        pool.push_packet(native(3, 'C', 40));
+
+        assert_eq!(id(pool.peek_nexthop_similar(node_c, 30)), Some(2));
+        assert_eq!(id(pool.peek_nexthop_similar(node_c, 800)), Some(1));
+        assert_eq!(id(pool.pop_nexthop_similar(node_c, 800).as_ref()), Some(1));
+
+        // NOTE: Without large packets, a small one is better than nothing
+        assert_eq!(id(pool.pop_nexthop_similar(node_c, 800).as_ref()), Some(2));
+        assert!(pool.peek_nexthop_similar(NodeID::new('D'), 30).is_none());
+    }
+}
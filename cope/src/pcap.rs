@@ -0,0 +1,209 @@
+// Writes COPE traffic to a capture file in the classic pcap format, so runs can
+// be inspected with standard packet tools.
+//
+// Every record uses the user-defined link type LINKTYPE_USER0 and starts with a
+// small pseudo-header, followed by the packet in its wire format (see packet::wire):
+//
+// version:u8 node:u8 direction:u8 reserved:u8 packet
+//
+// node is the ASCII id of the node that captured the packet and direction is
+// one of DIRECTION_RX or DIRECTION_TX. The record timestamp is the time of capture.
+//
+// To decode the packets in Wireshark, map DLT_USER0 (147) to a dissector with
+// a header size of PSEUDO_HEADER_SIZE bytes.
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cope_config::types::node_id::NodeID;
+
+use crate::packet::PacketEncodeError;
+use crate::Packet;
+
+pub const LINKTYPE_USER0: u32 = 147;
+pub const PSEUDO_HEADER_VERSION: u8 = 1;
+pub const PSEUDO_HEADER_SIZE: usize = 4;
+pub const DIRECTION_RX: u8 = 0;
+pub const DIRECTION_TX: u8 = 1;
+
+const PCAP_MAGIC: u32 = 0xA1B2C3D4;
+const PCAP_VERSION: (u16, u16) = (2, 4);
+const SNAPLEN: u32 = 65535;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    fn as_u8(self) -> u8 {
+        match self {
+            Direction::Rx => DIRECTION_RX,
+            Direction::Tx => DIRECTION_TX,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PcapError {
+    Io(std::io::Error),
+    Encode(PacketEncodeError),
+    Poisoned,
+}
+
+impl Display for PcapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PcapError::Io(e) => write!(f, "could not write capture: {}", e),
+            PcapError::Encode(e) => write!(f, "could not encode packet: {}", e),
+            PcapError::Poisoned => write!(f, "capture lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for PcapError {}
+
+impl From<std::io::Error> for PcapError {
+    fn from(e: std::io::Error) -> Self {
+        PcapError::Io(e)
+    }
+}
+
+impl From<PacketEncodeError> for PcapError {
+    fn from(e: PacketEncodeError) -> Self {
+        PcapError::Encode(e)
+    }
+}
+
+pub struct PcapWriter<W: Write = BufWriter<File>> {
+    out: W,
+}
+
+// NOTE: Used by the simulator to let multiple nodes write into the same capture
+pub type SharedPcapWriter = Arc<Mutex<PcapWriter>>;
+
+impl PcapWriter {
+    pub fn create(path: &str) -> Result<Self, PcapError> {
+        let p = Path::new(path);
+        if let Some(dirs) = p.parent() {
+            std::fs::create_dir_all(dirs)?;
+        }
+        Self::new(BufWriter::new(File::create(p)?))
+    }
+
+    pub fn shared(self) -> SharedPcapWriter {
+        Arc::new(Mutex::new(self))
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut out: W) -> Result<Self, PcapError> {
+        out.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        out.write_all(&PCAP_VERSION.0.to_le_bytes())?;
+        out.write_all(&PCAP_VERSION.1.to_le_bytes())?;
+        // NOTE: thiszone and sigfigs, which are always 0 in practice
+        out.write_all(&0i32.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&SNAPLEN.to_le_bytes())?;
+        out.write_all(&LINKTYPE_USER0.to_le_bytes())?;
+        Ok(Self { out })
+    }
+
+    pub fn write_packet(
+        &mut self,
+        node: NodeID,
+        direction: Direction,
+        packet: &Packet,
+    ) -> Result<(), PcapError> {
+        self.write_packet_at(SystemTime::now(), node, direction, packet)
+    }
+
+    pub fn write_packet_at(
+        &mut self,
+        timestamp: SystemTime,
+        node: NodeID,
+        direction: Direction,
+        packet: &Packet,
+    ) -> Result<(), PcapError> {
+        let bytes = packet.serialize_into()?;
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let len = (PSEUDO_HEADER_SIZE + bytes.len()) as u32;
+        let captured = u32::min(len, SNAPLEN);
+
+        self.out
+            .write_all(&(since_epoch.as_secs() as u32).to_le_bytes())?;
+        self.out
+            .write_all(&since_epoch.subsec_micros().to_le_bytes())?;
+        self.out.write_all(&captured.to_le_bytes())?;
+        self.out.write_all(&len.to_le_bytes())?;
+
+        let header = [
+            PSEUDO_HEADER_VERSION,
+            node.unwrap() as u8,
+            direction.as_u8(),
+            0,
+        ];
+        self.out.write_all(&header)?;
+        self.out
+            .write_all(&bytes[..captured as usize - PSEUDO_HEADER_SIZE])?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), PcapError> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::packet::{CodingInfo, PacketBuilder, PacketID};
+
+    #[test]
+    fn test_capture_layout() {
+        let packet = PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(CodingInfo {
+                source: NodeID::new('A'),
+                id: PacketID::new(1),
+                nexthop: NodeID::new('C'),
+                destination: NodeID::new('C'),
+                len: 0,
+            })
+            .ack_header(vec![])
+            .data_raw(vec![1, 2, 3])
+            .build()
+            .unwrap();
+        let timestamp = UNIX_EPOCH + Duration::from_micros(5_000_042);
+
+        let mut writer = PcapWriter::new(Vec::new()).unwrap();
+        writer
+            .write_packet_at(timestamp, NodeID::new('B'), Direction::Rx, &packet)
+            .unwrap();
+        let out = writer.into_inner();
+
+        let wire = packet.serialize_into().unwrap();
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        assert_eq!(u32_at(0), PCAP_MAGIC);
+        assert_eq!(u32_at(20), LINKTYPE_USER0);
+        // NOTE: The global header is 24 bytes long
+        assert_eq!(u32_at(24), 5);
+        assert_eq!(u32_at(28), 42);
+        assert_eq!(u32_at(32) as usize, PSEUDO_HEADER_SIZE + wire.len());
+        assert_eq!(u32_at(36) as usize, PSEUDO_HEADER_SIZE + wire.len());
+        assert_eq!(&out[40..44], &[PSEUDO_HEADER_VERSION, b'B', DIRECTION_RX, 0]);
+        assert_eq!(&out[44..], wire.as_slice());
+    }
+}
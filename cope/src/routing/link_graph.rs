@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::packet::LinkStateAdvert;
+use cope_config::types::node_id::NodeID;
+
+// NOTE: Expected number of transmissions to get a packet across a link and
+// its ack back, as defined in the ETX paper: 1 / (d_f * d_r). Links that lost
+// every probe in one of the directions can not be used.
+pub fn link_etx(forward: f64, reverse: f64) -> Option<f64> {
+    if forward <= 0.0 || reverse <= 0.0 {
+        return None;
+    }
+    Some(1.0 / (forward * reverse))
+}
+
+// NOTE: Delivery ratios of all links in the network, as reported by the
+// receiving end of each link.
+#[derive(Default)]
+pub struct LinkGraph {
+    // NOTE: (from, to) -> share of probes from from, that to received
+    links: HashMap<(NodeID, NodeID), f64>,
+}
+
+impl LinkGraph {
+    pub fn new() -> Self {
+        Self {
+            links: HashMap::new(),
+        }
+    }
+
+    // NOTE: Replaces everything origin reported before
+    pub fn insert_advert(&mut self, advert: &LinkStateAdvert) {
+        self.links.retain(|&(_, to), _| to != advert.origin);
+        for link in &advert.links {
+            self.links
+                .insert((link.neighbor, advert.origin), link.ratio());
+        }
+    }
+
+    pub fn delivery_ratio(&self, from: NodeID, to: NodeID) -> f64 {
+        self.links.get(&(from, to)).copied().unwrap_or(0.0)
+    }
+
+    pub fn etx(&self, a: NodeID, b: NodeID) -> Option<f64> {
+        link_etx(self.delivery_ratio(a, b), self.delivery_ratio(b, a))
+    }
+
+    // NOTE: Dijkstra over the ETX of all links, returns the nexthop and ETX
+    // of the best path from source to every node it can reach. Only relays
+    // forward packets and leaves only accept packets from relays, so paths may
+    // only pass through relays and every link has to end at one.
+    pub fn shortest_paths(&self, source: NodeID, relays: &[NodeID]) -> Vec<(NodeID, NodeID, f64)> {
+        let nodes: HashSet<NodeID> = self
+            .links
+            .keys()
+            .flat_map(|&(from, to)| [from, to])
+            .chain(std::iter::once(source))
+            .collect();
+
+        // NOTE: node -> (ETX from source, nexthop of source towards node)
+        let mut best: HashMap<NodeID, (f64, NodeID)> = HashMap::new();
+        let mut visited: HashSet<NodeID> = HashSet::new();
+        best.insert(source, (0.0, source));
+
+        // NOTE: Networks are small, so we just search for the closest node
+        // instead of keeping a priority queue.
+        while let Some((node, (cost, nexthop))) = best
+            .iter()
+            .filter(|(node, _)| !visited.contains(*node))
+            .min_by(|(a, (x, _)), (b, (y, _))| x.total_cmp(y).then(a.unwrap().cmp(&b.unwrap())))
+            .map(|(&node, &entry)| (node, entry))
+        {
+            visited.insert(node);
+            if node != source && !relays.contains(&node) {
+                continue;
+            }
+
+            for &neighbor in nodes.iter() {
+                let is_usable = relays.contains(&node) || relays.contains(&neighbor);
+                let Some(etx) = self.etx(node, neighbor).filter(|_| is_usable) else {
+                    continue;
+                };
+                let nexthop = if node == source { neighbor } else { nexthop };
+                let is_better = best
+                    .get(&neighbor)
+                    .is_none_or(|&(other, _)| cost + etx < other);
+                if !visited.contains(&neighbor) && is_better {
+                    best.insert(neighbor, (cost + etx, nexthop));
+                }
+            }
+        }
+
+        let mut paths: Vec<_> = best
+            .into_iter()
+            .filter(|&(node, _)| node != source)
+            .map(|(node, (cost, nexthop))| (node, nexthop, cost))
+            .collect();
+        paths.sort_by_key(|&(node, _, _)| node.unwrap());
+        paths
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::{LinkQuality, PacketID};
+
+    fn advert(origin: NodeID, links: &[(NodeID, f64)]) -> LinkStateAdvert {
+        LinkStateAdvert {
+            origin,
+            seq: PacketID::new(0),
+            links: links
+                .iter()
+                .map(|&(neighbor, ratio)| LinkQuality::new(neighbor, ratio))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_link_etx() {
+        assert_eq!(link_etx(1.0, 1.0), Some(1.0));
+        assert_eq!(link_etx(0.5, 0.5), Some(4.0));
+        assert_eq!(link_etx(1.0, 0.0), None);
+    }
+
+    fn nexthops(paths: Vec<(NodeID, NodeID, f64)>) -> Vec<(NodeID, NodeID)> {
+        paths
+            .into_iter()
+            .map(|(node, nexthop, _)| (node, nexthop))
+            .collect()
+    }
+
+    // NOTE: The diamond A - {B, C} - D, where B and C are relays. The link
+    // between B and D is lossy, so A reaches D over C.
+    #[test]
+    fn test_shortest_paths() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let mut graph = LinkGraph::new();
+        graph.insert_advert(&advert(a, &[(b, 1.0), (c, 1.0)]));
+        graph.insert_advert(&advert(b, &[(a, 1.0), (d, 0.5)]));
+        graph.insert_advert(&advert(c, &[(a, 1.0), (d, 1.0)]));
+        graph.insert_advert(&advert(d, &[(b, 0.5), (c, 1.0)]));
+
+        assert!((graph.etx(b, d).unwrap() - 4.0).abs() < 0.05);
+        let paths = graph.shortest_paths(a, &[b, c]);
+        assert_eq!(paths[2], (d, c, 2.0));
+        assert_eq!(nexthops(paths), vec![(b, b), (c, c), (d, c)]);
+
+        // NOTE: Leaves don't forward, so B can't take the detour over A
+        assert_eq!(
+            nexthops(graph.shortest_paths(b, &[b, c])),
+            vec![(a, a), (d, d)]
+        );
+
+        // NOTE: D stopped hearing C, so the link breaks in both directions
+        graph.insert_advert(&advert(d, &[(b, 0.5)]));
+        assert_eq!(graph.etx(c, d), None);
+        assert_eq!(
+            nexthops(graph.shortest_paths(a, &[b, c])),
+            vec![(b, b), (c, c), (d, b)]
+        );
+    }
+}
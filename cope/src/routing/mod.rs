@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::config::CONFIG;
+use crate::packet::{LinkQuality, LinkStateAdvert, Packet, PacketBuilder, PacketID};
+use crate::stats::Stats;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::routing_type::RoutingType;
+
+pub mod link_graph;
+pub mod probe_tracker;
+
+pub use link_graph::LinkGraph;
+pub use probe_tracker::ProbeTracker;
+
+// NOTE: Adverts that were not refreshed for this many intervals are dropped,
+// so links of nodes that went away don't stay in the graph forever.
+pub const ADVERT_TIMEOUT: u32 = 3;
+
+// NOTE: Learns routes from link state adverts. Every node broadcasts an advert
+// with the delivery ratios of its neighbors once per interval, which also
+// serves as the probe its neighbors measure. Relays rebroadcast the newest
+// advert of every origin once, and routes follow the paths with the lowest
+// ETX, see LinkGraph::shortest_paths. Leaves don't need to rebroadcast, since
+// nodes that can only be reached over a leaf can't be routed to anyway.
+pub struct Router {
+    id: NodeID,
+    relays: Vec<NodeID>,
+    interval: Duration,
+    next_advert: Option<Instant>,
+    seq: PacketID,
+    probes: ProbeTracker,
+    // NOTE: Newest advert of every other origin and when we received it
+    adverts: HashMap<NodeID, (LinkStateAdvert, Instant)>,
+    floods: Vec<LinkStateAdvert>,
+    routes: Vec<(NodeID, NodeID)>,
+    routes_changed: bool,
+    route_changes: u32,
+}
+
+impl Router {
+    pub fn new(id: NodeID, relays: Vec<NodeID>, interval: Duration) -> Self {
+        Self {
+            id,
+            relays,
+            interval,
+            next_advert: None,
+            seq: PacketID::new(0),
+            probes: ProbeTracker::new(interval),
+            adverts: HashMap::new(),
+            floods: vec![],
+            routes: vec![],
+            routes_changed: false,
+            route_changes: 0,
+        }
+    }
+
+    pub fn from_config(id: NodeID) -> Option<Self> {
+        match CONFIG.routing {
+            RoutingType::Static => None,
+            RoutingType::Etx(interval) => Some(Self::new(id, CONFIG.get_relays(), interval)),
+        }
+    }
+
+    pub fn handle_rx(&mut self, sender: NodeID, advert: &LinkStateAdvert, now: Instant) {
+        if sender == advert.origin {
+            self.probes.insert_probe(sender, now);
+        }
+        if advert.origin == self.id {
+            return;
+        }
+
+        // NOTE: Once the old advert expired, e.g. because the origin rebooted
+        // and started over at the first seq, any advert is accepted again.
+        self.expire(now);
+        let is_newer = self
+            .adverts
+            .get(&advert.origin)
+            .is_none_or(|(old, _)| advert.seq > old.seq);
+        if !is_newer {
+            return;
+        }
+        if self.relays.contains(&self.id) {
+            self.floods.retain(|a| a.origin != advert.origin);
+            self.floods.push(advert.clone());
+        }
+        self.adverts.insert(advert.origin, (advert.clone(), now));
+        self.update_routes(now);
+    }
+
+    // NOTE: Forgets adverts that were not refreshed for ADVERT_TIMEOUT
+    // intervals, like ProbeTracker::expire does with probes.
+    fn expire(&mut self, now: Instant) {
+        let timeout = self.interval * ADVERT_TIMEOUT;
+        self.adverts.retain(|origin, (_, received)| {
+            let is_alive = now.saturating_duration_since(*received) < timeout;
+            if !is_alive {
+                log::info!("[Node {}]: Advert of {} expired", self.id, origin);
+            }
+            is_alive
+        });
+    }
+
+    // NOTE: Our own advert is sent every interval, adverts of others
+    // are rebroadcast in between.
+    pub fn handle_tx(&mut self, now: Instant) -> Option<Packet> {
+        let advert = match self.next_advert {
+            Some(next) if now < next => {
+                if self.floods.is_empty() {
+                    return None;
+                }
+                self.floods.remove(0)
+            }
+            next => {
+                // NOTE: Adverts are sent at fixed times, so neighbors receive
+                // the expected amount of probes per window, see ProbeTracker.
+                self.next_advert = match next {
+                    Some(next) if now < next + self.interval => Some(next + self.interval),
+                    _ => Some(now + self.interval),
+                };
+                self.seq = self.seq.next();
+                self.probes.expire(now);
+                self.expire(now);
+                self.update_routes(now);
+                self.own_advert(now)
+            }
+        };
+
+        let packet = PacketBuilder::new()
+            .sender(self.id)
+            .routing_header(advert)
+            .ack_header(vec![])
+            .build()
+            .unwrap();
+        Some(packet)
+    }
+
+    fn own_advert(&self, now: Instant) -> LinkStateAdvert {
+        let mut links: Vec<LinkQuality> = self
+            .probes
+            .neighbors()
+            .map(|neighbor| LinkQuality::new(neighbor, self.probes.delivery_ratio(neighbor, now)))
+            .filter(|link| link.delivery > 0)
+            .collect();
+        links.sort_by_key(|link| link.neighbor.unwrap());
+        LinkStateAdvert {
+            origin: self.id,
+            seq: self.seq,
+            links,
+        }
+    }
+
+    fn update_routes(&mut self, now: Instant) {
+        let mut graph = LinkGraph::new();
+        for (advert, _) in self.adverts.values() {
+            graph.insert_advert(advert);
+        }
+        graph.insert_advert(&self.own_advert(now));
+
+        let routes: Vec<(NodeID, NodeID)> = graph
+            .shortest_paths(self.id, &self.relays)
+            .into_iter()
+            .map(|(destination, nexthop, _)| (destination, nexthop))
+            .collect();
+
+        // NOTE: Destinations that were added, removed or got a new nexthop
+        let changes = self
+            .routes
+            .iter()
+            .filter(|route| !routes.contains(route))
+            .chain(routes.iter().filter(|route| !self.routes.contains(route)))
+            .map(|(destination, _)| destination.unwrap())
+            .collect::<HashSet<_>>()
+            .len();
+        if changes > 0 {
+            log::info!("[Node {}]: New routes {:?}", self.id, routes);
+            self.routes = routes;
+            self.routes_changed = true;
+            self.route_changes += changes as u32;
+        }
+    }
+
+    pub fn routes(&self) -> &[(NodeID, NodeID)] {
+        &self.routes
+    }
+
+    // NOTE: Returns the routes, if they changed since the last call
+    pub fn take_routes(&mut self) -> Option<Vec<(NodeID, NodeID)>> {
+        match std::mem::take(&mut self.routes_changed) {
+            true => Some(self.routes.clone()),
+            false => None,
+        }
+    }
+
+    pub fn update_stats(&mut self, stats: &mut Stats) {
+        stats.add_route_changes(std::mem::take(&mut self.route_changes));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::CodingHeader;
+
+    fn advert(packet: &Packet) -> &LinkStateAdvert {
+        let CodingHeader::Routing(advert) = packet.coding_header() else {
+            panic!("Expected advert, got {}", packet);
+        };
+        advert
+    }
+
+    // NOTE: Delivers the packets of all routers to their neighbors, until
+    // nobody has anything left to send.
+    fn exchange(routers: &mut [Router], links: &[(NodeID, NodeID)], now: Instant) {
+        loop {
+            let packets: Vec<Packet> = routers
+                .iter_mut()
+                .filter_map(|r| r.handle_tx(now))
+                .collect();
+            if packets.is_empty() {
+                return;
+            }
+            for packet in packets {
+                for router in routers.iter_mut() {
+                    let (a, b) = (packet.sender(), router.id);
+                    if links.contains(&(a, b)) || links.contains(&(b, a)) {
+                        router.handle_rx(a, advert(&packet), now);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_advert_schedule() {
+        let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut relay = Router::new(b, vec![b], interval);
+        let start = Instant::now();
+
+        let first = relay.handle_tx(start).unwrap();
+        assert_eq!(advert(&first).origin, b);
+        assert!(relay.handle_tx(start + interval / 2).is_none());
+
+        // NOTE: Adverts of others are rebroadcast once, old ones are ignored
+        let from_a = LinkStateAdvert {
+            origin: a,
+            seq: PacketID::new(3),
+            links: vec![LinkQuality::new(b, 1.0)],
+        };
+        relay.handle_rx(a, &from_a, start);
+        relay.handle_rx(c, &from_a, start);
+        let flood = relay.handle_tx(start + interval / 2).unwrap();
+        assert_eq!((flood.sender(), advert(&flood)), (b, &from_a));
+        assert!(relay.handle_tx(start + interval / 2).is_none());
+
+        // NOTE: Only one of the two adverts A should have sent by now arrived
+        let next = relay.handle_tx(start + interval).unwrap();
+        assert_eq!(advert(&next).seq, advert(&first).seq.next());
+        assert_eq!(advert(&next).links, vec![LinkQuality::new(a, 0.5)]);
+
+        // NOTE: Leaves don't rebroadcast
+        let mut leaf = Router::new(c, vec![b], interval);
+        leaf.handle_tx(start);
+        leaf.handle_rx(b, &from_a, start);
+        assert!(leaf.handle_tx(start).is_none());
+    }
+
+    #[test]
+    fn test_advert_expiry() {
+        let [a, b] = ['A', 'B'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut relay = Router::new(b, vec![b], interval);
+        let start = Instant::now();
+        let from_a = |seq| LinkStateAdvert {
+            origin: a,
+            seq: PacketID::new(seq),
+            links: vec![LinkQuality::new(b, 1.0)],
+        };
+
+        relay.handle_rx(a, &from_a(40), start);
+        relay.handle_tx(start);
+        relay.handle_tx(start);
+        assert_eq!(relay.routes(), &[(a, a)]);
+
+        // NOTE: A rebooted, its adverts are ignored until the old one expired
+        relay.handle_rx(a, &from_a(1), start + interval);
+        assert!(relay.handle_tx(start + interval).is_some());
+        assert!(relay.handle_tx(start + interval).is_none());
+
+        let timeout = interval * ADVERT_TIMEOUT;
+        relay.handle_rx(a, &from_a(2), start + timeout);
+        assert_eq!(advert(&relay.handle_tx(start + timeout).unwrap()).origin, b);
+        let flood = relay.handle_tx(start + timeout).unwrap();
+        assert_eq!(advert(&flood), &from_a(2));
+
+        // NOTE: A went away, so the route to it does as well
+        let later = start + timeout * 3;
+        relay.handle_tx(later);
+        assert!(relay.routes().is_empty());
+    }
+
+    // NOTE: The diamond A - {B, C} - D, where B and C are relays. Both paths
+    // are equally good, until every second packet between B and D gets lost.
+    #[test]
+    fn test_route_convergence() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut routers = [a, b, c, d].map(|id| Router::new(id, vec![b, c], interval));
+        let links = [(a, b), (a, c), (b, d), (c, d)];
+        let start = Instant::now();
+
+        for i in 0..probe_tracker::PROBE_WINDOW {
+            exchange(&mut routers, &links, start + interval * i);
+        }
+        assert_eq!(routers[0].routes(), &[(b, b), (c, c), (d, b)]);
+        assert_eq!(routers[1].routes(), &[(a, a), (d, d)]);
+        assert!(routers[0].take_routes().is_some());
+        assert!(routers[0].take_routes().is_none());
+
+        let lossy = [(a, b), (a, c), (c, d)];
+        for i in 0..2 * probe_tracker::PROBE_WINDOW {
+            let links = if i % 2 == 0 { &links[..] } else { &lossy[..] };
+            exchange(
+                &mut routers,
+                links,
+                start + interval * (probe_tracker::PROBE_WINDOW + i),
+            );
+        }
+        assert_eq!(routers[0].routes(), &[(b, b), (c, c), (d, c)]);
+        assert_eq!(routers[3].routes(), &[(a, c), (b, b), (c, c)]);
+        assert_eq!(routers[0].take_routes(), Some(vec![(b, b), (c, c), (d, c)]));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use cope_config::types::node_id::NodeID;
+
+// NOTE: Number of probe intervals over which delivery ratios are measured,
+// the ETX paper uses 10 probes as well.
+pub const PROBE_WINDOW: u32 = 10;
+
+struct Neighbor {
+    first_heard: Instant,
+    received: VecDeque<Instant>,
+}
+
+// NOTE: Measures the share of probes we received from each neighbor during
+// the last PROBE_WINDOW probe intervals, as described in the ETX paper by
+// De Couto et al. The adverts of routing::Router are used as probes.
+pub struct ProbeTracker {
+    interval: Duration,
+    neighbors: HashMap<NodeID, Neighbor>,
+}
+
+impl ProbeTracker {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            neighbors: HashMap::new(),
+        }
+    }
+
+    fn window(&self) -> Duration {
+        self.interval * PROBE_WINDOW
+    }
+
+    pub fn insert_probe(&mut self, neighbor: NodeID, now: Instant) {
+        self.neighbors
+            .entry(neighbor)
+            .or_insert_with(|| Neighbor {
+                first_heard: now,
+                received: VecDeque::new(),
+            })
+            .received
+            .push_back(now);
+    }
+
+    // NOTE: Forgets probes that left the window, and neighbors we have not
+    // heard at all during it.
+    pub fn expire(&mut self, now: Instant) {
+        let window = self.window();
+        self.neighbors.retain(|_, neighbor| {
+            while let Some(&received) = neighbor.received.front() {
+                if now.saturating_duration_since(received) < window {
+                    break;
+                }
+                neighbor.received.pop_front();
+            }
+            !neighbor.received.is_empty()
+        });
+    }
+
+    // NOTE: Neighbors we met recently are only expected to have sent the
+    // probes since then, otherwise new links would look bad for a whole window.
+    pub fn delivery_ratio(&self, neighbor: NodeID, now: Instant) -> f64 {
+        let Some(neighbor) = self.neighbors.get(&neighbor) else {
+            return 0.0;
+        };
+        let window = self.window();
+        let heard_for = now.saturating_duration_since(neighbor.first_heard);
+        let expected = match heard_for < window {
+            true => (heard_for.as_nanos() / self.interval.as_nanos()) as u32 + 1,
+            false => PROBE_WINDOW,
+        };
+        let received = neighbor
+            .received
+            .iter()
+            .filter(|&&r| now.saturating_duration_since(r) < window)
+            .count();
+        (received as f64 / expected as f64).min(1.0)
+    }
+
+    pub fn neighbors(&self) -> impl Iterator<Item = NodeID> + '_ {
+        self.neighbors.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delivery_ratio() {
+        let [a, b] = ['A', 'B'].map(NodeID::new);
+        let interval = Duration::from_millis(100);
+        let mut probes = ProbeTracker::new(interval);
+        let start = Instant::now();
+        assert_eq!(probes.delivery_ratio(a, start), 0.0);
+
+        // NOTE: Shortly after meeting A, its first probe is all we expect
+        probes.insert_probe(a, start);
+        assert_eq!(probes.delivery_ratio(a, start + interval / 2), 1.0);
+
+        // NOTE: A is heard every interval, B only every second one
+        probes.insert_probe(b, start);
+        for i in 1..PROBE_WINDOW {
+            let now = start + interval * i;
+            probes.insert_probe(a, now);
+            if i % 2 == 0 {
+                probes.insert_probe(b, now);
+            }
+        }
+        let now = start + interval * (PROBE_WINDOW - 1);
+        assert_eq!(probes.delivery_ratio(a, now), 1.0);
+        assert_eq!(probes.delivery_ratio(b, now), 0.5);
+
+        // NOTE: A went silent, so its probes leave the window one by one
+        let now = start + interval * (PROBE_WINDOW + 4);
+        assert_eq!(probes.delivery_ratio(a, now), 0.5);
+        probes.expire(start + interval * (2 * PROBE_WINDOW));
+        assert_eq!(probes.neighbors().count(), 0);
+    }
+}
@@ -0,0 +1,23 @@
+use super::{CodingScheduler, PoolState};
+
+// NOTE: Never waits, so packets are only coded together,
+// if they arrive while the relay is busy sending.
+pub struct ImmediateScheduler {}
+
+impl ImmediateScheduler {
+    pub fn new() -> Self {
+        ImmediateScheduler {}
+    }
+}
+
+impl Default for ImmediateScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodingScheduler for ImmediateScheduler {
+    fn should_transmit(&self, _state: &PoolState) -> bool {
+        true
+    }
+}
@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+use super::{CodingScheduler, PoolState};
+
+// NOTE: A coded packet holds at most one packet per nexthop, so the coding gain
+// is the largest once we have packets for every nexthop. Waits for that, but
+// not longer than max_delay.
+pub struct MaxGainScheduler {
+    max_delay: Duration,
+}
+
+impl MaxGainScheduler {
+    pub fn new(max_delay: Duration) -> Self {
+        MaxGainScheduler { max_delay }
+    }
+}
+
+impl CodingScheduler for MaxGainScheduler {
+    fn should_transmit(&self, state: &PoolState) -> bool {
+        state.waiting_nexthops >= state.nexthops || state.oldest_age >= self.max_delay
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use cope_config::types::coding_scheduler_type::CodingSchedulerType;
+
+pub mod immediate_scheduler;
+pub mod max_gain_scheduler;
+pub mod queue_length_scheduler;
+pub mod wait_for_partner_scheduler;
+
+use immediate_scheduler::ImmediateScheduler;
+use max_gain_scheduler::MaxGainScheduler;
+use queue_length_scheduler::QueueLengthScheduler;
+use wait_for_partner_scheduler::WaitForPartnerScheduler;
+
+// NOTE: What the relay knows about its packet pool, when it decides whether to send
+#[derive(Debug, Clone, Copy)]
+pub struct PoolState {
+    pub packets: usize,
+    // NOTE: Nexthops that we have packets for, and all nexthops of the relay
+    pub waiting_nexthops: usize,
+    pub nexthops: usize,
+    pub oldest_age: Duration,
+}
+
+// NOTE: Decides when the relay sends the next coded packet. Waiting longer gives
+// more packets to code together, but delays the packets that are already there.
+// It is only asked, if the pool is not empty.
+pub trait CodingScheduler {
+    fn should_transmit(&self, state: &PoolState) -> bool;
+}
+
+pub fn from_type(scheduler: CodingSchedulerType) -> Box<dyn CodingScheduler + Send> {
+    match scheduler {
+        CodingSchedulerType::Immediate => Box::new(ImmediateScheduler::new()),
+        CodingSchedulerType::WaitForPartner(max_delay) => {
+            Box::new(WaitForPartnerScheduler::new(max_delay))
+        }
+        CodingSchedulerType::QueueLength(length) => Box::new(QueueLengthScheduler::new(length)),
+        CodingSchedulerType::MaxGain(max_delay) => Box::new(MaxGainScheduler::new(max_delay)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(packets: usize, waiting_nexthops: usize, oldest_age_ms: u64) -> PoolState {
+        PoolState {
+            packets,
+            waiting_nexthops,
+            nexthops: 3,
+            oldest_age: Duration::from_millis(oldest_age_ms),
+        }
+    }
+
+    #[test]
+    fn test_policies() {
+        let delay = Duration::from_millis(100);
+        let immediate = from_type(CodingSchedulerType::Immediate);
+        let partner = from_type(CodingSchedulerType::WaitForPartner(delay));
+        let length = from_type(CodingSchedulerType::QueueLength(4));
+        let gain = from_type(CodingSchedulerType::MaxGain(delay));
+
+        let single = state(3, 1, 0);
+        assert!(immediate.should_transmit(&single));
+        assert!(!partner.should_transmit(&single));
+        assert!(!length.should_transmit(&single));
+        assert!(!gain.should_transmit(&single));
+
+        let pair = state(2, 2, 0);
+        assert!(partner.should_transmit(&pair));
+        assert!(!length.should_transmit(&pair));
+        assert!(!gain.should_transmit(&pair));
+
+        assert!(length.should_transmit(&state(4, 1, 0)));
+        assert!(gain.should_transmit(&state(3, 3, 0)));
+
+        // NOTE: No packet waits longer than the delay
+        let old = state(1, 1, 150);
+        assert!(partner.should_transmit(&old));
+        assert!(gain.should_transmit(&old));
+    }
+}
@@ -0,0 +1,24 @@
+use crate::config::CONFIG;
+
+use super::{CodingScheduler, PoolState};
+
+// NOTE: Waits until the pool holds length packets. Without a time limit packets
+// could wait forever once traffic stops, so they are sent after
+// control_packet_duration anyway.
+pub struct QueueLengthScheduler {
+    length: usize,
+}
+
+impl QueueLengthScheduler {
+    pub fn new(length: usize) -> Self {
+        // NOTE: The pool evicts packets before it gets any longer
+        let length = length.min(CONFIG.packet_pool_size);
+        QueueLengthScheduler { length }
+    }
+}
+
+impl CodingScheduler for QueueLengthScheduler {
+    fn should_transmit(&self, state: &PoolState) -> bool {
+        state.packets >= self.length || state.oldest_age >= CONFIG.control_packet_duration
+    }
+}
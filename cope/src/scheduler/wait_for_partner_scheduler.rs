@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use super::{CodingScheduler, PoolState};
+
+// NOTE: Waits until there are packets for two nexthops, so at least two packets
+// can be coded together, but not longer than max_delay.
+pub struct WaitForPartnerScheduler {
+    max_delay: Duration,
+}
+
+impl WaitForPartnerScheduler {
+    pub fn new(max_delay: Duration) -> Self {
+        WaitForPartnerScheduler { max_delay }
+    }
+}
+
+impl CodingScheduler for WaitForPartnerScheduler {
+    fn should_transmit(&self, state: &PoolState) -> bool {
+        state.waiting_nexthops >= 2 || state.oldest_age >= self.max_delay
+    }
+}
@@ -0,0 +1,523 @@
+// Optional protection of COPE packets with a pre-shared network key, see
+// network_key in the config. Coded packets are the XOR of natives from different
+// sources, so they can not be protected end-to-end by a single sender. Instead
+// there are two layers:
+//
+// Hop-by-hop: Every packet is sealed by its sender right before it is transmitted
+// and opened by the receiver, before it is handed to the coding. The data is
+// encrypted with ChaCha20-Poly1305 and all headers are authenticated as associated
+// data. The nonce is made up of the sender, an epoch chosen randomly at startup
+// and a counter, so it never repeats, even if a node restarts. Receivers keep
+// replay windows per sender and epoch, see ReplayWindow.
+//
+// End-to-end: The source of a native appends a MAC over its coding info and
+// payload to the data. Since the MAC is part of the data, it is XORed along with
+// it by the relay, so it survives coding and the final receiver can check it after
+// decoding. This also catches packets that were decoded with the wrong natives.
+//
+// NOTE: Every node of the network knows the key, so this only keeps out
+// nodes that don't. It doesn't stop nodes of the network from lying about
+// who they are.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+use blake2::digest::consts::U16;
+use blake2::digest::Mac;
+use blake2::{Blake2sMac, Blake2sMac256};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use cope_config::types::node_id::NodeID;
+
+use crate::config::CONFIG;
+use crate::packet::{wire, CodingInfo, Packet, PacketData, PacketEncodeError, Seal};
+
+pub const KEY_SIZE: usize = 32;
+pub const NATIVE_TAG_SIZE: usize = 16;
+// NOTE: Counters of a sender that are this far behind the newest one are rejected
+pub const REPLAY_WINDOW: u32 = u64::BITS;
+// NOTE: We only remember the windows of a few epochs per sender, which is
+// enough to cover the last restarts.
+const KNOWN_EPOCHS: usize = 4;
+
+const HOP_KEY_CONTEXT: &[u8] = b"COPE hop-by-hop key";
+const NATIVE_KEY_CONTEXT: &[u8] = b"COPE end-to-end key";
+
+#[derive(Debug, PartialEq)]
+pub enum SecurityError {
+    Unsealed(NodeID),
+    Replayed(NodeID, u32, u32),
+    InvalidSeal(NodeID),
+    NativeTooShort(CodingInfo),
+    InvalidNativeTag(CodingInfo),
+    Encode(PacketEncodeError),
+}
+
+impl Display for SecurityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsealed(sender) => write!(f, "packet from {} is not sealed", sender),
+            Self::Replayed(sender, epoch, counter) => write!(
+                f,
+                "packet {} of epoch {:#010x} from {} was replayed",
+                counter, epoch, sender
+            ),
+            Self::InvalidSeal(sender) => write!(f, "packet from {} has an invalid seal", sender),
+            Self::NativeTooShort(info) => write!(f, "native {} is too short for its tag", info),
+            Self::InvalidNativeTag(info) => write!(f, "native {} has an invalid tag", info),
+            Self::Encode(e) => write!(f, "could not encode packet header: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SecurityError {}
+
+impl From<PacketEncodeError> for SecurityError {
+    fn from(e: PacketEncodeError) -> Self {
+        SecurityError::Encode(e)
+    }
+}
+
+// NOTE: Derives independent keys for both layers from the network key
+fn derive_key(network_key: &[u8; KEY_SIZE], context: &[u8]) -> [u8; KEY_SIZE] {
+    let mut mac = <Blake2sMac256 as Mac>::new_from_slice(network_key).unwrap();
+    mac.update(context);
+    mac.finalize().into_bytes().into()
+}
+
+fn nonce(sender: NodeID, epoch: u32, counter: u32) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[0..4].copy_from_slice(&(sender.unwrap() as u32).to_be_bytes());
+    nonce[4..8].copy_from_slice(&epoch.to_be_bytes());
+    nonce[8..].copy_from_slice(&counter.to_be_bytes());
+    nonce.into()
+}
+
+// NOTE: Sliding window over the counters of one epoch, like in IPsec.
+// seen has bit i set, if counter newest - i was already received.
+struct EpochWindow {
+    epoch: u32,
+    newest: u32,
+    seen: u64,
+}
+
+impl EpochWindow {
+    fn new(epoch: u32, counter: u32) -> Self {
+        Self {
+            epoch,
+            newest: counter,
+            seen: 1,
+        }
+    }
+
+    fn is_fresh(&self, counter: u32) -> bool {
+        if counter > self.newest {
+            return true;
+        }
+
+        let age = self.newest - counter;
+        age < REPLAY_WINDOW && self.seen & (1 << age) == 0
+    }
+
+    fn update(&mut self, counter: u32) {
+        if counter > self.newest {
+            let shift = counter - self.newest;
+            self.seen = self.seen.checked_shl(shift).unwrap_or(0) | 1;
+            self.newest = counter;
+        } else {
+            self.seen |= 1 << (self.newest - counter);
+        }
+    }
+}
+
+// NOTE: The windows of the last epochs of one sender, the most recently
+// updated one last. Packets from an epoch we don't know can't be told apart
+// from a restart, so they get a window of their own. The epochs we know are
+// kept, so a packet replayed from a forgotten epoch can't lock out the one
+// the sender is using.
+struct ReplayWindow {
+    epochs: VecDeque<EpochWindow>,
+}
+
+impl ReplayWindow {
+    fn new(epoch: u32, counter: u32) -> Self {
+        let mut epochs = VecDeque::with_capacity(KNOWN_EPOCHS);
+        epochs.push_back(EpochWindow::new(epoch, counter));
+        Self { epochs }
+    }
+
+    fn is_fresh(&self, epoch: u32, counter: u32) -> bool {
+        let Some(window) = self.epochs.iter().find(|window| window.epoch == epoch) else {
+            return true;
+        };
+        window.is_fresh(counter)
+    }
+
+    // NOTE: Must only be called for authenticated packets, otherwise
+    // anyone could move the window.
+    fn update(&mut self, epoch: u32, counter: u32) {
+        let position = self.epochs.iter().position(|window| window.epoch == epoch);
+        let window = match position.and_then(|pos| self.epochs.remove(pos)) {
+            Some(mut window) => {
+                window.update(counter);
+                window
+            }
+            None => {
+                if self.epochs.len() == KNOWN_EPOCHS {
+                    self.epochs.pop_front();
+                }
+                EpochWindow::new(epoch, counter)
+            }
+        };
+        self.epochs.push_back(window);
+    }
+}
+
+// NOTE: Hop-by-hop layer, every node needs exactly one.
+pub struct Security {
+    cipher: ChaCha20Poly1305,
+    epoch: u32,
+    counter: u32,
+    windows: HashMap<NodeID, ReplayWindow>,
+}
+
+impl Security {
+    pub fn new(network_key: &[u8; KEY_SIZE]) -> Self {
+        let key = derive_key(network_key, HOP_KEY_CONTEXT);
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            epoch: rand::random(),
+            counter: 0,
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn from_config() -> Option<Self> {
+        CONFIG.network_key.map(|key| Self::new(&key))
+    }
+
+    fn next_nonce(&mut self) -> (u32, u32) {
+        // NOTE: Switching to a fresh epoch keeps nonces unique,
+        // once the counter runs out.
+        if self.counter == u32::MAX {
+            let old = self.epoch;
+            while self.epoch == old {
+                self.epoch = rand::random();
+            }
+            self.counter = 0;
+        }
+
+        let counter = self.counter;
+        self.counter += 1;
+        (self.epoch, counter)
+    }
+
+    pub fn seal(&mut self, packet: Packet) -> Result<Packet, SecurityError> {
+        let packet = packet.set_seal(None);
+        let associated_data = wire::encode_header(&packet)?;
+        let (epoch, counter) = self.next_nonce();
+
+        let mut data = packet.data().as_slice().to_vec();
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(
+                &nonce(packet.sender(), epoch, counter),
+                &associated_data,
+                &mut data,
+            )
+            .expect("Packet should not exceed the ChaCha20-Poly1305 size limit");
+
+        let seal = Seal {
+            epoch,
+            counter,
+            tag: tag.into(),
+        };
+        Ok(replace_data(packet, data).set_seal(Some(seal)))
+    }
+
+    pub fn open(&mut self, packet: Packet) -> Result<Packet, SecurityError> {
+        let sender = packet.sender();
+        let Some(&seal) = packet.seal() else {
+            return Err(SecurityError::Unsealed(sender));
+        };
+
+        let window = self.windows.get(&sender);
+        if window.is_some_and(|w| !w.is_fresh(seal.epoch, seal.counter)) {
+            return Err(SecurityError::Replayed(sender, seal.epoch, seal.counter));
+        }
+
+        let packet = packet.set_seal(None);
+        let associated_data = wire::encode_header(&packet)?;
+        let mut data = packet.data().as_slice().to_vec();
+        self.cipher
+            .decrypt_in_place_detached(
+                &nonce(sender, seal.epoch, seal.counter),
+                &associated_data,
+                &mut data,
+                Tag::from_slice(&seal.tag),
+            )
+            .map_err(|_| SecurityError::InvalidSeal(sender))?;
+
+        match self.windows.get_mut(&sender) {
+            Some(window) => window.update(seal.epoch, seal.counter),
+            None => {
+                self.windows
+                    .insert(sender, ReplayWindow::new(seal.epoch, seal.counter));
+            }
+        }
+
+        Ok(replace_data(packet, data))
+    }
+}
+
+// NOTE: Control packets have no data, so there is nothing to replace.
+fn replace_data(packet: Packet, data: Vec<u8>) -> Packet {
+    if data.is_empty() {
+        return packet;
+    }
+    packet
+        .set_data(PacketData::new(data))
+        .expect("Packet should stay valid, since only the data changed")
+}
+
+// NOTE: End-to-end layer, used by the sources and final receivers of natives.
+#[derive(Clone)]
+pub struct NativeAuth {
+    key: [u8; KEY_SIZE],
+}
+
+impl NativeAuth {
+    pub fn new(network_key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            key: derive_key(network_key, NATIVE_KEY_CONTEXT),
+        }
+    }
+
+    pub fn from_config() -> Option<Self> {
+        CONFIG.network_key.map(|key| Self::new(&key))
+    }
+
+    // NOTE: The length of info is ignored, because it includes the tag
+    // once the native is built. So is the nexthop, which relays rewrite.
+    fn mac(&self, info: &CodingInfo, payload: &[u8]) -> Blake2sMac<U16> {
+        let mut mac = <Blake2sMac<U16> as Mac>::new_from_slice(&self.key).unwrap();
+        mac.update(&[info.source.unwrap() as u8]);
+        mac.update(&info.id.value().to_be_bytes());
+        mac.update(&[info.destination.unwrap() as u8]);
+        mac.update(payload);
+        mac
+    }
+
+    // NOTE: Returns the payload with the tag appended.
+    pub fn tag(&self, info: &CodingInfo, payload: &PacketData) -> PacketData {
+        let tag = self.mac(info, payload.as_slice()).finalize().into_bytes();
+        let mut data = Vec::with_capacity(payload.len() + NATIVE_TAG_SIZE);
+        data.extend_from_slice(payload.as_slice());
+        data.extend_from_slice(&tag);
+        PacketData::new(data)
+    }
+
+    // NOTE: Returns the payload without the tag, if the tag is valid.
+    pub fn verify(&self, info: &CodingInfo, data: PacketData) -> Result<PacketData, SecurityError> {
+        let Some(payload_len) = data.len().checked_sub(NATIVE_TAG_SIZE) else {
+            return Err(SecurityError::NativeTooShort(info.clone()));
+        };
+
+        let (payload, tag) = data.as_slice().split_at(payload_len);
+        self.mac(info, payload)
+            .verify_slice(tag)
+            .map_err(|_| SecurityError::InvalidNativeTag(info.clone()))?;
+        Ok(data.slice(0..payload_len))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::packet::{Ack, PacketBuilder, PacketID};
+
+    const KEY: [u8; KEY_SIZE] = [7; KEY_SIZE];
+
+    fn info(source: char, id: u16, nexthop: char) -> CodingInfo {
+        CodingInfo {
+            source: NodeID::new(source),
+            id: PacketID::new(id),
+            nexthop: NodeID::new(nexthop),
+            destination: NodeID::new(nexthop),
+            len: 0,
+        }
+    }
+
+    fn native(data: Vec<u8>) -> Packet {
+        PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(info('A', 1, 'C'))
+            .ack_header(vec![])
+            .data_raw(data)
+            .build()
+            .unwrap()
+    }
+
+    // NOTE: Sends the packet over the wire, like the channels do
+    fn transfer(packet: &Packet) -> Packet {
+        Packet::deserialize_from(&packet.serialize_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_seal_round_trip() {
+        let mut a = Security::new(&KEY);
+        let mut b = Security::new(&KEY);
+
+        let packet = native(vec![1, 2, 3, 4]);
+        let sealed = a.seal(packet.clone()).unwrap();
+        assert_ne!(sealed.data(), packet.data());
+        assert_eq!(b.open(transfer(&sealed)), Ok(packet));
+
+        let control = PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .control_header(NodeID::new('B'))
+            .ack_header(vec![Ack {
+                source: NodeID::new('A'),
+                packets: vec![info('C', 3, 'A')],
+            }])
+            .build()
+            .unwrap();
+        let sealed = a.seal(control.clone()).unwrap();
+        assert_eq!(b.open(transfer(&sealed)), Ok(control));
+    }
+
+    #[test]
+    fn test_rejects_forgeries() {
+        let mut a = Security::new(&KEY);
+        let mut b = Security::new(&KEY);
+        let mut outsider = Security::new(&[8; KEY_SIZE]);
+
+        let packet = native(vec![1, 2, 3, 4]);
+        assert_eq!(
+            b.open(packet.clone()),
+            Err(SecurityError::Unsealed(NodeID::new('A')))
+        );
+        assert_eq!(
+            b.open(outsider.seal(packet.clone()).unwrap()),
+            Err(SecurityError::InvalidSeal(NodeID::new('A')))
+        );
+
+        // NOTE: Headers are authenticated, not just the data
+        let sealed = a.seal(packet).unwrap();
+        let spoofed = sealed.set_sender(NodeID::new('C'));
+        assert_eq!(
+            b.open(spoofed),
+            Err(SecurityError::InvalidSeal(NodeID::new('C')))
+        );
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut a = Security::new(&KEY);
+        let mut b = Security::new(&KEY);
+
+        let sealed: Vec<Packet> = (0..REPLAY_WINDOW + 3)
+            .map(|i| a.seal(native(vec![i as u8])).unwrap())
+            .collect();
+
+        assert!(b.open(sealed[1].clone()).is_ok());
+        assert!(b.open(sealed[0].clone()).is_ok());
+        let replayed = b.open(sealed[1].clone());
+        assert!(matches!(replayed, Err(SecurityError::Replayed(_, _, 1))));
+
+        let newest = sealed.last().unwrap().clone();
+        assert!(b.open(newest.clone()).is_ok());
+        assert!(b.open(newest).is_err());
+        // NOTE: Still in the window, even if it arrives late
+        assert!(b.open(sealed[3].clone()).is_ok());
+        // NOTE: Too old, so we can't tell whether it was received before
+        assert!(b.open(sealed[2].clone()).is_err());
+    }
+
+    #[test]
+    fn test_epochs() {
+        let mut b = Security::new(&KEY);
+        let mut before = Security::new(&KEY);
+        let mut after = Security::new(&KEY);
+        after.epoch = before.epoch.wrapping_add(1);
+
+        let old = before.seal(native(vec![1])).unwrap();
+        assert!(b.open(old.clone()).is_ok());
+
+        // NOTE: A restarted node starts over in a new epoch, the old one
+        // stays known, so its packets can't be replayed
+        assert!(b.open(after.seal(native(vec![2])).unwrap()).is_ok());
+        assert!(b.open(old).is_err());
+        assert!(b.open(after.seal(native(vec![3])).unwrap()).is_ok());
+    }
+
+    // NOTE: A packet recorded more restarts ago than we remember is accepted,
+    // but the sender can still use its current epoch afterwards.
+    #[test]
+    fn test_replay_from_forgotten_epoch() {
+        let mut b = Security::new(&KEY);
+        let mut boots: Vec<Security> = (0..KNOWN_EPOCHS + 2)
+            .map(|epoch| {
+                let mut boot = Security::new(&KEY);
+                boot.epoch = epoch as u32;
+                boot
+            })
+            .collect();
+
+        let recorded = boots[0].seal(native(vec![0])).unwrap();
+        for boot in boots.iter_mut() {
+            assert!(b.open(boot.seal(native(vec![1])).unwrap()).is_ok());
+        }
+
+        let current = boots.last_mut().unwrap();
+        let seen = current.seal(native(vec![2])).unwrap();
+        assert!(b.open(seen.clone()).is_ok());
+        assert!(b.open(recorded).is_ok());
+        assert!(b.open(current.seal(native(vec![3])).unwrap()).is_ok());
+        assert!(b.open(seen).is_err());
+    }
+
+    #[test]
+    fn test_nonce_uses_whole_sender() {
+        let low = NodeID::new('A');
+        let high = NodeID::new(char::from_u32('A' as u32 + 256).unwrap());
+        assert_ne!(nonce(low, 1, 2), nonce(high, 1, 2));
+    }
+
+    #[test]
+    fn test_native_tags_survive_coding() {
+        let auth = NativeAuth::new(&KEY);
+        let ac = info('A', 1, 'C');
+        let ca = info('C', 9, 'A');
+        let from_a = auth.tag(&ac, &PacketData::new(vec![0xAA; 10]));
+        let from_c = auth.tag(&ca, &PacketData::new(vec![0xCC; 30]));
+        assert_eq!(from_a.len(), 10 + NATIVE_TAG_SIZE);
+
+        // NOTE: What the relay sends and C decodes with its own native
+        let coded = from_a.clone().xor(&from_c);
+        let decoded = coded.xor(&from_c).truncate(from_a.len());
+        assert_eq!(
+            auth.verify(&ac, decoded).unwrap(),
+            PacketData::new(vec![0xAA; 10])
+        );
+
+        // NOTE: A sends its native to the relay, which forwards it to C
+        let to_relay = CodingInfo {
+            nexthop: NodeID::new('B'),
+            ..ac.clone()
+        };
+        let tagged = auth.tag(&to_relay, &PacketData::new(vec![0xAA; 10]));
+        assert!(auth.verify(&ac, tagged).is_ok());
+
+        assert_eq!(
+            auth.verify(&ca, from_a.clone()),
+            Err(SecurityError::InvalidNativeTag(ca))
+        );
+        let mut tampered = from_a;
+        tampered[0] ^= 1;
+        assert!(auth.verify(&ac, tampered).is_err());
+        assert_eq!(
+            auth.verify(&ac, PacketData::new(vec![0; 3])),
+            Err(SecurityError::NativeTooShort(ac.clone()))
+        );
+    }
+}
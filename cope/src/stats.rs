@@ -37,6 +37,28 @@ pub struct Stats {
     coded_received: Wrapping<u32>,
     cache_hits: Wrapping<u32>,
     cache_misses: Wrapping<u32>,
+    rejected_received: Wrapping<u32>,
+    guesses_correct: Wrapping<u32>,
+    guesses_wrong: Wrapping<u32>,
+    padding_sent: Wrapping<u32>,
+    padding_saved: Wrapping<u32>,
+    late_decoded: Wrapping<u32>,
+    pending_dropped: Wrapping<u32>,
+    nacks_sent: Wrapping<u32>,
+    recovered: Wrapping<u32>,
+    recovery_ms: Wrapping<u32>,
+    retrans_dropped: Wrapping<u32>,
+    rto_ms: Wrapping<u32>,
+    implicit_acks: Wrapping<u32>,
+    adverts_sent: Wrapping<u32>,
+    adverts_received: Wrapping<u32>,
+    route_changes: Wrapping<u32>,
+    beacons_sent: Wrapping<u32>,
+    beacons_received: Wrapping<u32>,
+    neighbor_changes: Wrapping<u32>,
+    relay_changes: Wrapping<u32>,
+    local_sent: Wrapping<u32>,
+    forwarded_sent: Wrapping<u32>,
 }
 
 impl Stats {
@@ -71,6 +93,28 @@ impl Stats {
             coded_received: Wrapping(0),
             cache_hits: Wrapping(0),
             cache_misses: Wrapping(0),
+            rejected_received: Wrapping(0),
+            guesses_correct: Wrapping(0),
+            guesses_wrong: Wrapping(0),
+            padding_sent: Wrapping(0),
+            padding_saved: Wrapping(0),
+            late_decoded: Wrapping(0),
+            pending_dropped: Wrapping(0),
+            nacks_sent: Wrapping(0),
+            recovered: Wrapping(0),
+            recovery_ms: Wrapping(0),
+            retrans_dropped: Wrapping(0),
+            rto_ms: Wrapping(CONFIG.round_trip_time.as_millis() as u32),
+            implicit_acks: Wrapping(0),
+            adverts_sent: Wrapping(0),
+            adverts_received: Wrapping(0),
+            route_changes: Wrapping(0),
+            beacons_sent: Wrapping(0),
+            beacons_received: Wrapping(0),
+            neighbor_changes: Wrapping(0),
+            relay_changes: Wrapping(0),
+            local_sent: Wrapping(0),
+            forwarded_sent: Wrapping(0),
         };
 
         if CONFIG.log_node_stats {
@@ -82,7 +126,7 @@ impl Stats {
     }
 
     fn file_header(&self) -> String {
-        "time_us,node_id,target_id,traffic_generator,data_sent,packets_sent,reports_sent,natives_sent,coded_sent,data_received,packets_received,reports_received,natives_received,decoded_received,coded_received,cache_hits,cache_misses".to_owned()
+        "time_us,node_id,target_id,traffic_generator,data_sent,packets_sent,reports_sent,natives_sent,coded_sent,data_received,packets_received,reports_received,natives_received,decoded_received,coded_received,cache_hits,cache_misses,rejected_received,guesses_correct,guesses_wrong,padding_sent,padding_saved,late_decoded,pending_dropped,nacks_sent,recovered,recovery_ms,retrans_dropped,rto_ms,implicit_acks,adverts_sent,adverts_received,route_changes,beacons_sent,beacons_received,neighbor_changes,relay_changes,local_sent,forwarded_sent".to_owned()
     }
 
     pub fn log_data(&mut self) {
@@ -93,7 +137,7 @@ impl Stats {
         self.last_log = std::time::Instant::now();
 
         let formatted = format!(
-            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
             self.creation_time.elapsed().as_micros(),
             self.own_id,
             self.target_id,
@@ -110,7 +154,29 @@ impl Stats {
             self.decoded_received,
             self.coded_received,
             self.cache_hits,
-            self.cache_misses
+            self.cache_misses,
+            self.rejected_received,
+            self.guesses_correct,
+            self.guesses_wrong,
+            self.padding_sent,
+            self.padding_saved,
+            self.late_decoded,
+            self.pending_dropped,
+            self.nacks_sent,
+            self.recovered,
+            self.recovery_ms,
+            self.retrans_dropped,
+            self.rto_ms,
+            self.implicit_acks,
+            self.adverts_sent,
+            self.adverts_received,
+            self.route_changes,
+            self.beacons_sent,
+            self.beacons_received,
+            self.neighbor_changes,
+            self.relay_changes,
+            self.local_sent,
+            self.forwarded_sent
         );
 
         self.logger.log(&formatted);
@@ -118,14 +184,19 @@ impl Stats {
 
     pub fn add_sent(&mut self, packet: &Packet) {
         // FIXME: What to do about encoded packets with multiple receivers?
-        self.target_id = packet.canonical_receiver().unwrap();
+        if let Some(receiver) = packet.canonical_receiver() {
+            self.target_id = receiver;
+        }
         self.packets_sent += 1;
         self.data_sent += packet.data().len() as u32;
+        self.nacks_sent += packet.nack_header().len() as u32;
 
         match packet.coding_header() {
             CodingHeader::Native(_) => self.natives_sent += 1,
-            CodingHeader::Encoded(_) => self.coded_sent += 1,
+            CodingHeader::Encoded(_) | CodingHeader::Linear(_) => self.coded_sent += 1,
             CodingHeader::Control(_) => self.reports_sent += 1,
+            CodingHeader::Routing(_) => self.adverts_sent += 1,
+            CodingHeader::Beacon(_) => self.beacons_sent += 1,
         };
     }
 
@@ -145,9 +216,92 @@ impl Stats {
             (CodingHeader::Encoded(_), true) => self.decoded_received += 1,
             (CodingHeader::Encoded(_), false) => self.coded_received += 1,
             (CodingHeader::Control(_), _) => self.reports_received += 1,
+            // NOTE: RLNC decodes whole generations at once, which is when data is returned
+            (CodingHeader::Linear(h), true) if h.nexthop == self.own_id && data_size > 0 => {
+                self.decoded_received += 1
+            }
+            (CodingHeader::Linear(_), _) => self.coded_received += 1,
+            (CodingHeader::Routing(_), _) => self.adverts_received += 1,
+            (CodingHeader::Beacon(_), _) => self.beacons_received += 1,
         };
     }
 
+    // NOTE: Packets that failed authentication, see security::Security
+    pub fn add_rejected(&mut self, sender: NodeID) {
+        self.target_id = sender;
+        self.rejected_received += 1;
+    }
+
+    // NOTE: Outcomes of coding decisions that relied on a guess, see kbase::LinkEstimator
+    pub fn add_guesses(&mut self, correct: u32, wrong: u32) {
+        self.guesses_correct += correct;
+        self.guesses_wrong += wrong;
+    }
+
+    // NOTE: Bytes of zero padding in coded packets, and how many bytes coding
+    // packets of similar size saved, see packet_pool::VirtualQueuePool
+    pub fn add_padding(&mut self, sent: u32, saved: u32) {
+        self.padding_sent += sent;
+        self.padding_saved += saved;
+    }
+
+    // NOTE: Coded packets that were decoded after they were buffered, see
+    // coding::pending_store::PendingStore. They count as decoded as well.
+    pub fn add_late_decoded(&mut self, packets: u32, data_size: u32) {
+        self.late_decoded += packets;
+        self.decoded_received += packets;
+        self.data_received += data_size;
+    }
+
+    pub fn add_pending_dropped(&mut self, packets: u32) {
+        self.pending_dropped += packets;
+    }
+
+    // NOTE: Pending packets whose native arrived after all, either through late
+    // decoding, a NACK or a retransmission. recovery_ms / recovered is the average
+    // time it took, which NACKs are meant to reduce.
+    pub fn add_recovered(&mut self, packets: u32, time: std::time::Duration) {
+        self.recovered += packets;
+        self.recovery_ms += time.as_millis() as u32;
+    }
+
+    // NOTE: Packets that were not acked after max_retrans_amount retransmissions,
+    // and the current retransmission timeout, see coding::rtt_estimator::RttEstimator
+    pub fn add_retrans_dropped(&mut self, packets: u32) {
+        self.retrans_dropped += packets;
+    }
+
+    pub fn set_rto(&mut self, rto: std::time::Duration) {
+        self.rto_ms = Wrapping(rto.as_millis() as u32);
+    }
+
+    // NOTE: Packets that were acked by overhearing the relay forward them
+    pub fn add_implicit_acks(&mut self, packets: u32) {
+        self.implicit_acks += packets;
+    }
+
+    // NOTE: Destinations whose nexthop changed, see routing::Router
+    pub fn add_route_changes(&mut self, changes: u32) {
+        self.route_changes += changes;
+    }
+
+    // NOTE: Neighbors that appeared or vanished, see discovery::Discovery
+    pub fn add_neighbor_changes(&mut self, changes: u32) {
+        self.neighbor_changes += changes;
+    }
+
+    // NOTE: Newly elected relays, see election::RelayElection
+    pub fn add_relay_changes(&mut self, changes: u32) {
+        self.relay_changes += changes;
+    }
+
+    // NOTE: Natives a relay coded for the first time, split into its own and
+    // forwarded ones, see FairnessType
+    pub fn add_relay_natives(&mut self, local: u32, forwarded: u32) {
+        self.local_sent += local;
+        self.forwarded_sent += forwarded;
+    }
+
     // TODO: call these functions from inside the cache.
     // I think the 26 branch has an abstraction for the cache, so it should go there.
     pub fn add_cache_hit(&mut self, node: &NodeID) {
@@ -3,52 +3,137 @@ use std::vec::Vec;
 
 pub struct Topology {
     id: NodeID,
-    relay: NodeID,
+    relays: Vec<NodeID>,
+    // NOTE: Nexthop for each destination, see nexthop_for_target
+    routes: Vec<(NodeID, NodeID)>,
     rxlist: Vec<NodeID>,
     txlist: Vec<NodeID>,
+    // NOTE: Static filters for discovered neighbors, see set_neighbors
+    rx_allowlist: Vec<NodeID>,
+    tx_allowlist: Vec<NodeID>,
+    denylist: Vec<NodeID>,
 }
 
 impl Topology {
     pub fn new(id: NodeID, relay: NodeID, rxlist: Vec<NodeID>, txlist: Vec<NodeID>) -> Topology {
         return Topology {
             id,
-            relay,
+            relays: vec![relay],
+            routes: vec![],
             rxlist,
             txlist,
+            rx_allowlist: vec![],
+            tx_allowlist: vec![],
+            denylist: vec![],
         };
     }
 
+    // NOTE: Replaces the relay given to new(), the first relay is the default
+    // nexthop of leaves.
+    pub fn with_relays(mut self, relays: Vec<NodeID>) -> Self {
+        assert!(!relays.is_empty(), "There has to be at least one relay");
+        self.relays = relays;
+        self
+    }
+
+    // NOTE: Replaces the relays, when a new one was elected by election::RelayElection
+    pub fn set_relays(&mut self, relays: Vec<NodeID>) {
+        assert!(!relays.is_empty(), "There has to be at least one relay");
+        self.relays = relays;
+    }
+
+    pub fn with_routes(mut self, routes: Vec<(NodeID, NodeID)>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    // NOTE: Empty allowlists don't filter, nodes in the denylist are never
+    // accepted, not even from the lists given to new().
+    pub fn with_filters(
+        mut self,
+        rx_allowlist: Vec<NodeID>,
+        tx_allowlist: Vec<NodeID>,
+        denylist: Vec<NodeID>,
+    ) -> Self {
+        self.rx_allowlist = rx_allowlist;
+        self.tx_allowlist = tx_allowlist;
+        self.denylist = denylist;
+        self
+    }
+
+    // NOTE: Replaces both lists, when they were learned by discovery::Discovery
+    pub fn set_neighbors(&mut self, rxlist: Vec<NodeID>, txlist: Vec<NodeID>) {
+        let denylist = &self.denylist;
+        let is_allowed = |allowlist: &[NodeID], id: &NodeID| {
+            !denylist.contains(id) && (allowlist.is_empty() || allowlist.contains(id))
+        };
+        self.rxlist = rxlist
+            .into_iter()
+            .filter(|id| is_allowed(&self.rx_allowlist, id))
+            .collect();
+        self.txlist = txlist
+            .into_iter()
+            .filter(|id| is_allowed(&self.tx_allowlist, id))
+            .collect();
+    }
+
+    // NOTE: Replaces all routes, when they were learned by routing::Router
+    pub fn set_routes(&mut self, routes: Vec<(NodeID, NodeID)>) {
+        self.routes = routes;
+    }
+
     pub fn is_relay(&self) -> bool {
-        self.id == self.relay
+        self.is_relay_node(self.id)
     }
 
-    pub fn relay(&self) -> NodeID {
-        self.relay
+    pub fn is_relay_node(&self, id: NodeID) -> bool {
+        self.relays.contains(&id)
+    }
+
+    pub fn is_denied(&self, id: NodeID) -> bool {
+        self.denylist.contains(&id)
     }
 
     pub fn can_receive_from(&self, id: NodeID) -> bool {
-        return self.rxlist.contains(&id);
+        return !self.is_denied(id) && self.rxlist.contains(&id);
     }
 
     pub fn can_send_to(&self, id: NodeID) -> bool {
-        return self.txlist.contains(&id);
+        return !self.is_denied(id) && self.txlist.contains(&id);
     }
 
     pub fn nexthop_for_target(&self, id: NodeID) -> NodeID {
-        // NOTE: Because we only focus on the star topology, we have two paths:
-        // 1. If we are the relay, we know that the next hop will be the target
+        if let Some(&(_, nexthop)) = self.routes.iter().find(|(target, _)| *target == id) {
+            return nexthop;
+        }
+
+        // NOTE: Without a route, we fall back to the star topology:
+        // 1. If we are a relay, we know that the next hop will be the target
         // 2. Otherwise, we have to send the packet to the relay
-        if self.id == self.relay {
+        if self.is_relay() {
             return id;
         } else {
-            return self.relay;
+            return self.relays[0];
         }
     }
 
+    // NOTE: Control packets need some receiver, see Packet::canonical_receiver.
+    // With discovery, the txlist may still be empty, and the acks are meant for
+    // the nodes we receive from anyway.
+    pub fn control_receiver(&self) -> NodeID {
+        self.txlist
+            .iter()
+            .chain(&self.rxlist)
+            .next()
+            .copied()
+            .unwrap_or(self.relays[0])
+    }
+
     pub fn id(&self) -> NodeID {
         self.id
     }
 
+    // NOTE: Neighbors for relays, destinations of the traffic generator otherwise
     pub fn txlist(&self) -> &[NodeID] {
         self.txlist.as_ref()
     }
@@ -102,4 +187,41 @@ mod test {
             NodeID::new('C')
         );
     }
+
+    #[test]
+    fn test_discovered_neighbors() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let mut topology =
+            Topology::new(a, b, vec![], vec![]).with_filters(vec![], vec![c], vec![d]);
+        assert!(!topology.can_receive_from(b));
+
+        topology.set_neighbors(vec![b, c, d], vec![c, d]);
+        assert!(topology.can_receive_from(b));
+        assert!(topology.can_receive_from(c));
+        assert!(!topology.can_receive_from(d));
+        assert_eq!(topology.txlist(), &[c]);
+
+        topology.set_neighbors(vec![c], vec![]);
+        assert!(!topology.can_receive_from(b));
+        assert!(topology.txlist().is_empty());
+    }
+
+    // NOTE: The chain A - B - C - D with the relays B and C
+    #[test]
+    fn test_nexthop_route() {
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+        let leaf = Topology::new(a, b, vec![b], vec![d])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(d, b)]);
+        let relay = Topology::new(b, b, vec![a, c], vec![a, c])
+            .with_relays(vec![b, c])
+            .with_routes(vec![(d, c)]);
+
+        assert!(!leaf.is_relay());
+        assert!(leaf.is_relay_node(c));
+        assert_eq!(leaf.nexthop_for_target(d), b);
+        assert!(relay.is_relay());
+        assert_eq!(relay.nexthop_for_target(d), c);
+        assert_eq!(relay.nexthop_for_target(a), a);
+    }
 }
@@ -2,6 +2,7 @@ use cope_config::types::{node_id::NodeID, traffic_generator_type::TrafficGenerat
 
 use super::packet::PacketBuilder;
 use crate::packet::{CodingInfo, PacketID};
+use crate::topology::Topology;
 
 pub mod data_generator;
 pub mod greedy_strategy;
@@ -38,13 +39,12 @@ impl TrafficGenerator {
         tx_whitelist: Vec<NodeID>,
         sender_id: NodeID,
     ) -> Self {
-        assert_ne!(tx_whitelist.len(), 0, "tx_whitelist.len() should not be 0");
         TrafficGenerator {
             strategy,
             tx_whitelist,
             current_tx_id: 0,
             sender_id,
-            current_packet_id: 0,
+            current_packet_id: PacketID::default(),
         }
     }
 
@@ -63,6 +63,15 @@ impl TrafficGenerator {
         TrafficGenerator::new(strategy, tx_whitelist.clone(), sender_id)
     }
 
+    // NOTE: Replaces the receivers, when they were learned by discovery::Discovery
+    pub fn set_tx_whitelist(&mut self, tx_whitelist: Vec<NodeID>) {
+        self.tx_whitelist = tx_whitelist;
+    }
+
+    pub fn sender_id(&self) -> NodeID {
+        self.sender_id
+    }
+
     pub fn next_receiver(&mut self) -> NodeID {
         self.current_tx_id = if self.current_tx_id + 1 < self.tx_whitelist.len() {
             self.current_tx_id + 1
@@ -73,16 +82,24 @@ impl TrafficGenerator {
     }
 
     pub fn next_packet_id(&mut self) -> PacketID {
-        self.current_packet_id = self.current_packet_id.checked_add(1).unwrap_or(0);
+        self.current_packet_id = self.current_packet_id.next();
         self.current_packet_id
     }
 
-    pub fn generate(&mut self) -> Option<PacketBuilder> {
+    pub fn generate(&mut self, topology: &Topology) -> Option<PacketBuilder> {
+        // NOTE: Until discovery found a receiver, there is nobody to send to
+        if self.tx_whitelist.is_empty() {
+            return None;
+        }
         self.strategy.generate().map(|builder| {
+            let destination = self.next_receiver();
             builder.sender(self.sender_id).native_header(CodingInfo {
                 source: self.sender_id,
                 id: self.next_packet_id(),
-                nexthop: self.next_receiver(),
+                nexthop: topology.nexthop_for_target(destination),
+                destination,
+                // NOTE: Filled in by PacketBuilder::build()
+                len: 0,
             })
         })
     }
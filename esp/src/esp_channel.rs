@@ -1,14 +1,14 @@
 use crate::esp_stats_logger::EspStatsLogger;
-use crate::espnow_frame::{EspNowDecodingError, EspNowFrame, ESPNOW_HEADER_SIZE};
 use crate::espnow_stats::EspNowStats;
-use crate::frame_collection::{Frame, FrameCollection, FrameCollectionError, FrameError};
 use crate::promiscuous_wifi;
-use crate::wifi_frame::{WifiFrame, WIFI_HEADER_SIZE};
 use cope::channel::Channel;
 use cope::config::CONFIG;
-use cope::packet::Packet;
+use cope::packet::{Packet, PacketEncodeError};
 use cope::stats::StatsLogger;
 use cope_config::types::{mac_address::MacAddress, node_id::NodeID};
+use cope_frames::espnow_frame::{EspNowDecodingError, EspNowFrame, ESPNOW_HEADER_SIZE};
+use cope_frames::frame_collection::{Frame, FrameCollection, FrameCollectionError, FrameError};
+use cope_frames::wifi_frame::{WifiFrame, WIFI_HEADER_SIZE};
 use esp_idf_svc::sys::EspError;
 use esp_idf_svc::{
     espnow::{EspNow, PeerInfo, SendStatus},
@@ -39,7 +39,7 @@ const ESPNOW_FRAME_SIZE: u8 = 250;
 pub enum EspChannelError {
     UnknownReceiver,
     UnicastPeerError(MacAddress, EspError),
-    SerializationError(bincode::Error),
+    SerializationError(PacketEncodeError),
     FrameEncodingError(FrameCollectionError),
     _FrameDecodingError(FrameError),
     // FIXME: Stupid name, try to find a better one
@@ -60,28 +60,20 @@ impl std::fmt::Display for EspChannelError {
                 write!(f, "could not serialize packet: {}", e)
             }
             EspChannelError::FrameEncodingError(e) => {
-                // FIXME: Implement Display trait for FrameCollectionError
                 write!(
                     f,
-                    "could not encode serialized packet into partial packet frames: {:?}",
+                    "could not encode serialized packet into partial packet frames: {}",
                     e
                 )
             }
             EspChannelError::_FrameDecodingError(e) => {
-                // FIXME: Implement Display trait for FrameError
-                write!(f, "could not decode partial packet frame: {:?}", e)
+                write!(f, "could not decode partial packet frame: {}", e)
             }
             EspChannelError::_PacketDecodingError(e) => {
-                // FIXME: Implement Display trait for FrameCollectionError
-                write!(
-                    f,
-                    "could not add partial packet frame to collection: {:?}",
-                    e
-                )
+                write!(f, "could not add partial packet frame to collection: {}", e)
             }
             EspChannelError::_EspNowFrameDecodingError(e) => {
-                // FIXME: Implement Display trait for EspNowDecodingError
-                write!(f, "could not decode bytes into EspNow frame: {:?}", e)
+                write!(f, "could not decode bytes into EspNow frame: {}", e)
             }
             EspChannelError::EspNowTransmissionError(e) => {
                 write!(f, "could not transmit EspNow frame: {}", e)
@@ -236,6 +228,10 @@ impl EspChannel {
             }
         }
 
+        // NOTE: Broadcasts are sent to the broadcast MAC, which has to be added
+        // as a peer like any other receiver.
+        self.add_unicast_peer(&MacAddress::broadcast())?;
+
         Ok(())
     }
 
@@ -267,12 +263,13 @@ impl Channel for EspChannel {
     fn transmit(&mut self, packet: &Packet) -> Result<(), Box<dyn Error>> {
         self.stats.lock().unwrap().log_data();
 
-        let receiver = match packet.canonical_receiver() {
+        let mac = match packet.canonical_receiver() {
+            Some(receiver) => self.mac_map.get(&receiver).copied(),
+            None if packet.is_broadcast() => Some(MacAddress::broadcast()),
             None => return Err(Box::new(EspChannelError::UnknownReceiver)),
-            Some(r) => r,
         };
 
-        if let Some(mac) = self.mac_map.get(&receiver) {
+        if let Some(mac) = &mac {
             if !(self.is_unicast_peer_added(mac)) {
                 log::warn!(
                     "Peer {} should have already been added. Is the peer part of the config?",
@@ -390,12 +387,12 @@ impl Channel for EspChannel {
             let wifi_frame: WifiFrame = match raw_frame.as_slice().try_into() {
                 Ok(f) => f,
                 Err(e) => {
-                    log::error!("Could not decode raw WiFi frame: {:?}", e);
+                    log::error!("Could not decode raw WiFi frame: {}", e);
                     self.stats.lock().unwrap().add_raw_frame_dropped();
                     self.stats
                         .lock()
                         .unwrap()
-                        .add_raw_data_dropped(raw_frame.len() - WIFI_HEADER_SIZE);
+                        .add_raw_data_dropped(raw_frame.len().saturating_sub(WIFI_HEADER_SIZE));
                     continue;
                 }
             };
@@ -447,16 +444,28 @@ impl Channel for EspChannel {
                 );
             }
 
-            let entry = self
-                .frame_collection_pool
-                .get_mut(&frame.get_magic())
-                .unwrap();
+            let magic = frame.get_magic();
+            let entry = self.frame_collection_pool.get_mut(&magic).unwrap();
 
             if let Err(e) = entry.1.add_frame(frame) {
                 log::warn!("Could not add split frame to collection: {:?}", e);
                 continue;
             }
 
+            // NOTE: Frames can be sent by anyone in range, so we stop collecting as soon
+            // as a packet grows larger than we would ever be able to decode.
+            let collected_size = entry.1.total_size();
+            if collected_size > CONFIG.max_packet_size {
+                log::warn!("Dropping frame collection, because it exceeds the maximum packet size.");
+                self.frame_collection_pool.remove(&magic);
+                self.stats.lock().unwrap().add_packet_dropped();
+                self.stats
+                    .lock()
+                    .unwrap()
+                    .add_packet_data_dropped(collected_size);
+                continue;
+            }
+
             // NOTE: We reset the collection timestamp here, since a frame indicates that
             // the packet is still in transit. If we would only set the timestamp for the
             // first frame, we could prematurely drop large packets or packets sent during
@@ -3,12 +3,9 @@
 
 mod esp_channel;
 mod esp_stats_logger;
-mod espnow_frame;
 mod espnow_stats;
-mod frame_collection;
 mod promiscuous_wifi;
 mod test_runner;
-mod wifi_frame;
 
 use std::time::Duration;
 
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use cope::packet::{Ack, CodingHeader, Packet, Seal};
+use cope_config::types::node_id::NodeID;
+use cope_frames::espnow_frame::EspNowFrame;
+use cope_frames::frame_collection::{Frame, FrameCollection};
+use cope_frames::wifi_frame::WifiFrame;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+// NOTE: The layer the input bytes start at. Decoding always continues
+// down to the packet, if possible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    Wifi,
+    EspNow,
+    Frame,
+    Packet,
+}
+
+impl FromStr for Layer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wifi" => Ok(Self::Wifi),
+            "espnow" => Ok(Self::EspNow),
+            "frame" => Ok(Self::Frame),
+            "packet" => Ok(Self::Packet),
+            _ => Err(format!(
+                "Unknown layer {}, expected one of wifi, espnow, frame or packet",
+                s
+            )),
+        }
+    }
+}
+
+// NOTE: Everything that could be decoded from a single input, in order.
+// If decoding failed, error describes at which layer and why.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub layers: Vec<(&'static str, Value)>,
+    pub error: Option<String>,
+}
+
+impl Report {
+    fn push<T: Serialize>(&mut self, name: &'static str, layer: &T) {
+        let value = serde_json::to_value(layer).unwrap_or_else(|e| json!(e.to_string()));
+        self.layers.push((name, value));
+    }
+
+    fn fail(mut self, layer: &str, error: impl std::fmt::Display) -> Self {
+        self.error = Some(format!("could not decode {}: {}", layer, error));
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut object: serde_json::Map<String, Value> = self
+            .layers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        if let Some(error) = &self.error {
+            object.insert("error".into(), json!(error));
+        }
+        Value::Object(object)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![];
+        for (name, value) in &self.layers {
+            lines.push(format!("{}:", name));
+            format_value(value, 1, &mut lines);
+        }
+        if let Some(error) = &self.error {
+            lines.push(format!("error: {}", error));
+        }
+        lines.join("\n")
+    }
+}
+
+fn format_value(value: &Value, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(_) => {
+                        lines.push(format!("{}{}:", indent, key));
+                        format_value(value, depth + 1, lines);
+                    }
+                    _ => lines.push(format!("{}{}: {}", indent, key, value)),
+                }
+            }
+        }
+        _ => lines.push(format!("{}{}", indent, value)),
+    }
+}
+
+#[derive(Serialize)]
+struct ReportView {
+    source: NodeID,
+    last_id: u16,
+    ids: Vec<u16>,
+}
+
+#[derive(Serialize)]
+struct PacketView<'a> {
+    summary: String,
+    sender: NodeID,
+    coding_header: &'a CodingHeader,
+    reception_header: Vec<ReportView>,
+    ack_header: &'a [Ack],
+    // NOTE: The data of sealed packets is encrypted
+    seal: Option<&'a Seal>,
+    data_len: usize,
+    data: String,
+}
+
+impl<'a> From<&'a Packet> for PacketView<'a> {
+    fn from(packet: &'a Packet) -> Self {
+        Self {
+            summary: packet.to_string(),
+            sender: packet.sender(),
+            coding_header: packet.coding_header(),
+            reception_header: packet
+                .reception_header()
+                .iter()
+                .map(|report| ReportView {
+                    source: report.source(),
+                    last_id: report.last_id().value(),
+                    ids: report.ids().iter().map(|id| id.value()).collect(),
+                })
+                .collect(),
+            ack_header: packet.ack_header(),
+            seal: packet.seal(),
+            data_len: packet.data().len(),
+            data: to_hex(packet.data().as_slice()),
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// NOTE: Accepts hex dumps in most common formats, e.g. "de ad be ef",
+// "0xde, 0xad", "de:ad:be:ef" or "deadbeef".
+pub fn parse_hex(input: &str) -> Result<Vec<u8>, String> {
+    let digits: String = input
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':' || c == '-')
+        .map(|token| token.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits ({})", digits.len()));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex byte {:?}", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+// NOTE: Packets are usually split into multiple frames, so frames are
+// collected by their magic until the packet is complete.
+#[derive(Default)]
+pub struct Inspector {
+    collections: BTreeMap<u32, FrameCollection>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inspect(&mut self, bytes: &[u8], layer: Layer) -> Report {
+        let mut report = Report::default();
+
+        let mut bytes = bytes.to_vec();
+        if layer == Layer::Wifi {
+            let wifi = match WifiFrame::try_from(bytes.as_slice()) {
+                Ok(f) => f,
+                Err(e) => return report.fail("WiFi frame", e),
+            };
+            report.push("wifi", &wifi);
+            bytes = wifi.get_data().to_vec();
+        }
+
+        if matches!(layer, Layer::Wifi | Layer::EspNow) {
+            let espnow = match EspNowFrame::try_from(bytes.as_slice()) {
+                Ok(f) => f,
+                Err(e) => return report.fail("EspNow frame", e),
+            };
+            report.push("espnow", &espnow);
+            bytes = espnow.get_body().to_vec();
+        }
+
+        if layer != Layer::Packet {
+            let frame = match Frame::try_from(bytes.as_slice()) {
+                Ok(f) => f,
+                Err(e) => return report.fail("frame", e),
+            };
+            report.push("frame", &frame);
+
+            let magic = frame.get_magic();
+            let collection = self
+                .collections
+                .entry(magic)
+                .or_insert_with(FrameCollection::new);
+            if let Err(e) = collection.add_frame(frame) {
+                self.collections.remove(&magic);
+                return report.fail("frame collection", e);
+            }
+
+            if !collection.is_complete() {
+                let received = collection.iter().filter(|f| f.is_some()).count();
+                report.push(
+                    "collection",
+                    &json!({ "received": received, "total": collection.len() }),
+                );
+                return report;
+            }
+
+            let collection = self.collections.remove(&magic).unwrap();
+            bytes = match collection.decode() {
+                Ok(b) => b,
+                Err(e) => return report.fail("frame collection", e),
+            };
+        }
+
+        match Packet::deserialize_from(&bytes) {
+            Ok(packet) => report.push("packet", &PacketView::from(&packet)),
+            Err(e) => return report.fail("packet", e),
+        }
+        report
+    }
+
+    // NOTE: Magics of packets, for which not all frames were seen
+    pub fn incomplete(&self) -> Vec<u32> {
+        self.collections.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cope::packet::{CodingInfo, PacketBuilder, PacketID};
+    use cope_frames::wifi_frame::WIFI_HEADER_SIZE;
+
+    use super::*;
+
+    fn packet() -> Packet {
+        PacketBuilder::new()
+            .sender(NodeID::new('A'))
+            .native_header(CodingInfo {
+                source: NodeID::new('A'),
+                id: PacketID::new(7),
+                nexthop: NodeID::new('C'),
+                destination: NodeID::new('C'),
+                len: 0,
+            })
+            .ack_header(vec![])
+            .data_raw((0..100).collect())
+            .build()
+            .unwrap()
+    }
+
+    // NOTE: Wraps a frame the same way the ESP receives it in promiscuous mode
+    fn wifi_frame(body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; WIFI_HEADER_SIZE];
+        bytes.extend([0xd0, 0x00, 0x00, 0x00]);
+        bytes.extend([0x11; 6]);
+        bytes.extend([0x22; 6]);
+        bytes.extend([0xff; 6]);
+        bytes.extend([0x00, 0x00, 127, 0x18, 0xfe, 0x34]);
+        bytes.extend([0x01, 0x02, 0x03, 0x04]);
+        bytes.extend([221, body.len() as u8 + 5, 0x18, 0xfe, 0x34, 4, 1]);
+        bytes.extend(body);
+        bytes.extend([0; 4]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("de ad:BE-ef"), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse_hex("0xde, 0x0A\n"), Ok(vec![0xde, 0x0a]));
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_inspect_packet() {
+        let packet = packet();
+        let bytes = packet.serialize_into().unwrap();
+
+        let report = Inspector::new().inspect(&bytes, Layer::Packet);
+        assert!(report.error.is_none(), "{:?}", report.error);
+        let json = report.to_json();
+        assert_eq!(json["packet"]["sender"], "A");
+        assert_eq!(json["packet"]["coding_header"]["Native"]["id"], 7);
+        assert_eq!(json["packet"]["data_len"], 100);
+    }
+
+    #[test]
+    fn test_inspect_split_wifi_frames() {
+        let bytes = packet().serialize_into().unwrap();
+        let mut collection = FrameCollection::new().with_frame_size(64).unwrap();
+        collection.encode(&bytes).unwrap();
+        let frames: Vec<Vec<u8>> = collection
+            .iter()
+            .map(|f| wifi_frame(&Into::<Vec<u8>>::into(f.clone().unwrap())))
+            .collect();
+        assert!(frames.len() > 1);
+
+        let mut inspector = Inspector::new();
+        for frame in &frames[..frames.len() - 1] {
+            let report = inspector.inspect(frame, Layer::Wifi);
+            assert!(report.error.is_none(), "{:?}", report.error);
+            assert!(report.to_json().get("packet").is_none());
+            assert_eq!(
+                report.to_json()["espnow"]["mac_header"]["source"],
+                "22:22:22:22:22:22"
+            );
+        }
+        assert_eq!(inspector.incomplete().len(), 1);
+
+        let report = inspector.inspect(frames.last().unwrap(), Layer::Wifi);
+        assert!(report.error.is_none(), "{:?}", report.error);
+        assert_eq!(report.to_json()["packet"]["data_len"], 100);
+        assert!(inspector.incomplete().is_empty());
+        assert!(report.to_text().contains("\npacket:\n"));
+        assert!(report.to_text().contains("\n  summary: \"Native<7@A -> C>"));
+    }
+
+    #[test]
+    fn test_inspect_invalid_input() {
+        let report = Inspector::new().inspect(&[0; 10], Layer::Wifi);
+        assert!(report.layers.is_empty());
+        assert!(report
+            .error
+            .unwrap()
+            .starts_with("could not decode WiFi frame"));
+
+        let report = Inspector::new().inspect(&wifi_frame(&[]), Layer::Wifi);
+        assert_eq!(report.layers.len(), 2);
+        assert!(report.error.unwrap().starts_with("could not decode frame"));
+    }
+}
@@ -0,0 +1,83 @@
+// Decodes raw bytes, e.g. hex dumps from the ESP serial console, layer by layer:
+// promiscuous mode buffer (WifiFrame), EspNowFrame, partial packet Frame and
+// finally the COPE Packet, once all of its frames have been seen.
+//
+// Usage: inspector [--json] [--layer wifi|espnow|frame|packet] [HEX...]
+//
+// Every HEX argument is decoded as a single input. Without arguments, every
+// line read from stdin is one input.
+
+use std::io::BufRead;
+
+use inspect::{parse_hex, Inspector, Layer};
+
+mod inspect;
+
+struct Options {
+    json: bool,
+    layer: Layer,
+    inputs: Vec<String>,
+}
+
+fn parse_args() -> anyhow::Result<Options> {
+    let mut options = Options {
+        json: false,
+        layer: Layer::Wifi,
+        inputs: vec![],
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--layer" => {
+                let Some(layer) = args.next() else {
+                    anyhow::bail!("--layer expects one of wifi, espnow, frame or packet");
+                };
+                options.layer = layer.parse().map_err(anyhow::Error::msg)?;
+            }
+            "-h" | "--help" => {
+                println!("Usage: inspector [--json] [--layer wifi|espnow|frame|packet] [HEX...]");
+                std::process::exit(0);
+            }
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    Ok(options)
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = parse_args()?;
+    let inputs: Box<dyn Iterator<Item = String>> = match options.inputs.is_empty() {
+        true => Box::new(std::io::stdin().lock().lines().map_while(Result::ok)),
+        false => Box::new(options.inputs.into_iter()),
+    };
+
+    let mut inspector = Inspector::new();
+    for (i, input) in inputs.enumerate() {
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        let report = match parse_hex(&input) {
+            Ok(bytes) => inspector.inspect(&bytes, options.layer),
+            Err(e) => {
+                eprintln!("Input {}: {}", i, e);
+                continue;
+            }
+        };
+
+        if options.json {
+            println!("{}", report.to_json());
+        } else {
+            println!("=== Input {} ===\n{}\n", i, report.to_text());
+        }
+    }
+
+    for magic in inspector.incomplete() {
+        eprintln!("Packet with magic {:#010x} is missing frames.", magic);
+    }
+
+    Ok(())
+}
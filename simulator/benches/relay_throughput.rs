@@ -0,0 +1,183 @@
+// Measures how many packets the relay can forward per second, when frames are
+// passed around like in the simulator: A and C send natives to the relay B, which
+// codes them and transmits the result to both leaves.
+//
+// The frames are fanned out to the receivers either as a handle to one shared
+// buffer, like the simulator does, or as a deep copy per receiver, like it did
+// before packet data was reference counted.
+//
+// Run with: cargo bench --bench relay_throughput
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::channel::Channel;
+use cope::coding::relay_node_coding::RelayNodeCoding;
+use cope::coding::CodingStrategy;
+use cope::packet::{Ack, CodingHeader, CodingInfo, PacketBuilder, PacketData, PacketID};
+use cope::topology::Topology;
+use cope_config::types::node_id::NodeID;
+
+#[path = "../src/simulator_channel.rs"]
+mod simulator_channel;
+
+use simulator_channel::{SimulatorChannel, SimulatorFrame};
+
+const PAYLOAD_SIZES: [usize; 3] = [64, 512, 1400];
+const RUNTIME: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy)]
+enum FanOut {
+    Shared,
+    Copied,
+}
+
+struct Leaf {
+    id: NodeID,
+    tx: Sender<SimulatorFrame>,
+    channel: SimulatorChannel,
+}
+
+fn native(source: NodeID, target: NodeID, id: u16, size: usize) -> PacketData {
+    let packet = PacketBuilder::new()
+        .sender(source)
+        .native_header(CodingInfo {
+            source,
+            id: PacketID::new(id),
+            nexthop: NodeID::new('B'),
+            destination: target,
+            // NOTE: Filled in by PacketBuilder::build()
+            len: 0,
+        })
+        .ack_header(vec![])
+        .data_raw(vec![id as u8; size])
+        .build()
+        .unwrap();
+    PacketData::new(packet.serialize_into().unwrap())
+}
+
+fn ack(leaf: NodeID, packets: Vec<CodingInfo>) -> PacketData {
+    let packet = PacketBuilder::new()
+        .sender(leaf)
+        .control_header(NodeID::new('B'))
+        .ack_header(vec![Ack {
+            source: leaf,
+            packets,
+        }])
+        .build()
+        .unwrap();
+    PacketData::new(packet.serialize_into().unwrap())
+}
+
+fn forward(frame: &SimulatorFrame, leaves: &[Leaf], fan_out: FanOut) {
+    for leaf in leaves.iter().filter(|leaf| leaf.id != frame.0) {
+        let buffer = match fan_out {
+            FanOut::Shared => frame.1.clone(),
+            FanOut::Copied => PacketData::new(frame.1.as_slice().to_vec()),
+        };
+        leaf.tx.send((frame.0, buffer)).unwrap();
+    }
+}
+
+// NOTE: Returns the number of coded packets and payload bytes, that were
+// received by the leaves.
+fn run(payload_size: usize, fan_out: FanOut) -> (u64, u64, Duration) {
+    let node_a = NodeID::new('A');
+    let node_b = NodeID::new('B');
+    let node_c = NodeID::new('C');
+
+    let (relay_tx, relay_rx) = channel();
+    let (medium_tx, medium_rx): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+    let mut relay_channel = SimulatorChannel::new(relay_rx, medium_tx.clone());
+    let mut relay = RelayNodeCoding::new(vec![node_a, node_c]);
+    let topology = Topology::new(node_b, node_b, vec![node_a, node_c], vec![node_a, node_c]);
+
+    let mut leaves: Vec<Leaf> = [node_a, node_c]
+        .into_iter()
+        .map(|id| {
+            let (tx, rx) = channel();
+            Leaf {
+                id,
+                tx,
+                channel: SimulatorChannel::new(rx, medium_tx.clone()),
+            }
+        })
+        .collect();
+
+    // NOTE: The natives are serialized up front, so only the relay is measured.
+    let natives: Vec<(PacketData, PacketData)> = (0..1024)
+        .map(|id| {
+            (
+                native(node_a, node_c, id, payload_size),
+                native(node_c, node_a, id, payload_size),
+            )
+        })
+        .collect();
+
+    let mut packets = 0;
+    let mut bytes = 0;
+    let start = Instant::now();
+
+    for (from_a, from_c) in natives.iter().cycle() {
+        if start.elapsed() > RUNTIME {
+            break;
+        }
+
+        for (sender, frame) in [(node_a, from_a), (node_c, from_c)] {
+            relay_tx.send((sender, frame.clone())).unwrap();
+            let packet = relay_channel.receive().unwrap();
+            relay.handle_rx(&packet, &topology).unwrap();
+        }
+
+        while let Some(packet) = relay.handle_tx(&topology).unwrap() {
+            relay_channel.transmit(&packet).unwrap();
+            relay.update_last_packet_send();
+        }
+
+        while let Ok(frame) = medium_rx.try_recv() {
+            forward(&frame, &leaves, fan_out);
+        }
+
+        // NOTE: Without acks, the retransmission queue of the relay fills up
+        // and it stops forwarding new packets.
+        for leaf in leaves.iter_mut() {
+            let mut acked = vec![];
+            while let Some(packet) = leaf.channel.receive() {
+                packets += 1;
+                bytes += packet.data().len() as u64;
+                if let CodingHeader::Encoded(infos) = packet.coding_header() {
+                    acked.extend(infos.iter().filter(|info| info.nexthop == leaf.id).cloned());
+                }
+            }
+            if acked.is_empty() {
+                continue;
+            }
+            relay_tx.send((leaf.id, ack(leaf.id, acked))).unwrap();
+            let packet = relay_channel.receive().unwrap();
+            relay.handle_rx(&packet, &topology).unwrap();
+        }
+    }
+
+    (packets, bytes, start.elapsed())
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>8} {:>14} {:>12}",
+        "payload", "fan-out", "packets/s", "MiB/s"
+    );
+
+    for payload_size in PAYLOAD_SIZES {
+        for (name, fan_out) in [("shared", FanOut::Shared), ("copied", FanOut::Copied)] {
+            let (packets, bytes, elapsed) = run(payload_size, fan_out);
+            let seconds = elapsed.as_secs_f64();
+            println!(
+                "{:>8} {:>8} {:>14.0} {:>12.2}",
+                payload_size,
+                name,
+                packets as f64 / seconds,
+                bytes as f64 / seconds / (1024.0 * 1024.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// Measures how fast packet data is XORed, when the relay codes natives and the
+// leaves decode them: the word-wise kernel in cope::packet::xor is compared to
+// the byte loop it replaced, for single XORs and for coding several natives.
+//
+// Run with: cargo bench --bench xor_throughput
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cope::packet::xor::{xor_into, xor_many};
+
+const PAYLOAD_SIZES: [usize; 4] = [64, 512, 1400, 2048];
+const RUNTIME: Duration = Duration::from_millis(500);
+const CODED_NATIVES: usize = 4;
+
+// NOTE: The XOR used before the word-wise kernel
+fn xor_bytes(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+fn xor_many_bytes(inputs: &[Vec<u8>]) -> Vec<u8> {
+    let len = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+    let mut out = vec![0; len];
+    for input in inputs {
+        xor_bytes(&mut out, input);
+    }
+    out
+}
+
+// NOTE: Returns the number of XORed bytes per second
+fn measure(bytes_per_run: usize, mut run: impl FnMut()) -> f64 {
+    let mut runs = 0;
+    let start = Instant::now();
+    while start.elapsed() < RUNTIME {
+        for _ in 0..64 {
+            run();
+        }
+        runs += 64;
+    }
+    (runs * bytes_per_run) as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>10} {:>14} {:>14} {:>8}",
+        "payload", "operation", "bytes MiB/s", "words MiB/s", "speedup"
+    );
+
+    for payload_size in PAYLOAD_SIZES {
+        let natives: Vec<Vec<u8>> = (0..CODED_NATIVES)
+            .map(|n| (0..payload_size).map(|i| (i * 7 + n) as u8).collect())
+            .collect();
+        let mut dst = vec![0xA5; payload_size];
+
+        let xor_into_bytes = measure(payload_size, || {
+            xor_bytes(black_box(&mut dst), black_box(&natives[0]));
+        });
+        let xor_into_words = measure(payload_size, || {
+            xor_into(black_box(&mut dst), black_box(&natives[0]));
+        });
+
+        let coded_size = payload_size * CODED_NATIVES;
+        let xor_many_bytes = measure(coded_size, || {
+            black_box(xor_many_bytes(black_box(&natives)));
+        });
+        let xor_many_words = measure(coded_size, || {
+            black_box(xor_many(black_box(&natives).iter().map(Vec::as_slice)));
+        });
+
+        for (name, bytes, words) in [
+            ("xor_into", xor_into_bytes, xor_into_words),
+            ("xor_many", xor_many_bytes, xor_many_words),
+        ] {
+            println!(
+                "{:>8} {:>10} {:>14.0} {:>14.0} {:>7.1}x",
+                payload_size,
+                name,
+                bytes / (1024.0 * 1024.0),
+                words / (1024.0 * 1024.0),
+                words / bytes
+            );
+        }
+    }
+}
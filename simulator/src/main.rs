@@ -5,6 +5,7 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use cope::config::CONFIG;
+use cope::pcap::{PcapWriter, SharedPcapWriter};
 use cope::stats::{Stats, StatsLogger};
 use cope::Node;
 use rand::Rng;
@@ -15,11 +16,34 @@ use simulator_stats_logger::SimulatorStatsLogger;
 mod simulator_channel;
 mod simulator_stats_logger;
 
+// NOTE: Selected with --capture per-node or --capture merged
+enum CaptureMode {
+    None,
+    PerNode,
+    Merged(SharedPcapWriter),
+}
+
+fn parse_capture_mode(run_id: u64) -> anyhow::Result<CaptureMode> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => Ok(CaptureMode::None),
+        ["--capture", "per-node"] => Ok(CaptureMode::PerNode),
+        ["--capture", "merged"] => {
+            let path = format!("./log/capture_{:X}.pcap", run_id);
+            Ok(CaptureMode::Merged(PcapWriter::create(&path)?.shared()))
+        }
+        _ => anyhow::bail!("Usage: simulator [--capture per-node|merged]"),
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     SimpleLogger::new()
         .with_level(log::LevelFilter::Off)
         .init()?;
 
+    let run_id = rand::thread_rng().gen::<u64>();
+    let capture_mode = parse_capture_mode(run_id)?;
+
     let (tx, rx) = channel();
     let mut node_channels = HashMap::new();
 
@@ -27,6 +51,7 @@ fn main() -> anyhow::Result<()> {
     let mut handles = vec![];
 
     let finished = Arc::new(AtomicBool::new(false));
+    let start = SystemTime::now();
 
     for id in node_ids.iter() {
         let (node_tx, node_rx) = channel();
@@ -51,12 +76,26 @@ fn main() -> anyhow::Result<()> {
         let bench_path = format!("./log/bench/log_{}", id);
         node.set_bench_log_path(&bench_path);
 
+        match &capture_mode {
+            CaptureMode::None => (),
+            CaptureMode::PerNode => {
+                let path = format!("./log/capture_{}_{:X}.pcap", id.unwrap(), run_id);
+                node.set_capture(PcapWriter::create(&path)?.shared());
+            }
+            CaptureMode::Merged(capture) => node.set_capture(capture.clone()),
+        }
+
+        // NOTE: A failed node just stops, like an ESP that lost power
+        let failure_time = CONFIG.get_failure_time_for(*id);
         let handle = std::thread::spawn({
             let finished_clone = finished.clone();
             move || loop {
                 if finished_clone.load(Ordering::SeqCst) {
                     break;
                 }
+                if failure_time.is_some_and(|t| start.elapsed().unwrap_or_default() > t) {
+                    break;
+                }
 
                 node.tick();
             }
@@ -64,7 +103,6 @@ fn main() -> anyhow::Result<()> {
         handles.push(handle);
     }
 
-    let start = SystemTime::now();
     let runtime = Duration::from_secs(10);
 
     loop {
@@ -78,24 +116,32 @@ fn main() -> anyhow::Result<()> {
             break;
         }
 
-        let packet = rx.recv().unwrap();
-        let sender = packet.sender();
+        let (sender, packet) = rx.recv().unwrap();
+        let failed = |id| CONFIG.get_failure_time_for(id).is_some_and(|t| elapsed > t);
+        if failed(sender) {
+            continue;
+        }
         for (id, node_tx) in node_channels.iter() {
-            if *id == sender {
+            if *id == sender || failed(*id) {
                 continue;
             }
 
-            if CONFIG.simulator_packet_loss > 0.0 {
+            let loss = match CONFIG.get_link_loss_for(sender, *id) {
+                Some(link_loss) => link_loss.loss_at(elapsed),
+                None => CONFIG.simulator_packet_loss,
+            };
+            if loss > 0.0 {
                 let r = rand::random::<f64>();
-                if r < CONFIG.simulator_packet_loss {
+                if r < loss {
                     log::info!("Dropping packet from {} to {}", sender, id);
                     continue;
                 }
             }
 
             // NOTE: Because the simulator channel is implemented using a multi-producer, single-consumer queue,
-            // we have to forward the packet to each node individually.
-            if let Err(e) = node_tx.send(packet.clone()) {
+            // we have to forward the packet to each node individually. This only clones
+            // a handle to the shared buffer, not the packet itself.
+            if let Err(e) = node_tx.send((sender, packet.clone())) {
                 panic!("{}", e);
             }
         }
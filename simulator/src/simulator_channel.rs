@@ -1,13 +1,21 @@
 use cope::channel::Channel;
-use cope::packet::Packet;
+use cope::packet::{Packet, PacketData};
+use cope_config::types::node_id::NodeID;
 use std::{
     error::Error,
     sync::mpsc::{Receiver, Sender},
 };
 
+// NOTE: Packets are passed around in their wire format, so the simulator
+// exercises the same serialization code as the ESP. The sender is passed
+// alongside, so the simulator does not need to parse packets to route them.
+// Every receiver gets a handle to the same buffer, and the data of received
+// packets is a slice of it.
+pub type SimulatorFrame = (NodeID, PacketData);
+
 pub struct SimulatorChannel {
-    rx: Receiver<Packet>,
-    tx: Sender<Packet>,
+    rx: Receiver<SimulatorFrame>,
+    tx: Sender<SimulatorFrame>,
 }
 
 // TODO: Figure out if this is needed
@@ -15,15 +23,15 @@ unsafe impl Send for SimulatorChannel {}
 unsafe impl Sync for SimulatorChannel {}
 
 impl SimulatorChannel {
-    pub fn new(rx: Receiver<Packet>, tx: Sender<Packet>) -> Self {
+    pub fn new(rx: Receiver<SimulatorFrame>, tx: Sender<SimulatorFrame>) -> Self {
         SimulatorChannel { rx, tx }
     }
 }
 
 impl Channel for SimulatorChannel {
     fn transmit(&mut self, packet: &Packet) -> Result<(), Box<dyn Error>> {
-        // FIXME: Figure out how to send without cloning
-        if let Err(e) = self.tx.send(packet.clone()) {
+        let serialized = PacketData::new(packet.serialize_into()?);
+        if let Err(e) = self.tx.send((packet.sender(), serialized)) {
             println!("{}", e);
         }
 
@@ -31,6 +39,9 @@ impl Channel for SimulatorChannel {
     }
 
     fn receive(&mut self) -> Option<Packet> {
-        self.rx.try_recv().ok()
+        let (_, buffer) = self.rx.try_recv().ok()?;
+        Packet::deserialize_shared(&buffer)
+            .map_err(|e| log::warn!("Could not decode packet: {}", e))
+            .ok()
     }
 }
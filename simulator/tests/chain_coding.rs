@@ -0,0 +1,135 @@
+// Runs the chain A - B - C - D from configs/chain_cfg.toml, once with and once
+// without coding: A and D send the same amount of packets to each other over
+// the relays B and C, which decode and re-code them on every hop. With coding,
+// the relays need fewer transmissions to deliver all of them.
+//
+// Run with: cargo test --test chain_coding
+
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::coding::leaf_node_coding::LeafNodeCoding;
+use cope::coding::relay_node_coding::RelayNodeCoding;
+use cope::packet::{CodingHeader, PacketBuilder};
+use cope::topology::Topology;
+use cope::traffic_generator::{TGStrategy, TrafficGenerator};
+use cope_config::types::node_id::NodeID;
+
+mod common;
+
+use common::{deliver, sim_node, SimulatorFrame};
+
+const PACKETS: usize = 100;
+const INTERVAL: Duration = Duration::from_millis(5);
+
+// NOTE: Generates the given amount of packets, one per interval
+struct CountStrategy {
+    left: usize,
+    last: Instant,
+}
+
+impl TGStrategy for CountStrategy {
+    fn generate(&mut self) -> Option<PacketBuilder> {
+        if self.left == 0 || self.last.elapsed() < INTERVAL {
+            return None;
+        }
+        self.left -= 1;
+        self.last = Instant::now();
+        Some(PacketBuilder::new().data_raw(vec![self.left as u8; 64]))
+    }
+}
+
+fn leaf(id: NodeID, target: NodeID) -> LeafNodeCoding {
+    let strategy = CountStrategy {
+        left: PACKETS,
+        last: Instant::now(),
+    };
+    LeafNodeCoding::new(TrafficGenerator::new(Box::new(strategy), vec![target], id))
+}
+
+// NOTE: Returns how many packets the relays sent, until A and D received all
+// packets of each other.
+fn run(use_coding: bool) -> usize {
+    let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+    let (medium, frames): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+
+    let mut nodes = [
+        sim_node(
+            Topology::new(a, b, vec![b], vec![d]),
+            Box::new(leaf(a, d)),
+            &medium,
+        ),
+        sim_node(
+            Topology::new(b, b, vec![a, c], vec![a, c])
+                .with_relays(vec![b, c])
+                .with_routes(vec![(d, c)]),
+            Box::new(RelayNodeCoding::new(vec![a, c]).with_coding(use_coding)),
+            &medium,
+        ),
+        sim_node(
+            Topology::new(c, b, vec![b, d], vec![b, d])
+                .with_relays(vec![b, c])
+                .with_routes(vec![(a, b)]),
+            Box::new(RelayNodeCoding::new(vec![b, d]).with_coding(use_coding)),
+            &medium,
+        ),
+        sim_node(
+            Topology::new(d, c, vec![c], vec![a]),
+            Box::new(leaf(d, a)),
+            &medium,
+        ),
+    ];
+
+    // NOTE: Natives decoded by A and D, by the leaf and the native's source and id
+    let mut received = HashSet::new();
+    let mut relay_sent = 0;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while received.len() < 2 * PACKETS && Instant::now() < deadline {
+        for node in nodes.iter_mut() {
+            let id = node.id();
+            let is_leaf = id == a || id == d;
+            if let Some((packet, Ok(Some(_)))) = node.receive() {
+                if let CodingHeader::Encoded(infos) = packet.coding_header() {
+                    let wanted = infos.iter().find(|info| is_leaf && info.nexthop == id);
+                    if let Some(info) = wanted {
+                        received.insert((id, info.source, info.id));
+                    }
+                }
+            }
+            if let Some(packet) = node.transmit() {
+                let is_data = matches!(packet.coding_header(), CodingHeader::Encoded(_));
+                relay_sent += (is_data && !is_leaf) as usize;
+            }
+        }
+
+        deliver(&frames, nodes.iter(), |sender, node, _| {
+            !node.topology.can_receive_from(sender)
+        });
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(
+        received.len(),
+        2 * PACKETS,
+        "A and D should receive all packets"
+    );
+    relay_sent
+}
+
+#[test]
+fn test_chain_coding_gain() {
+    let without_coding = run(false);
+    let with_coding = run(true);
+    println!(
+        "relays sent {} packets without coding, {} with coding",
+        without_coding, with_coding
+    );
+
+    // NOTE: Both relays forward every packet once without coding
+    assert!(without_coding >= 4 * PACKETS);
+    assert!(
+        with_coding * 4 < without_coding * 3,
+        "Coding should save at least a quarter of the relay transmissions"
+    );
+}
@@ -0,0 +1,99 @@
+// Harness shared by the simulator tests: every node runs a coding on its own
+// SimulatorChannel, and the frames they transmit are passed on to the other
+// nodes, like the simulator does.
+
+// NOTE: Not every test uses every helper
+#![allow(dead_code)]
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use cope::channel::Channel;
+use cope::coding::{CodingError, CodingStrategy};
+use cope::packet::{Packet, PacketBuilder, PacketData};
+use cope::topology::Topology;
+use cope::traffic_generator::{TGStrategy, TrafficGenerator};
+use cope_config::types::node_id::NodeID;
+
+#[path = "../../src/simulator_channel.rs"]
+mod simulator_channel;
+
+pub use simulator_channel::{SimulatorChannel, SimulatorFrame};
+
+// NOTE: Generates a single packet with the given data
+pub struct OnceStrategy(Option<Vec<u8>>);
+
+impl TGStrategy for OnceStrategy {
+    fn generate(&mut self) -> Option<PacketBuilder> {
+        self.0
+            .take()
+            .map(|data| PacketBuilder::new().data_raw(data))
+    }
+}
+
+// NOTE: A generator of id, that sends a single packet with data to target
+pub fn once(id: NodeID, target: NodeID, data: Vec<u8>) -> TrafficGenerator {
+    TrafficGenerator::new(Box::new(OnceStrategy(Some(data))), vec![target], id)
+}
+
+pub struct SimNode {
+    pub topology: Topology,
+    pub coding: Box<dyn CodingStrategy + Send>,
+    pub channel: SimulatorChannel,
+    pub tx: Sender<SimulatorFrame>,
+}
+
+pub fn sim_node(
+    topology: Topology,
+    coding: Box<dyn CodingStrategy + Send>,
+    medium: &Sender<SimulatorFrame>,
+) -> SimNode {
+    let (tx, rx) = channel();
+    SimNode {
+        topology,
+        coding,
+        channel: SimulatorChannel::new(rx, medium.clone()),
+        tx,
+    }
+}
+
+impl SimNode {
+    pub fn id(&self) -> NodeID {
+        self.topology.id()
+    }
+
+    // NOTE: Hands the next received packet to the coding, and returns it with
+    // the result of the coding
+    pub fn receive(&mut self) -> Option<(Packet, Result<Option<PacketData>, CodingError>)> {
+        let packet = self.channel.receive()?;
+        let result = self.coding.handle_rx(&packet, &self.topology);
+        Some((packet, result))
+    }
+
+    // NOTE: Transmits the next packet of the coding, if it has one
+    pub fn transmit(&mut self) -> Option<Packet> {
+        let Ok(Some(packet)) = self.coding.handle_tx(&self.topology) else {
+            return None;
+        };
+        self.channel.transmit(&packet).unwrap();
+        self.coding.update_last_packet_send();
+        Some(packet)
+    }
+}
+
+// NOTE: Passes the frames on the medium on to all other nodes, except the ones
+// is_lost returns true for, by the sender of the frame and the receiving node.
+pub fn deliver<'a>(
+    frames: &Receiver<SimulatorFrame>,
+    nodes: impl Iterator<Item = &'a SimNode> + Clone,
+    mut is_lost: impl FnMut(NodeID, &SimNode, &Packet) -> bool,
+) {
+    while let Ok((sender, frame)) = frames.try_recv() {
+        let packet = Packet::deserialize_from(frame.as_slice()).unwrap();
+        for node in nodes.clone() {
+            if node.id() == sender || is_lost(sender, node, &packet) {
+                continue;
+            }
+            node.tx.send((sender, frame.clone())).unwrap();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Runs the diamond A - {B, C} - D from configs/etx_cfg.toml with ETX routing:
+// A and D send to each other over the relay with the better path. At first that
+// is B, until the link between B and D degrades mid-run, then the adverts have
+// to move the routes of A and D over to C, like cope::Node does with them.
+//
+// Run with: cargo test --test etx_convergence
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::channel::Channel;
+use cope::coding::leaf_node_coding::LeafNodeCoding;
+use cope::coding::relay_node_coding::RelayNodeCoding;
+use cope::coding::CodingStrategy;
+use cope::packet::{CodingHeader, Packet};
+use cope::routing::Router;
+use cope::topology::Topology;
+use cope::traffic_generator::TrafficGenerator;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+use common::{deliver, sim_node, SimNode, SimulatorFrame};
+
+const INTERVAL: Duration = Duration::from_millis(50);
+const DEGRADE_AFTER: Duration = Duration::from_secs(1);
+const RUNTIME: Duration = Duration::from_secs(3);
+
+// NOTE: A node with the Router of cope::Node
+struct RoutedNode {
+    node: SimNode,
+    router: Router,
+}
+
+impl RoutedNode {
+    fn new(
+        topology: Topology,
+        coding: Box<dyn CodingStrategy + Send>,
+        medium: &Sender<SimulatorFrame>,
+    ) -> Self {
+        RoutedNode {
+            router: Router::new(topology.id(), relays(), INTERVAL),
+            node: sim_node(topology, coding, medium),
+        }
+    }
+
+    // NOTE: Same as Node::update_routes
+    fn update_routes(&mut self) {
+        if let Some(routes) = self.router.take_routes() {
+            self.node.topology.set_routes(routes);
+        }
+    }
+
+    fn tick(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(advert) = self.router.handle_tx(now) {
+            self.update_routes();
+            self.node.channel.transmit(&advert).unwrap();
+            return None;
+        }
+
+        let mut received = None;
+        if let Some(packet) = self.node.channel.receive() {
+            match packet.coding_header() {
+                CodingHeader::Routing(advert) => {
+                    self.router.handle_rx(packet.sender(), advert, now);
+                    self.update_routes();
+                }
+                _ => {
+                    let node = &mut self.node;
+                    if let Ok(Some(_)) = node.coding.handle_rx(&packet, &node.topology) {
+                        received = Some(packet);
+                    }
+                }
+            }
+        }
+        self.node.transmit();
+        received
+    }
+}
+
+fn relays() -> Vec<NodeID> {
+    vec![NodeID::new('B'), NodeID::new('C')]
+}
+
+fn leaf(id: NodeID, target: NodeID, medium: &Sender<SimulatorFrame>) -> RoutedNode {
+    let [b, c] = ['B', 'C'].map(NodeID::new);
+    let periodic = TrafficGeneratorType::Periodic(Duration::from_millis(20));
+    let generator = TrafficGenerator::from_tg_type(periodic, vec![target], id);
+    let topology = Topology::new(id, b, vec![b, c], vec![target]).with_relays(vec![b, c]);
+    RoutedNode::new(topology, Box::new(LeafNodeCoding::new(generator)), medium)
+}
+
+fn relay(id: NodeID, medium: &Sender<SimulatorFrame>) -> RoutedNode {
+    let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+    let topology = Topology::new(id, b, vec![a, d], vec![a, d]).with_relays(vec![b, c]);
+    RoutedNode::new(topology, Box::new(RelayNodeCoding::new(vec![a, d])), medium)
+}
+
+#[test]
+fn test_etx_convergence() {
+    let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+    let (medium, frames): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let mut nodes = [
+        leaf(a, d, &medium),
+        relay(b, &medium),
+        relay(c, &medium),
+        leaf(d, a, &medium),
+    ];
+    let links = [(a, b), (a, c), (b, d), (c, d)];
+
+    // NOTE: Same losses as the simulator_link_loss of etx_cfg.toml
+    let start = Instant::now();
+    let loss = |link: (NodeID, NodeID), now: Instant| match link {
+        (x, y) if [(b, d), (d, b)].contains(&(x, y)) && now > start + DEGRADE_AFTER => 0.7,
+        (x, y) if x == c || y == c => 0.2,
+        _ => 0.0,
+    };
+
+    let mut converged = None;
+    let mut over_b = 0;
+    let mut over_c = 0;
+    while start.elapsed() < RUNTIME {
+        let now = Instant::now();
+        for node in nodes.iter_mut() {
+            let received = node.tick(now);
+            let Some(packet) = received.filter(|_| node.node.id() == d) else {
+                continue;
+            };
+            match packet.sender() {
+                sender if sender == b => over_b += 1,
+                sender if sender == c && converged.is_some() => over_c += 1,
+                _ => (),
+            }
+        }
+
+        let is_switched = nodes[0].node.topology.nexthop_for_target(d) == c
+            && nodes[3].node.topology.nexthop_for_target(a) == c;
+        if converged.is_none() && is_switched && now > start + DEGRADE_AFTER {
+            converged = Some(now - start - DEGRADE_AFTER);
+        }
+
+        deliver(&frames, nodes.iter().map(|n| &n.node), |sender, node, _| {
+            let receiver = node.id();
+            let is_linked =
+                links.contains(&(sender, receiver)) || links.contains(&(receiver, sender));
+            !is_linked || rng.gen_bool(loss((sender, receiver), now))
+        });
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    println!(
+        "switched to C {:?} after the degradation, D received {} packets over B and {} over C",
+        converged, over_b, over_c
+    );
+
+    let converged = converged.expect("A and D should switch their routes to C");
+    assert!(converged < Duration::from_secs(1));
+    assert!(over_b > 0, "D should receive over B before the degradation");
+    assert!(over_c > 0, "D should receive over C after the switch");
+}
@@ -0,0 +1,127 @@
+// Runs the X topology with 10% packet loss, once with and once without NACKs:
+// A sends to D, C to E, D to A and E to C through the relay B, and C and D
+// overhear A and E. Coded packets that a leaf can't decode, because it missed
+// the overheard native, are recovered much faster, if the leaf nacks them.
+//
+// Run with: cargo test --test nack_recovery
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::coding::leaf_node_coding::LeafNodeCoding;
+use cope::coding::relay_node_coding::RelayNodeCoding;
+use cope::packet::{CodingHeader, CodingInfo, Packet, PacketID};
+use cope::topology::Topology;
+use cope::traffic_generator::TrafficGenerator;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+use common::{deliver, sim_node, SimNode, SimulatorFrame};
+
+const LOSS: f64 = 0.1;
+const RUNTIME: Duration = Duration::from_secs(3);
+
+// NOTE: Natives are identified by their source and id, per leaf that wants them
+type Wanted = (NodeID, NodeID, PacketID);
+
+fn wanted(id: NodeID, info: &CodingInfo) -> Wanted {
+    (id, info.source, info.id)
+}
+
+fn leaf(id: NodeID, target: NodeID, use_nacks: bool) -> LeafNodeCoding {
+    let periodic = TrafficGeneratorType::Periodic(Duration::from_millis(10));
+    let generator = TrafficGenerator::from_tg_type(periodic, vec![target], id);
+    LeafNodeCoding::new(generator).with_nacks(use_nacks)
+}
+
+// NOTE: Returns how many coded packets the leaves could not decode right away,
+// and the average time until they acked them after all.
+fn run(use_nacks: bool) -> (usize, Duration) {
+    let [a, b, c, d, e] = ['A', 'B', 'C', 'D', 'E'].map(NodeID::new);
+    let (medium, frames): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let leaves = [
+        (a, vec![b], d),
+        (c, vec![b, a], e),
+        (d, vec![b, e], a),
+        (e, vec![b], c),
+    ];
+    let mut nodes: Vec<SimNode> = leaves
+        .into_iter()
+        .map(|(id, rxlist, target)| {
+            let coding = leaf(id, target, use_nacks);
+            let topology = Topology::new(id, b, rxlist, vec![target]);
+            sim_node(topology, Box::new(coding), &medium)
+        })
+        .collect();
+    let relay = RelayNodeCoding::new(vec![a, c, d, e]);
+    let topology = Topology::new(b, b, vec![a, c, d, e], vec![a, c, d, e]);
+    nodes.push(sim_node(topology, Box::new(relay), &medium));
+
+    // NOTE: Coded packets a leaf could not decode, by the native it wants
+    let mut undecodable: HashMap<Wanted, Instant> = HashMap::new();
+    let mut recovered = vec![];
+    let start = Instant::now();
+    while start.elapsed() < RUNTIME {
+        for node in nodes.iter_mut() {
+            if let Some((packet, Err(_))) = node.receive() {
+                if let CodingHeader::Encoded(infos) = packet.coding_header() {
+                    if let Some(info) = infos.iter().find(|info| info.nexthop == node.id()) {
+                        undecodable
+                            .entry(wanted(node.id(), info))
+                            .or_insert_with(Instant::now);
+                    }
+                }
+            }
+            if let Some(packet) = node.transmit() {
+                acked(&packet, &mut undecodable, &mut recovered);
+            }
+        }
+
+        deliver(&frames, nodes.iter(), |sender, node, _| {
+            rng.gen_bool(LOSS) || !node.topology.can_receive_from(sender)
+        });
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let count = recovered.len();
+    let total: Duration = recovered.into_iter().sum();
+    (count, total / count.max(1) as u32)
+}
+
+// NOTE: Leaves ack every packet they decoded with their next packet
+fn acked(
+    packet: &Packet,
+    undecodable: &mut HashMap<Wanted, Instant>,
+    recovered: &mut Vec<Duration>,
+) {
+    for ack in packet.ack_header() {
+        for info in ack.packets() {
+            if let Some(since) = undecodable.remove(&wanted(packet.sender(), info)) {
+                recovered.push(since.elapsed());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_nacks_speed_up_recovery() {
+    let (without_count, without_nacks) = run(false);
+    let (with_count, with_nacks) = run(true);
+    println!(
+        "recovered {} packets in {:?} without NACKs, {} in {:?} with NACKs",
+        without_count, without_nacks, with_count, with_nacks
+    );
+
+    assert!(without_count > 0 && with_count > 0);
+    assert!(
+        with_nacks * 3 < without_nacks * 2,
+        "NACKs should cut the recovery time by at least a third"
+    );
+}
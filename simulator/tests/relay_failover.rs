@@ -0,0 +1,151 @@
+// Sends traffic from A to D, which only reach each other over a relay. B and C
+// are both candidates, B is elected and then fails mid-run, so the nodes have to
+// elect C and switch roles, like cope::Node does with a LowestId election.
+//
+// Run with: cargo test --test relay_failover
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::channel::Channel;
+use cope::coding;
+use cope::discovery::Discovery;
+use cope::election::RelayElection;
+use cope::packet::{CodingHeader, Packet};
+use cope::topology::Topology;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+
+mod common;
+
+use common::{deliver, sim_node, SimNode, SimulatorFrame};
+
+const INTERVAL: Duration = Duration::from_millis(20);
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+// NOTE: A node with the Discovery and RelayElection of cope::Node
+struct ElectedNode {
+    node: SimNode,
+    discovery: Discovery,
+    election: RelayElection,
+    tgt: TrafficGeneratorType,
+}
+
+impl ElectedNode {
+    fn new(id: NodeID, tgt: TrafficGeneratorType, medium: &Sender<SimulatorFrame>) -> Self {
+        let candidates = vec![NodeID::new('B'), NodeID::new('C')];
+        let topology = Topology::new(id, candidates[0], vec![], vec![]);
+        let coding = coding::from_role(id, &topology, tgt);
+        ElectedNode {
+            node: sim_node(topology, coding, medium),
+            discovery: Discovery::new(id, candidates.clone(), INTERVAL, TIMEOUT),
+            election: RelayElection::new(id, candidates, TIMEOUT),
+            tgt,
+        }
+    }
+
+    // NOTE: Same as Node::update_neighbors and Node::update_relay
+    fn update(&mut self, now: Instant) {
+        let node = &mut self.node;
+        if let Some((rxlist, txlist)) = self.discovery.take_neighbors() {
+            node.topology.set_neighbors(rxlist, txlist);
+            node.coding.update_neighbors(&node.topology);
+        }
+        self.election.update(&self.discovery.neighbors(), now);
+        if let Some(relay) = self.election.take_relay() {
+            let was_relay = node.topology.is_relay();
+            node.topology.set_relays(vec![relay]);
+            if node.topology.is_relay() != was_relay {
+                let queued = node.coding.take_queued();
+                node.coding = coding::from_role(node.id(), &node.topology, self.tgt);
+                node.coding.requeue(queued, &node.topology);
+            } else {
+                node.coding.update_relay(&node.topology);
+            }
+        }
+    }
+
+    fn tick(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(beacon) = self.discovery.handle_tx(now) {
+            self.update(now);
+            self.node.channel.transmit(&beacon).unwrap();
+            return None;
+        }
+
+        let mut received = None;
+        if let Some(packet) = self.node.channel.receive() {
+            match packet.coding_header() {
+                CodingHeader::Beacon(neighbors) => {
+                    self.discovery.handle_rx(packet.sender(), neighbors, now);
+                    self.update(now);
+                }
+                _ => {
+                    let node = &mut self.node;
+                    if let Ok(Some(_)) = node.coding.handle_rx(&packet, &node.topology) {
+                        received = Some(packet);
+                    }
+                }
+            }
+        }
+        self.node.transmit();
+        received
+    }
+}
+
+#[test]
+fn test_relay_failover() {
+    let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(NodeID::new);
+    let (medium, frames): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+
+    let periodic = TrafficGeneratorType::Periodic(INTERVAL);
+    let mut nodes = [
+        ElectedNode::new(a, periodic, &medium),
+        ElectedNode::new(b, TrafficGeneratorType::None, &medium),
+        ElectedNode::new(c, TrafficGeneratorType::None, &medium),
+        ElectedNode::new(d, TrafficGeneratorType::None, &medium),
+    ];
+
+    let start = Instant::now();
+    let failure = start + Duration::from_millis(400);
+    let deadline = start + Duration::from_secs(3);
+    let mut before_failure = 0;
+    let mut after_failover = 0;
+    while after_failover < 5 && Instant::now() < deadline {
+        let now = Instant::now();
+        let failed = |id| id == b && now > failure;
+        for node in nodes.iter_mut() {
+            if failed(node.node.id()) {
+                continue;
+            }
+            let received = node.tick(now);
+            let Some(packet) = received.filter(|_| node.node.id() == d) else {
+                continue;
+            };
+            match packet.sender() {
+                sender if sender == b => before_failure += 1,
+                sender if sender == c && now > failure => after_failover += 1,
+                _ => (),
+            }
+        }
+
+        deliver(&frames, nodes.iter().map(|n| &n.node), |sender, node, _| {
+            let receiver = node.id();
+            let is_lost = [(a, d), (d, a)].contains(&(sender, receiver));
+            is_lost || failed(sender) || failed(receiver)
+        });
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert!(
+        before_failure > 0,
+        "D should receive over B before it fails"
+    );
+    assert!(
+        after_failover >= 5,
+        "D should receive over C after B failed"
+    );
+    assert!(nodes[2].node.topology.is_relay());
+    for node in [&nodes[0], &nodes[2], &nodes[3]] {
+        assert!(!node.node.topology.is_relay_node(b));
+    }
+}
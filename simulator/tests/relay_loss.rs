@@ -0,0 +1,86 @@
+// Sends a single packet from A to C over the relay B, with frames passed around
+// like in the simulator. The first coded frame from B to C is lost, and so are
+// all retransmissions of A, so C can only get the packet, if B retransmits it.
+//
+// Run with: cargo test --test relay_loss
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use cope::coding::leaf_node_coding::LeafNodeCoding;
+use cope::coding::relay_node_coding::RelayNodeCoding;
+use cope::config::CONFIG;
+use cope::packet::{CodingHeader, PacketData};
+use cope::topology::Topology;
+use cope::traffic_generator::TrafficGenerator;
+use cope_config::types::node_id::NodeID;
+use cope_config::types::traffic_generator_type::TrafficGeneratorType;
+
+mod common;
+
+use common::{deliver, once, sim_node, SimulatorFrame};
+
+#[test]
+fn test_relay_recovers_lost_packet() {
+    let [a, b, c] = ['A', 'B', 'C'].map(NodeID::new);
+    let data = vec![0xAA; 32];
+    let (medium, frames): (Sender<SimulatorFrame>, Receiver<SimulatorFrame>) = channel();
+
+    let leaf_a = LeafNodeCoding::new(once(a, c, data.clone()));
+    let none = TrafficGenerator::from_tg_type(TrafficGeneratorType::None, vec![a], c);
+    let leaf_c = LeafNodeCoding::new(none);
+    let relay = RelayNodeCoding::new(vec![a, c]);
+    let mut nodes = [
+        sim_node(
+            Topology::new(a, b, vec![b], vec![c]),
+            Box::new(leaf_a),
+            &medium,
+        ),
+        sim_node(
+            Topology::new(b, b, vec![a, c], vec![a, c]),
+            Box::new(relay),
+            &medium,
+        ),
+        sim_node(
+            Topology::new(c, b, vec![b], vec![a]),
+            Box::new(leaf_c),
+            &medium,
+        ),
+    ];
+
+    let mut frames_from_a = 0;
+    let mut coded_from_b = 0;
+    let mut received = None;
+    let deadline = Instant::now() + 2 * CONFIG.round_trip_time + Duration::from_secs(1);
+    while received.is_none() && Instant::now() < deadline {
+        for node in nodes.iter_mut() {
+            if let Some((packet, result)) = node.receive() {
+                let is_coded = matches!(packet.coding_header(), CodingHeader::Encoded(_));
+                if let (true, true, Ok(Some(data))) = (node.id() == c, is_coded, result) {
+                    received = Some(data);
+                }
+            }
+            if let Some(packet) = node.transmit() {
+                let is_coded = matches!(packet.coding_header(), CodingHeader::Encoded(_));
+                frames_from_a += (node.id() == a) as usize;
+                coded_from_b += (node.id() == b && is_coded) as usize;
+            }
+        }
+
+        deliver(&frames, nodes.iter(), |sender, node, packet| {
+            let receiver = node.id();
+            let is_coded = matches!(packet.coding_header(), CodingHeader::Encoded(_));
+            (sender == a && frames_from_a > 1)
+                || (sender == b && receiver == c && is_coded && coded_from_b == 1)
+                || !node.topology.can_receive_from(sender)
+        });
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(received, Some(PacketData::new(data)));
+    assert!(frames_from_a >= 1);
+    assert!(
+        coded_from_b >= 2,
+        "C can only receive a retransmission of B"
+    );
+}